@@ -0,0 +1,11 @@
+use shared::admin_panel::AuditRecord;
+
+pub(crate) const AUDIT_PAGE_SIZE: u32 = 50;
+
+#[derive(Default)]
+pub(crate) struct AuditHolder {
+    pub(crate) take: u32,
+    pub(crate) skip: u32,
+    pub(crate) total: u32,
+    pub(crate) records: Vec<AuditRecord>,
+}
@@ -0,0 +1,62 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use shared::admin_panel::{AdminUser, TgLoginData};
+
+static TG_AUTH_RESULT: &str = "#tgAuthResult=";
+
+#[derive(Default)]
+pub(crate) struct AuthHolder {
+    pub(crate) origin: String,
+    /// Login data received from Telegram redirect, sent to server on `AuthRequired`
+    pub(crate) pending_login: Option<TgLoginData>,
+    pub(crate) bot_id: Option<u64>,
+    pub(crate) user: Option<AdminUser>,
+    pub(crate) error: Option<String>,
+    pub(crate) admin_users: Vec<AdminUser>,
+}
+
+impl AuthHolder {
+    pub fn new(origin: String, pending_login: Option<TgLoginData>) -> Self {
+        Self {
+            origin,
+            pending_login,
+            ..Default::default()
+        }
+    }
+
+    /// Telegram OAuth page, which redirects back with `#tgAuthResult=...`
+    pub fn login_url(&self) -> Option<String> {
+        let origin = url_encode(&self.origin);
+
+        self.bot_id.map(|bot_id| {
+            format!(
+                "https://oauth.telegram.org/auth?bot_id={bot_id}&origin={origin}&return_to={origin}"
+            )
+        })
+    }
+}
+
+/// Parses `#tgAuthResult=...` left in the page url by Telegram login redirect
+pub fn parse_tg_auth_result(hash: &str) -> Option<TgLoginData> {
+    let encoded = hash
+        .strip_prefix(TG_AUTH_RESULT)?
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_");
+
+    let json = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+
+    serde_json::from_slice(&json).ok()
+}
+
+fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,18 @@
+use shared::admin_panel::DashboardSummary;
+
+/// Periods of the dashboard charts, in days
+pub(crate) const DASHBOARD_PERIODS: [u32; 4] = [7, 14, 30, 90];
+
+pub(crate) struct DashboardHolder {
+    pub(crate) days: u32,
+    pub(crate) summary: Option<DashboardSummary>,
+}
+
+impl Default for DashboardHolder {
+    fn default() -> Self {
+        Self {
+            days: 14,
+            summary: None,
+        }
+    }
+}
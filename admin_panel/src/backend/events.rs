@@ -1,6 +1,6 @@
+use crate::backend::audit::AUDIT_PAGE_SIZE;
 use crate::backend::notification::{FileUploadState, Notification};
 use crate::backend::{Backend, BackendCommand, FrontendEvent, Screen};
-use crate::frontend::easy_mark::DEFAULT_CODE;
 use log::{log, Level};
 use shared::admin_panel::ClientPacket;
 use uuid::Uuid;
@@ -40,22 +40,31 @@ impl Backend {
 
                 FrontendEvent::RequestOpenScreen(new_screen) => match new_screen {
                     Screen::Dashboard => {
-                        todo!()
+                        self.send_packet(ClientPacket::Dashboard {
+                            days: self.dashboard_holder.days,
+                        });
                     }
-                    Screen::PatchNotes => {
-                        //TODO: add take and skip!
-                        self.send_packet(ClientPacket::PatchNotes { take: 10, skip: 0 });
+                    Screen::PatchNotes { skip } => {
+                        self.patch_note_holder.query.skip = skip;
+
+                        self.send_packet(ClientPacket::PatchNotes(
+                            self.patch_note_holder.query.clone(),
+                        ));
                     }
                     Screen::EditPatchNote { id } => {
                         if let Some(id) = id {
                             self.send_packet(ClientPacket::RequestEditPatchNote { id });
                         } else {
-                            res.push(BackendCommand::OpenPatchNote {
-                                id: None,
-                                data: DEFAULT_CODE.to_string(),
-                            })
+                            res.push(BackendCommand::OpenPatchNote(None))
                         }
                     }
+                    Screen::PatchNoteHistory { id } => {
+                        if self.patch_note_holder.history_id != id {
+                            self.patch_note_holder.compared = None;
+                        }
+
+                        self.send_packet(ClientPacket::PatchNoteRevisions { id });
+                    }
                     Screen::Files { dir } => {
                         log!(Level::Debug, "Navigate to |{}|", dir);
 
@@ -64,7 +73,26 @@ impl Backend {
                         });
                     }
                     Screen::Logs => {
-                        self.send_packet(ClientPacket::Logs);
+                        self.send_packet(ClientPacket::Logs {
+                            query: self.log_holder.query.clone(),
+                            subscribe: self.log_holder.live,
+                        });
+                    }
+                    Screen::Audit { skip } => {
+                        self.send_packet(ClientPacket::AuditLog {
+                            take: AUDIT_PAGE_SIZE,
+                            skip,
+                        });
+                    }
+                    Screen::AdminUsers => {
+                        self.send_packet(ClientPacket::AdminUsers);
+                    }
+                    Screen::Servers => res.push(BackendCommand::OpenServers),
+                    Screen::Trash => self.send_packet(ClientPacket::Trash),
+                    Screen::Maintenance => {
+                        self.send_packet(ClientPacket::LastScrubReport);
+
+                        res.push(BackendCommand::OpenMaintenance)
                     }
                 },
 
@@ -75,16 +103,57 @@ impl Backend {
                     })
                 }
 
-                FrontendEvent::SavePatchNote { id, data } => {
+                FrontendEvent::SavePatchNote {
+                    id,
+                    title,
+                    data,
+                    translations,
+                    publish_at,
+                } => {
                     if let Some(id) = id {
-                        self.send_packet(ClientPacket::SavePatchNote { id, data })
+                        self.send_packet(ClientPacket::SavePatchNote {
+                            id,
+                            title,
+                            data,
+                            translations,
+                            publish_at,
+                        })
                     } else {
-                        self.send_packet(ClientPacket::AddPatchNote { data })
+                        self.send_packet(ClientPacket::AddPatchNote {
+                            title,
+                            data,
+                            translations,
+                            publish_at,
+                        })
                     }
                 }
 
+                FrontendEvent::SetPatchNoteStatus { id, status } => {
+                    self.send_packet(ClientPacket::SetPatchNoteStatus { id, status })
+                }
+
                 FrontendEvent::DeletePatchNote { id } => {
-                    self.send_packet(ClientPacket::DeletePatchNote { id })
+                    self.send_packet(ClientPacket::DeletePatchNote { id });
+
+                    self.patch_note_holder.set_deleted(id, true);
+                }
+
+                FrontendEvent::RestorePatchNote { id } => {
+                    self.send_packet(ClientPacket::RestorePatchNote { id });
+
+                    self.patch_note_holder.set_deleted(id, false);
+                }
+
+                FrontendEvent::UploadMedia { name, data } => {
+                    self.send_packet(ClientPacket::UploadMedia { name, data })
+                }
+
+                FrontendEvent::RequestPatchNoteRevision { revision } => {
+                    self.send_packet(ClientPacket::PatchNoteRevision { revision })
+                }
+
+                FrontendEvent::RestorePatchNoteRevision { revision } => {
+                    self.send_packet(ClientPacket::RestorePatchNoteRevision { revision })
                 }
 
                 FrontendEvent::SkipFileHashCheck { dir, name } => {
@@ -99,6 +168,66 @@ impl Backend {
                         name,
                     })
                 }
+
+                FrontendEvent::CreateAdminUser { name, tg_user } => {
+                    self.send_packet(ClientPacket::CreateAdminUser { name, tg_user })
+                }
+
+                FrontendEvent::SubscribeLogs(subscribe) => self.send_packet(ClientPacket::Logs {
+                    query: self.log_holder.query.clone(),
+                    subscribe,
+                }),
+
+                FrontendEvent::RequestStatistics { days } => {
+                    self.dashboard_holder.days = days;
+
+                    self.send_packet(ClientPacket::Statistics { days })
+                }
+
+                FrontendEvent::LinkTelegram { user_id, tg_user } => {
+                    self.send_packet(ClientPacket::LinkTelegram { user_id, tg_user })
+                }
+
+                FrontendEvent::AddServer { name, url } => {
+                    res.push(match self.servers.add(&name, &url) {
+                        Ok(_) => BackendCommand::ServerAdded,
+                        Err(e) => BackendCommand::AddServerFailed(e),
+                    })
+                }
+
+                FrontendEvent::RemoveServer { url } => self.servers.remove(&url),
+
+                FrontendEvent::SelectServer { url } => self.servers.select(&url),
+
+                FrontendEvent::CollectGarbage { dry_run } => {
+                    self.maintenance.gc_running = true;
+
+                    self.send_packet(ClientPacket::CollectGarbage { dry_run })
+                }
+
+                FrontendEvent::Move { from, to, folder } => {
+                    let to = to.trim().trim_matches('/').to_string();
+
+                    self.send_packet(if folder {
+                        ClientPacket::MoveFolder { from, to }
+                    } else {
+                        ClientPacket::MoveFile { from, to }
+                    })
+                }
+
+                FrontendEvent::RestoreFromTrash(items) => {
+                    self.send_packet(ClientPacket::RestoreFromTrash { items })
+                }
+
+                FrontendEvent::PurgeFromTrash(items) => {
+                    self.send_packet(ClientPacket::PurgeFromTrash { items })
+                }
+
+                FrontendEvent::Scrub { quarantine } => {
+                    self.maintenance.scrub_running = true;
+
+                    self.send_packet(ClientPacket::Scrub { quarantine })
+                }
             }
         }
 
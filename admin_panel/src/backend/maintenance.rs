@@ -0,0 +1,10 @@
+use shared::admin_panel::{GcReport, ScrubReport};
+
+#[derive(Default)]
+pub(crate) struct MaintenanceHolder {
+    pub(crate) gc_report: Option<GcReport>,
+    /// Collection was requested and its report did not come yet
+    pub(crate) gc_running: bool,
+    pub(crate) scrub_report: Option<ScrubReport>,
+    pub(crate) scrub_running: bool,
+}
@@ -1,23 +1,68 @@
+use crate::backend::audit::AuditHolder;
+use crate::backend::auth::AuthHolder;
+use crate::backend::dashboard::DashboardHolder;
 use crate::backend::file_info_holder::FileInfoHolder;
+use crate::backend::maintenance::MaintenanceHolder;
 use crate::backend::network::Network;
 use crate::backend::notification::Notification;
 use crate::backend::patch_note::PatchNoteHolder;
+use crate::backend::servers::ServerHolder;
+use crate::backend::trash::TrashHolder;
 use log::{log, Level};
-use shared::admin_panel::{ClientPacket, Log, LogHolder, LogLevel, ServerPacket};
+use shared::admin_panel::{
+    ClientPacket, Locale, Log, LogHolder, LogLevel, PatchNote, PatchNoteStatus, ServerPacket,
+    TgLoginData, TgUser, TrashItem,
+};
+use std::collections::BTreeMap;
 use std::sync::mpsc::{channel, Receiver};
 
+pub(crate) mod audit;
+pub(crate) mod auth;
+pub(crate) mod dashboard;
 pub(crate) mod events;
 pub(crate) mod file_info_holder;
+pub(crate) mod maintenance;
 pub(crate) mod network;
 pub(crate) mod notification;
 mod packet_handler;
-mod patch_note;
+pub(crate) mod patch_note;
+pub(crate) mod servers;
+pub(crate) mod trash;
 
 pub enum BackendCommand {
-    OpenFileObserve { dir: String },
+    OpenDashboard,
+    OpenFileObserve {
+        dir: String,
+    },
     OpenLogs,
     OpenPatchNotes,
-    OpenPatchNote { id: Option<u32>, data: String },
+    /// `None` opens new patch note
+    OpenPatchNote(Option<PatchNote>),
+    OpenPatchNoteHistory,
+    /// Appends the uploaded image to the edited patch note
+    InsertMedia {
+        name: String,
+        url: String,
+    },
+    MediaUploadFailed {
+        name: String,
+        reason: String,
+    },
+    OpenAudit,
+    OpenLogin,
+    LoggedIn,
+    OpenAdminUsers,
+    OpenServers,
+    OpenMaintenance,
+    OpenTrash,
+    ServerAdded,
+    AddServerFailed(String),
+    MoveFailed {
+        from: String,
+        to: String,
+        folder: bool,
+        reason: String,
+    },
 }
 
 pub enum FrontendEvent {
@@ -44,25 +89,93 @@ pub enum FrontendEvent {
     RequestOpenScreen(Screen),
     SavePatchNote {
         id: Option<u32>,
+        title: String,
         data: String,
+        translations: BTreeMap<Locale, String>,
+        publish_at: Option<i64>,
+    },
+    SetPatchNoteStatus {
+        id: u32,
+        status: PatchNoteStatus,
     },
     DeletePatchNote {
         id: u32,
     },
+    RestorePatchNote {
+        id: u32,
+    },
+    UploadMedia {
+        name: String,
+        data: Vec<u8>,
+    },
+    RequestPatchNoteRevision {
+        revision: u32,
+    },
+    RestorePatchNoteRevision {
+        revision: u32,
+    },
+    CreateAdminUser {
+        name: String,
+        tg_user: Option<TgUser>,
+    },
+    SubscribeLogs(bool),
+    RequestStatistics {
+        days: u32,
+    },
+    LinkTelegram {
+        user_id: u32,
+        tg_user: Option<TgUser>,
+    },
+    AddServer {
+        name: String,
+        url: String,
+    },
+    RemoveServer {
+        url: String,
+    },
+    SelectServer {
+        url: String,
+    },
+    CollectGarbage {
+        dry_run: bool,
+    },
+    Scrub {
+        quarantine: bool,
+    },
+    /// `from` and `to` are full paths without the leading dot
+    Move {
+        from: String,
+        to: String,
+        folder: bool,
+    },
+    RestoreFromTrash(Vec<TrashItem>),
+    PurgeFromTrash(Vec<TrashItem>),
 }
 
 #[derive(Default, Eq, PartialEq)]
 pub enum Screen {
     #[default]
     Dashboard,
-    PatchNotes,
+    PatchNotes {
+        skip: u32,
+    },
     EditPatchNote {
         id: Option<u32>,
     },
+    PatchNoteHistory {
+        id: u32,
+    },
     Files {
         dir: String,
     },
     Logs,
+    Audit {
+        skip: u32,
+    },
+    AdminUsers,
+    Servers,
+    Maintenance,
+    Trash,
 }
 
 pub struct Backend {
@@ -75,12 +188,24 @@ pub struct Backend {
     pub(crate) log_holder: LogHolder,
     pub(crate) patch_note_holder: PatchNoteHolder,
     pub(crate) file_info_holder: FileInfoHolder,
+    pub(crate) audit_holder: AuditHolder,
+    pub(crate) auth: AuthHolder,
+    pub(crate) dashboard_holder: DashboardHolder,
+    pub(crate) maintenance: MaintenanceHolder,
+    pub(crate) trash: TrashHolder,
+    pub(crate) servers: ServerHolder,
 }
 
 impl Backend {
-    pub fn new(frontend_rx: Receiver<FrontendEvent>) -> Self {
+    pub fn new(
+        frontend_rx: Receiver<FrontendEvent>,
+        origin: String,
+        pending_login: Option<TgLoginData>,
+    ) -> Self {
+        let servers = ServerHolder::load(&origin);
+
         let (sender, receiver) = channel();
-        let mut network = Network::init(sender);
+        let mut network = Network::init(sender, servers.current.clone());
 
         network.run();
 
@@ -91,6 +216,12 @@ impl Backend {
             from_server: receiver,
             from_frontend: frontend_rx,
             file_info_holder: FileInfoHolder::default(),
+            audit_holder: AuditHolder::default(),
+            auth: AuthHolder::new(origin, pending_login),
+            dashboard_holder: DashboardHolder::default(),
+            maintenance: MaintenanceHolder::default(),
+            trash: TrashHolder::default(),
+            servers,
             notifications: vec![],
         }
     }
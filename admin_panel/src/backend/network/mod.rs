@@ -4,13 +4,13 @@ use gloo_timers::future::TimeoutFuture;
 use log::{log, Level};
 use wasm_bindgen_futures::spawn_local;
 
-use crate::WS_SERVER;
 use reqwasm::websocket::{futures::WebSocket, Message};
 use shared::admin_panel::{ClientPacket, ServerPacket};
 
 pub struct Network {
     to_client: Option<Sender<ClientPacket>>,
     from_server: std::sync::mpsc::Sender<ServerPacket>,
+    ws_url: String,
 }
 
 impl Network {
@@ -27,10 +27,11 @@ impl Network {
         }
     }
 
-    pub fn init(tx: std::sync::mpsc::Sender<ServerPacket>) -> Self {
+    pub fn init(tx: std::sync::mpsc::Sender<ServerPacket>, ws_url: String) -> Self {
         Self {
             to_client: None,
             from_server: tx,
+            ws_url,
         }
     }
 
@@ -40,16 +41,17 @@ impl Network {
         self.to_client = Some(tx);
 
         let tx = self.from_server.clone();
+        let ws_url = self.ws_url.clone();
 
         spawn_local(async move {
-            create_connection(tx, rx).await;
+            create_connection(&ws_url, tx, rx).await;
         });
     }
 }
 
-async fn try_connect() -> WebSocket {
+async fn try_connect(ws_url: &str) -> WebSocket {
     loop {
-        match WebSocket::open(WS_SERVER) {
+        match WebSocket::open(ws_url) {
             Ok(socket) => {
                 log!(Level::Debug, "Handshake has been completed");
 
@@ -65,10 +67,11 @@ async fn try_connect() -> WebSocket {
 }
 
 async fn create_connection(
+    ws_url: &str,
     tx: std::sync::mpsc::Sender<ServerPacket>,
     mut rx: Receiver<ClientPacket>,
 ) {
-    let ws = try_connect().await;
+    let ws = try_connect(ws_url).await;
 
     let (mut sender, mut receiver) = ws.split();
     //spawn an async sender to push some more messages into the server
@@ -6,6 +6,7 @@ pub enum FileUploadState {
     Uploading,
     Processing,
     Completed,
+    Failed,
 }
 
 #[derive(Display, Debug)]
@@ -16,16 +17,22 @@ pub enum Notification {
         name: String,
         state: FileUploadState,
     },
+    Error {
+        text: String,
+    },
 }
 
 impl PartialEq<Self> for Notification {
     fn eq(&self, other: &Self) -> bool {
-        match self {
-            Notification::FileUpload { id, .. } => {
-                let Notification::FileUpload { id: other_id, .. } = other;
-
-                id == other_id
+        match (self, other) {
+            (
+                Notification::FileUpload { id, .. },
+                Notification::FileUpload { id: other_id, .. },
+            ) => id == other_id,
+            (Notification::Error { text }, Notification::Error { text: other_text }) => {
+                text == other_text
             }
+            _ => false,
         }
     }
 }
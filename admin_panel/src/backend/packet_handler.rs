@@ -27,23 +27,30 @@ impl Backend {
                     self.file_info_holder.set(files, folders, dir);
                 }
 
-                ServerPacket::Logs(logs) => {
-                    self.log_holder.add_server(logs);
+                ServerPacket::Logs {
+                    total,
+                    producers,
+                    logs,
+                } => {
+                    self.log_holder.set_server(total, producers, logs);
 
                     res.push(BackendCommand::OpenLogs);
                 }
 
+                ServerPacket::NewLogs(logs) => {
+                    self.log_holder.add_new_server(logs);
+                }
+
                 ServerPacket::FileUploaded { id } => {
                     for v in &mut self.notifications {
-                        match v {
-                            Notification::FileUpload {
-                                id: v_id, state, ..
-                            } => {
-                                if id.eq(v_id) {
-                                    *state = FileUploadState::Processing;
-
-                                    break;
-                                }
+                        if let Notification::FileUpload {
+                            id: v_id, state, ..
+                        } = v
+                        {
+                            if id.eq(v_id) {
+                                *state = FileUploadState::Processing;
+
+                                break;
                             }
                         }
                     }
@@ -51,27 +58,49 @@ impl Backend {
 
                 ServerPacket::FileProceeded { id } => {
                     for v in &mut self.notifications {
-                        match v {
-                            Notification::FileUpload {
-                                id: v_id,
-                                state,
-                                dir,
-                                ..
-                            } => {
-                                if id.eq(v_id) {
-                                    if dir == &self.file_info_holder.current_dir {
-                                        self.network.send_packet(ClientPacket::FileList {
-                                            dir: remove_leading_dot(dir),
-                                        })
-                                    }
-
-                                    *state = FileUploadState::Completed;
-
-                                    break;
+                        if let Notification::FileUpload {
+                            id: v_id,
+                            state,
+                            dir,
+                            ..
+                        } = v
+                        {
+                            if id.eq(v_id) {
+                                if dir == &self.file_info_holder.current_dir {
+                                    self.network.send_packet(ClientPacket::FileList {
+                                        dir: remove_leading_dot(dir),
+                                    })
                                 }
+
+                                *state = FileUploadState::Completed;
+
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                ServerPacket::FileFailed { id, reason } => {
+                    for v in &mut self.notifications {
+                        if let Notification::FileUpload {
+                            id: v_id, state, ..
+                        } = v
+                        {
+                            if id.eq(v_id) {
+                                *state = FileUploadState::Failed;
+
+                                break;
                             }
                         }
                     }
+
+                    self.notifications
+                        .push(Notification::Error { text: reason });
+                }
+
+                ServerPacket::ActionFailed(reason) => {
+                    self.notifications
+                        .push(Notification::Error { text: reason });
                 }
 
                 ServerPacket::PatchNotes {
@@ -82,17 +111,130 @@ impl Backend {
                 } => {
                     self.patch_note_holder.patch_notes = patch_notes;
                     self.patch_note_holder.total = total;
-                    self.patch_note_holder.take = take;
-                    self.patch_note_holder.skip = skip;
+                    self.patch_note_holder.query.take = take;
+                    self.patch_note_holder.query.skip = skip;
 
                     res.push(BackendCommand::OpenPatchNotes);
                 }
 
                 ServerPacket::OpenPatchNote(patch_note) => {
-                    res.push(BackendCommand::OpenPatchNote {
-                        id: Some(patch_note.id),
-                        data: patch_note.data,
-                    })
+                    res.push(BackendCommand::OpenPatchNote(Some(patch_note)))
+                }
+
+                ServerPacket::PatchNoteRevisions { id, revisions } => {
+                    self.patch_note_holder.history_id = id;
+                    self.patch_note_holder.revisions = revisions;
+
+                    res.push(BackendCommand::OpenPatchNoteHistory);
+                }
+
+                ServerPacket::MediaUploaded { name, url } => {
+                    res.push(BackendCommand::InsertMedia { name, url })
+                }
+
+                ServerPacket::MediaUploadFailed { name, reason } => {
+                    res.push(BackendCommand::MediaUploadFailed { name, reason })
+                }
+
+                ServerPacket::PatchNoteRevision { revision, current } => {
+                    self.patch_note_holder.compared = Some((revision, current));
+                }
+
+                ServerPacket::AuditLog {
+                    take,
+                    skip,
+                    total,
+                    records,
+                } => {
+                    self.audit_holder.records = records;
+                    self.audit_holder.total = total;
+                    self.audit_holder.take = take;
+                    self.audit_holder.skip = skip;
+
+                    res.push(BackendCommand::OpenAudit);
+                }
+
+                ServerPacket::AuthRequired { bot_id } => {
+                    self.auth.bot_id = Some(bot_id);
+
+                    match self.auth.pending_login.take() {
+                        // The login is for this page, a server from a link must not get it
+                        Some(data) if self.servers.is_saved() => {
+                            self.network.send_packet(ClientPacket::TelegramLogin(data));
+                        }
+                        Some(_) => {
+                            self.auth.error = Some(format!(
+                                "Telegram login was not sent to {}, save the server first",
+                                self.servers.current
+                            ));
+
+                            res.push(BackendCommand::OpenLogin);
+                        }
+                        None => res.push(BackendCommand::OpenLogin),
+                    }
+                }
+
+                ServerPacket::LoggedIn(user) => {
+                    self.auth.user = Some(user);
+                    self.auth.error = None;
+
+                    self.send_packet(ClientPacket::Dashboard {
+                        days: self.dashboard_holder.days,
+                    });
+
+                    res.push(BackendCommand::LoggedIn);
+                }
+
+                ServerPacket::AuthFailed(reason) => {
+                    self.auth.error = Some(reason);
+
+                    res.push(BackendCommand::OpenLogin);
+                }
+
+                ServerPacket::AdminUsers(users) => {
+                    self.auth.admin_users = users;
+
+                    res.push(BackendCommand::OpenAdminUsers);
+                }
+
+                ServerPacket::Dashboard(summary) => {
+                    self.dashboard_holder.summary = Some(summary);
+
+                    res.push(BackendCommand::OpenDashboard);
+                }
+
+                ServerPacket::Statistics(statistics) => {
+                    if let Some(summary) = &mut self.dashboard_holder.summary {
+                        summary.statistics = statistics;
+                    }
+                }
+
+                ServerPacket::GcReport(report) => {
+                    self.maintenance.gc_report = Some(report);
+                    self.maintenance.gc_running = false;
+                }
+
+                ServerPacket::MoveFailed {
+                    from,
+                    to,
+                    folder,
+                    reason,
+                } => res.push(BackendCommand::MoveFailed {
+                    from,
+                    to,
+                    folder,
+                    reason,
+                }),
+
+                ServerPacket::Trash { entries, errors } => {
+                    self.trash.set(entries, errors);
+
+                    res.push(BackendCommand::OpenTrash);
+                }
+
+                ServerPacket::ScrubReport(report) => {
+                    self.maintenance.scrub_report = report;
+                    self.maintenance.scrub_running = false;
                 }
             }
         }
@@ -1,9 +1,51 @@
-use shared::admin_panel::PatchNote;
+use shared::admin_panel::{PatchNote, PatchNoteQuery, PatchNoteRevision, PatchNoteRevisionInfo};
+
+pub(crate) const PATCH_NOTES_PAGE_SIZE: u32 = 10;
 
-#[derive(Default)]
 pub(crate) struct PatchNoteHolder {
-    pub(crate) take: u32,
-    pub(crate) skip: u32,
+    pub(crate) query: PatchNoteQuery,
     pub(crate) total: u32,
     pub(crate) patch_notes: Vec<PatchNote>,
+
+    /// Patch note whose revisions are shown
+    pub(crate) history_id: u32,
+    /// Newest first
+    pub(crate) revisions: Vec<PatchNoteRevisionInfo>,
+    /// Selected revision and the current patch note it is compared with
+    pub(crate) compared: Option<(PatchNoteRevision, PatchNote)>,
+}
+
+impl Default for PatchNoteHolder {
+    fn default() -> Self {
+        Self {
+            query: PatchNoteQuery {
+                take: PATCH_NOTES_PAGE_SIZE,
+                skip: 0,
+                with_deleted: false,
+                status: None,
+                author: None,
+            },
+            total: 0,
+            patch_notes: vec![],
+            history_id: 0,
+            revisions: vec![],
+            compared: None,
+        }
+    }
+}
+
+impl PatchNoteHolder {
+    /// Local update of the shown page, hidden patch notes are removed from it
+    pub(crate) fn set_deleted(&mut self, id: u32, deleted: bool) {
+        let Some(index) = self.patch_notes.iter().position(|v| v.id == id) else {
+            return;
+        };
+
+        if deleted && !self.query.with_deleted {
+            self.patch_notes.remove(index);
+            self.total = self.total.saturating_sub(1);
+        } else {
+            self.patch_notes[index].deleted = deleted;
+        }
+    }
 }
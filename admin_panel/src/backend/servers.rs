@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+
+/// Browser storage key of [`StoredServers`]
+const STORAGE_KEY: &str = "admin_panel_servers";
+/// `?server=wss://example.com/ws` connects to another server for this page load only
+const SERVER_QUERY: &str = "server";
+/// Used when the page is not served over http, e.g. opened from a file
+const FALLBACK_WS_SERVER: &str = "ws://127.0.0.1:3000/ws";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct ServerEntry {
+    pub(crate) name: String,
+    /// WebSocket url
+    pub(crate) url: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct StoredServers {
+    servers: Vec<ServerEntry>,
+    selected: Option<String>,
+}
+
+pub(crate) struct ServerHolder {
+    /// Named servers added by the user
+    pub(crate) servers: Vec<ServerEntry>,
+    /// WebSocket url of the server the page origin belongs to
+    pub(crate) origin_url: String,
+    /// WebSocket url the panel is connected to
+    pub(crate) current: String,
+    /// Stored choice, `current` differs from it while the `?server=` query is used
+    selected: Option<String>,
+}
+
+impl ServerHolder {
+    /// Priority: `?server=` query, stored choice, page origin. The query is not stored, the user
+    /// saves its server explicitly
+    pub fn load(origin: &str) -> Self {
+        let stored = load_stored();
+        let origin_url = ws_url_from_origin(origin);
+
+        let query = web_sys::window()
+            .and_then(|w| w.location().search().ok())
+            .and_then(|v| web_sys::UrlSearchParams::new_with_str(&v).ok())
+            .and_then(|v| v.get(SERVER_QUERY))
+            .and_then(|v| normalize_ws_url(&v));
+
+        Self {
+            current: query
+                .or(stored.selected.clone())
+                .unwrap_or_else(|| origin_url.clone()),
+            servers: stored.servers,
+            origin_url,
+            selected: stored.selected,
+        }
+    }
+
+    /// Whether the current server is the page origin or one the user added. Telegram login data
+    /// is sent only to such servers
+    pub fn is_saved(&self) -> bool {
+        self.current == self.origin_url || self.servers.iter().any(|v| v.url == self.current)
+    }
+
+    /// Http address of the current server, it serves patch note media
+    pub fn http_url(&self) -> String {
+        let url = self.current.trim_end_matches("/ws");
+
+        if let Some(host) = url.strip_prefix("wss://") {
+            format!("https://{host}")
+        } else if let Some(host) = url.strip_prefix("ws://") {
+            format!("http://{host}")
+        } else {
+            url.to_string()
+        }
+    }
+
+    /// Name of the current server, its url if it is not in the list
+    pub fn current_name(&self) -> String {
+        if self.current == self.origin_url {
+            return "This site".to_string();
+        }
+
+        self.servers
+            .iter()
+            .find(|v| v.url == self.current)
+            .map_or_else(|| self.current.clone(), |v| v.name.clone())
+    }
+
+    pub fn add(&mut self, name: &str, url: &str) -> Result<(), String> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            return Err("Name is required".to_string());
+        }
+
+        let Some(url) = normalize_ws_url(url) else {
+            return Err(format!("{url} is not a ws(s) or http(s) url"));
+        };
+
+        if self.servers.iter().any(|v| v.name == name || v.url == url) {
+            return Err(format!("{name} is already in the list"));
+        }
+
+        self.servers.push(ServerEntry {
+            name: name.to_string(),
+            url,
+        });
+        self.save();
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, url: &str) {
+        self.servers.retain(|v| v.url != url);
+        self.save();
+    }
+
+    /// Connection is made on start, so the page is reloaded to switch it
+    pub fn select(&mut self, url: &str) {
+        if self.current == url {
+            return;
+        }
+
+        self.current = url.to_string();
+        self.selected = (url != self.origin_url).then(|| url.to_string());
+        self.save();
+
+        if let Some(window) = web_sys::window() {
+            let location = window.location();
+
+            // The query would override the new choice
+            match location.search() {
+                Ok(search) if !search.is_empty() => {
+                    let _ = location.set_search("");
+                }
+                _ => {
+                    let _ = location.reload();
+                }
+            }
+        }
+    }
+
+    fn save(&self) {
+        let stored = StoredServers {
+            servers: self.servers.clone(),
+            // The origin server is not remembered, so a moved page follows its new origin
+            selected: self.selected.clone(),
+        };
+
+        let Ok(json) = serde_json::to_string(&stored) else {
+            return;
+        };
+
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(STORAGE_KEY, &json);
+        }
+    }
+}
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+fn load_stored() -> StoredServers {
+    local_storage()
+        .and_then(|v| v.get_item(STORAGE_KEY).ok()?)
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+/// `https://admin.example.com` -> `wss://admin.example.com/ws`
+fn ws_url_from_origin(origin: &str) -> String {
+    if origin.starts_with("http://") || origin.starts_with("https://") {
+        normalize_ws_url(origin).unwrap_or_else(|| FALLBACK_WS_SERVER.to_string())
+    } else {
+        FALLBACK_WS_SERVER.to_string()
+    }
+}
+
+/// Accepts ws(s) and http(s) urls, `/ws` is added when there is no path
+fn normalize_ws_url(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/');
+
+    let (scheme, rest) = url.split_once("://")?;
+
+    let scheme = match scheme.to_ascii_lowercase().as_str() {
+        "ws" | "http" => "ws",
+        "wss" | "https" => "wss",
+        _ => return None,
+    };
+
+    if rest.is_empty() || rest.starts_with('/') {
+        return None;
+    }
+
+    if rest.contains('/') {
+        Some(format!("{scheme}://{rest}"))
+    } else {
+        Some(format!("{scheme}://{rest}/ws"))
+    }
+}
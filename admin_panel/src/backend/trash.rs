@@ -0,0 +1,23 @@
+use shared::admin_panel::{TrashEntry, TrashItem};
+use std::collections::BTreeSet;
+
+#[derive(Default)]
+pub(crate) struct TrashHolder {
+    pub(crate) entries: Vec<TrashEntry>,
+    /// Problems of the last restore or purge
+    pub(crate) errors: Vec<String>,
+    pub(crate) selected: BTreeSet<TrashItem>,
+}
+
+impl TrashHolder {
+    pub(crate) fn set(&mut self, entries: Vec<TrashEntry>, errors: Vec<String>) {
+        self.selected
+            .retain(|v| entries.iter().any(|entry| entry.item == *v));
+        self.entries = entries;
+        self.errors = errors;
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.entries.iter().map(|v| v.size).sum()
+    }
+}
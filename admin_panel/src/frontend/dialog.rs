@@ -1,9 +1,23 @@
 use crate::frontend::Frontend;
+use shared::admin_panel::TrashItem;
 
 #[derive(Eq, PartialEq)]
 pub enum Dialog {
     None,
-    CreateFolder { dir: String, name: String },
+    CreateFolder {
+        dir: String,
+        name: String,
+    },
+    /// Paths are full and without the leading dot, `error` is the reason the last try failed
+    Move {
+        from: String,
+        to: String,
+        folder: bool,
+        error: Option<String>,
+    },
+    PurgeFromTrash {
+        items: Vec<TrashItem>,
+    },
 }
 
 impl Frontend {
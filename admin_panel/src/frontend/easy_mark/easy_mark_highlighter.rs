@@ -1,4 +1,4 @@
-use crate::frontend::easy_mark::easy_mark_parser;
+use shared::easy_mark::parser as easy_mark_parser;
 
 /// Highlight easymark, memoizing previous output to save CPU.
 ///
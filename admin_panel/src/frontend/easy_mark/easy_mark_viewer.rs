@@ -1,5 +1,13 @@
-use super::easy_mark_parser as easy_mark;
 use egui::*;
+use shared::easy_mark::parser as easy_mark;
+
+const MEDIA_BASE_URL: &str = "easy_mark_media_base_url";
+const MAX_IMAGE_HEIGHT: f32 = 400.;
+
+/// Sets the prefix of relative image urls like `/media/1.png`
+pub fn set_media_base_url(ctx: &Context, url: String) {
+    ctx.data_mut(|d| d.insert_temp(Id::new(MEDIA_BASE_URL), url));
+}
 
 /// Parse and display a VERY simple and small subset of Markdown.
 pub fn easy_mark(ui: &mut Ui, easy_mark: &str) {
@@ -60,6 +68,26 @@ pub fn item_ui(ui: &mut Ui, item: easy_mark::Item<'_>) {
             }
         }
 
+        easy_mark::Item::Image(alt, url) => {
+            let url = if url.starts_with('/') {
+                let base: String = ui
+                    .ctx()
+                    .data(|d| d.get_temp(Id::new(MEDIA_BASE_URL)))
+                    .unwrap_or_default();
+
+                format!("{base}{url}")
+            } else {
+                url.to_string()
+            };
+
+            ui.add(
+                Image::new(url)
+                    .max_width(ui.available_width())
+                    .max_height(MAX_IMAGE_HEIGHT),
+            )
+            .on_hover_text(alt);
+        }
+
         easy_mark::Item::Separator => {
             ui.add(Separator::default().horizontal());
         }
@@ -2,12 +2,11 @@
 
 mod easy_mark_editor;
 mod easy_mark_highlighter;
-pub mod easy_mark_parser;
 mod easy_mark_viewer;
 
 pub use easy_mark_editor::EasyMarkEditor;
 pub use easy_mark_highlighter::MemoizedEasymarkHighlighter;
-pub use easy_mark_viewer::easy_mark;
+pub use easy_mark_viewer::{easy_mark, set_media_base_url};
 
 /// Create a [`Hyperlink`](egui::Hyperlink) to this egui source code file on github.
 #[macro_export]
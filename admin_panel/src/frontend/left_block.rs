@@ -2,7 +2,7 @@ use crate::backend::{FrontendEvent, Screen};
 use crate::frontend::right_block::RightBlockScreen;
 use crate::frontend::ui_kit::UiKit;
 use crate::frontend::Frontend;
-use egui::Ui;
+use egui::{Color32, RichText, Ui};
 
 #[derive(Default)]
 pub enum LeftBlockScreen {
@@ -35,7 +35,9 @@ impl Frontend {
                 .clicked()
             {
                 self.to_backend
-                    .send(FrontendEvent::RequestOpenScreen(Screen::PatchNotes))
+                    .send(FrontendEvent::RequestOpenScreen(Screen::PatchNotes {
+                        skip: 0,
+                    }))
                     .unwrap();
             }
 
@@ -54,6 +56,19 @@ impl Frontend {
                     .unwrap();
             }
 
+            if ui
+                .left_menu_button(
+                    "Trash",
+                    self.right_block_screen == RightBlockScreen::Trash,
+                    width,
+                )
+                .clicked()
+            {
+                self.to_backend
+                    .send(FrontendEvent::RequestOpenScreen(Screen::Trash))
+                    .unwrap();
+            }
+
             if ui
                 .left_menu_button(
                     "Logs",
@@ -66,6 +81,83 @@ impl Frontend {
                     .send(FrontendEvent::RequestOpenScreen(Screen::Logs))
                     .unwrap();
             }
+
+            if ui
+                .left_menu_button(
+                    "Audit",
+                    self.right_block_screen == RightBlockScreen::Audit,
+                    width,
+                )
+                .clicked()
+            {
+                self.to_backend
+                    .send(FrontendEvent::RequestOpenScreen(Screen::Audit { skip: 0 }))
+                    .unwrap();
+            }
+
+            if ui
+                .left_menu_button(
+                    "Admins",
+                    self.right_block_screen == RightBlockScreen::AdminUsers,
+                    width,
+                )
+                .clicked()
+            {
+                self.to_backend
+                    .send(FrontendEvent::RequestOpenScreen(Screen::AdminUsers))
+                    .unwrap();
+            }
+
+            if ui
+                .left_menu_button(
+                    "Maintenance",
+                    self.right_block_screen == RightBlockScreen::Maintenance,
+                    width,
+                )
+                .clicked()
+            {
+                self.to_backend
+                    .send(FrontendEvent::RequestOpenScreen(Screen::Maintenance))
+                    .unwrap();
+            }
+
+            if ui
+                .left_menu_button(
+                    "Servers",
+                    self.right_block_screen == RightBlockScreen::Servers,
+                    width,
+                )
+                .clicked()
+            {
+                self.to_backend
+                    .send(FrontendEvent::RequestOpenScreen(Screen::Servers))
+                    .unwrap();
+            }
+
+            ui.separator();
+
+            let servers = &self.backend.servers;
+            let server = RichText::new(format!("Server: {}", servers.current_name()));
+
+            ui.label(if !servers.is_saved() {
+                server.color(Color32::from_rgb(238, 62, 62))
+            } else if servers.current != servers.origin_url {
+                server.color(Color32::from_rgb(238, 180, 62))
+            } else {
+                server
+            })
+            .on_hover_text(&servers.current);
+
+            if !servers.is_saved() {
+                ui.label(
+                    RichText::new("Not saved, opened from a link")
+                        .color(Color32::from_rgb(238, 62, 62)),
+                );
+            }
+
+            if let Some(user) = &self.backend.auth.user {
+                ui.label(format!("Logged in as {}", user.name));
+            }
         });
     }
 }
@@ -1,10 +1,16 @@
 use crate::backend::{Backend, BackendCommand, FrontendEvent};
 use crate::frontend::dialog::Dialog;
-use crate::frontend::easy_mark::EasyMarkEditor;
+use crate::frontend::easy_mark::{EasyMarkEditor, DEFAULT_CODE};
 use crate::frontend::left_block::LeftBlockScreen;
+use crate::frontend::right_block::admin_users::AdminUserForm;
+use crate::frontend::right_block::logs::LogDateRange;
+use crate::frontend::right_block::patchnotes::PatchNoteForm;
+use crate::frontend::right_block::servers::ServerForm;
 use crate::frontend::right_block::RightBlockScreen;
 use crate::frontend::ui_kit::UiKit;
-use egui::{Align2, Vec2};
+use egui::{Align2, Color32, RichText, Vec2};
+use log::{log, Level};
+use shared::admin_panel::Locale;
 use std::sync::mpsc::Sender;
 
 mod dialog;
@@ -21,6 +27,11 @@ pub struct Frontend {
     show_deleted_files: bool,
 
     markup_editor: EasyMarkEditor,
+    admin_user_form: AdminUserForm,
+    log_date_range: LogDateRange,
+    patch_note_form: PatchNoteForm,
+    revision_locale: Locale,
+    server_form: ServerForm,
 
     to_backend: Sender<FrontendEvent>,
     dialog: Dialog,
@@ -40,6 +51,11 @@ impl Frontend {
             to_backend,
             backend,
             markup_editor: Default::default(),
+            admin_user_form: Default::default(),
+            log_date_range: Default::default(),
+            patch_note_form: Default::default(),
+            revision_locale: Default::default(),
+            server_form: Default::default(),
         }
     }
 
@@ -116,6 +132,84 @@ impl Frontend {
                     });
             }
 
+            Dialog::Move {
+                from,
+                to,
+                folder,
+                error,
+            } => {
+                egui::Window::new(if *folder { "Move folder" } else { "Move file" })
+                    .id(egui::Id::new("_warn_"))
+                    .collapsible(false)
+                    .resizable(false)
+                    .default_pos([rect.x / 3.0, rect.y / 3.0])
+                    .pivot(Align2::CENTER_CENTER)
+                    .show(ctx, |ui| {
+                        ui.set_width(300.);
+                        ui.vertical_centered(|ui| {
+                            ui.label(format!("Move {from} to"));
+
+                            ui.text_edit_singleline(to);
+
+                            if let Some(error) = error {
+                                ui.label(RichText::new(error.as_str()).color(Color32::RED));
+                            }
+
+                            ui.horizontal(|ui| {
+                                if ui.button_s("Move", 60., 1.).clicked() {
+                                    self.to_backend
+                                        .send(FrontendEvent::Move {
+                                            from: from.to_string(),
+                                            to: to.to_string(),
+                                            folder: *folder,
+                                        })
+                                        .unwrap();
+                                    close = true;
+                                }
+
+                                ui.add_space(172.);
+
+                                if ui.button_s("Cancel", 60., 1.).clicked() {
+                                    close = true;
+                                }
+                            });
+                        })
+                    });
+            }
+
+            Dialog::PurgeFromTrash { items } => {
+                egui::Window::new("Purge from trash")
+                    .id(egui::Id::new("_warn_"))
+                    .collapsible(false)
+                    .resizable(false)
+                    .default_pos([rect.x / 3.0, rect.y / 3.0])
+                    .pivot(Align2::CENTER_CENTER)
+                    .show(ctx, |ui| {
+                        ui.set_width(200.);
+                        ui.vertical_centered(|ui| {
+                            ui.label(format!(
+                                "Permanently delete {} entries? This can't be undone",
+                                items.len()
+                            ));
+
+                            ui.horizontal(|ui| {
+                                if ui.button_s("Purge", 60., 1.).clicked() {
+                                    self.to_backend
+                                        .send(FrontendEvent::PurgeFromTrash(items.clone()))
+                                        .unwrap();
+                                    close = true;
+                                }
+
+                                ui.add_space(72.);
+
+                                if ui.button_s("Cancel", 60., 1.).clicked() {
+                                    close = true;
+                                }
+                            });
+                        })
+                    });
+            }
+
             Dialog::None => {}
         }
 
@@ -131,18 +225,81 @@ impl Frontend {
                     self.right_block_screen = RightBlockScreen::Files;
                 }
 
+                BackendCommand::OpenDashboard => {
+                    self.right_block_screen = RightBlockScreen::Dashboard
+                }
+
                 BackendCommand::OpenLogs => self.right_block_screen = RightBlockScreen::Logs,
 
                 BackendCommand::OpenPatchNotes => {
                     self.right_block_screen = RightBlockScreen::PatchNotes
                 }
 
-                BackendCommand::OpenPatchNote { id, data } => {
-                    self.markup_editor.code = data;
-                    self.markup_editor.edit_id = id;
+                BackendCommand::OpenPatchNote(patch_note) => {
+                    self.patch_note_form = PatchNoteForm::new(patch_note.as_ref());
+
+                    self.markup_editor.edit_id = patch_note.as_ref().map(|v| v.id);
+                    self.markup_editor.code = patch_note
+                        .map(|v| v.data)
+                        .unwrap_or_else(|| DEFAULT_CODE.to_string());
 
                     self.right_block_screen = RightBlockScreen::EditPatchNote;
                 }
+
+                BackendCommand::OpenPatchNoteHistory => {
+                    self.right_block_screen = RightBlockScreen::PatchNoteHistory
+                }
+
+                BackendCommand::InsertMedia { name, url } => {
+                    let alt = name.rsplit_once('.').map_or(name.as_str(), |v| v.0);
+
+                    self.markup_editor.code += &format!("\n![{alt}]({url})\n");
+                    self.patch_note_form.media_error = None;
+                }
+
+                BackendCommand::MediaUploadFailed { name, reason } => {
+                    log!(Level::Warn, "Upload of {name} failed: {reason}");
+
+                    self.patch_note_form.media_error = Some(reason);
+                }
+
+                BackendCommand::MoveFailed {
+                    from,
+                    to,
+                    folder,
+                    reason,
+                } => {
+                    log!(Level::Warn, "Move of {from} to {to} failed: {reason}");
+
+                    self.show_dialog(Dialog::Move {
+                        from,
+                        to,
+                        folder,
+                        error: Some(reason),
+                    });
+                }
+
+                BackendCommand::OpenAudit => self.right_block_screen = RightBlockScreen::Audit,
+
+                BackendCommand::OpenTrash => self.right_block_screen = RightBlockScreen::Trash,
+
+                BackendCommand::OpenLogin => self.right_block_screen = RightBlockScreen::Login,
+
+                BackendCommand::LoggedIn => self.right_block_screen = RightBlockScreen::Dashboard,
+
+                BackendCommand::OpenAdminUsers => {
+                    self.right_block_screen = RightBlockScreen::AdminUsers
+                }
+
+                BackendCommand::OpenServers => self.right_block_screen = RightBlockScreen::Servers,
+
+                BackendCommand::OpenMaintenance => {
+                    self.right_block_screen = RightBlockScreen::Maintenance
+                }
+
+                BackendCommand::ServerAdded => self.server_form = ServerForm::default(),
+
+                BackendCommand::AddServerFailed(error) => self.server_form.error = Some(error),
             }
         }
     }
@@ -33,6 +33,7 @@ impl Notification {
                 format!("Status: {state}\nFile: {name}\nDir: {dir}"),
                 "File Upload",
             ),
+            Notification::Error { text } => (text.clone(), "Error"),
         };
 
         ui.vertical_centered(|ui| {
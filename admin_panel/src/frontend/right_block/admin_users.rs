@@ -0,0 +1,99 @@
+use crate::backend::FrontendEvent;
+use crate::frontend::ui_kit::UiKit;
+use crate::frontend::Frontend;
+use eframe::epaint::Color32;
+use egui::{RichText, ScrollArea, Ui};
+use shared::admin_panel::TgUser;
+
+#[derive(Default)]
+pub(crate) struct AdminUserForm {
+    name: String,
+    tg_id: String,
+    tg_user_name: String,
+}
+
+impl AdminUserForm {
+    fn tg_user(&self) -> Option<TgUser> {
+        Some(TgUser {
+            id: self.tg_id.trim().parse().ok()?,
+            user_name: self.tg_user_name.trim().to_string(),
+        })
+    }
+}
+
+impl Frontend {
+    pub(crate) fn draw_admin_users(&mut self, ui: &mut Ui) {
+        ui.vertical(|ui| {
+            egui::Grid::new("admin_user_form").show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut self.admin_user_form.name);
+                ui.end_row();
+
+                ui.label("Telegram id");
+                ui.text_edit_singleline(&mut self.admin_user_form.tg_id);
+                ui.end_row();
+
+                ui.label("Telegram user name");
+                ui.text_edit_singleline(&mut self.admin_user_form.tg_user_name);
+                ui.end_row();
+            });
+
+            if ui.button_s("Create admin", 100., 1.).clicked()
+                && !self.admin_user_form.name.trim().is_empty()
+            {
+                self.emit_event(FrontendEvent::CreateAdminUser {
+                    name: self.admin_user_form.name.trim().to_string(),
+                    tg_user: self.admin_user_form.tg_user(),
+                });
+
+                self.admin_user_form = AdminUserForm::default();
+            }
+
+            ui.separator();
+
+            ScrollArea::vertical()
+                .id_source("admin_users")
+                .show(ui, |ui| {
+                    for user in &self.backend.auth.admin_users {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("#{}", user.id));
+                            ui.label(RichText::new(&user.name).color(Color32::WHITE));
+
+                            if let Some(tg_user) = &user.tg_user {
+                                ui.label(format!(
+                                    "Telegram: {} ({})",
+                                    tg_user.user_name, tg_user.id
+                                ));
+
+                                if ui.button_s("Unlink", 60., 1.).clicked() {
+                                    self.emit_event(FrontendEvent::LinkTelegram {
+                                        user_id: user.id,
+                                        tg_user: None,
+                                    });
+                                }
+                            } else {
+                                ui.label(
+                                    RichText::new("Telegram: not linked").color(Color32::GRAY),
+                                );
+
+                                let tg_user = self.admin_user_form.tg_user();
+
+                                if ui
+                                    .add_enabled(tg_user.is_some(), egui::Button::new("Link"))
+                                    .on_hover_text("Links Telegram account from the form above")
+                                    .clicked()
+                                {
+                                    self.emit_event(FrontendEvent::LinkTelegram {
+                                        user_id: user.id,
+                                        tg_user,
+                                    });
+                                }
+                            }
+                        });
+
+                        ui.separator();
+                    }
+                });
+        });
+    }
+}
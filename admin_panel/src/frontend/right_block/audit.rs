@@ -0,0 +1,68 @@
+use crate::backend::{FrontendEvent, Screen};
+use crate::frontend::ui_kit::UiKit;
+use crate::frontend::Frontend;
+use eframe::epaint::Color32;
+use egui::{CollapsingHeader, RichText, ScrollArea, Ui};
+
+impl Frontend {
+    pub(crate) fn draw_audit(&mut self, ui: &mut Ui) {
+        ui.vertical(|ui| {
+            let holder = &self.backend.audit_holder;
+
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Records {}-{} of {}",
+                    (holder.skip + 1).min(holder.total),
+                    (holder.skip + holder.take).min(holder.total),
+                    holder.total
+                ));
+
+                if holder.skip > 0 && ui.button_s("Prev", 60., 1.).clicked() {
+                    self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Audit {
+                        skip: holder.skip.saturating_sub(holder.take),
+                    }));
+                }
+
+                if holder.skip + holder.take < holder.total
+                    && ui.button_s("Next", 60., 1.).clicked()
+                {
+                    self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Audit {
+                        skip: holder.skip + holder.take,
+                    }));
+                }
+            });
+
+            ui.separator();
+
+            ScrollArea::vertical().id_source("audit").show(ui, |ui| {
+                for record in &holder.records {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{}",
+                            chrono::DateTime::from_timestamp(record.time, 0)
+                                .unwrap()
+                                .format("%d/%m/%y %H:%M:%S")
+                        ));
+                        ui.label(format!("User {}", record.user_id));
+                        ui.label(&record.addr);
+                        ui.label(RichText::new(record.action.to_string()).color(Color32::WHITE));
+                        ui.label(RichText::new(&record.target).color(Color32::LIGHT_BLUE));
+                    });
+
+                    CollapsingHeader::new("Changes")
+                        .id_source(("audit_record", record.id))
+                        .show(ui, |ui| {
+                            ui.columns(2, |columns| {
+                                columns[0].label("Before");
+                                columns[0].monospace(record.before.as_deref().unwrap_or("-"));
+                                columns[1].label("After");
+                                columns[1].monospace(record.after.as_deref().unwrap_or("-"));
+                            });
+                        });
+
+                    ui.separator();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,169 @@
+use crate::backend::dashboard::DASHBOARD_PERIODS;
+use crate::backend::{FrontendEvent, Screen};
+use crate::frontend::ui_kit::{AsColor, UiKit};
+use crate::frontend::Frontend;
+use bytesize::ByteSize;
+use eframe::epaint::Color32;
+use egui::{RichText, ScrollArea, Ui};
+use egui_plot::{Bar, BarChart, Plot};
+use shared::admin_panel::DayStatistics;
+
+const CHART_HEIGHT: f32 = 180.;
+
+impl Frontend {
+    pub(crate) fn draw_dashboard(&mut self, ui: &mut Ui) {
+        let Some(summary) = &self.backend.dashboard_holder.summary else {
+            ui.horizontal(|ui| {
+                ui.label("No data");
+
+                if ui.button_s("Refresh", 60., 1.).clicked() {
+                    self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Dashboard));
+                }
+            });
+
+            return;
+        };
+
+        ScrollArea::vertical()
+            .id_source("dashboard")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Files: {}", summary.files_count));
+                    ui.separator();
+                    ui.label(format!("Total size: {}", ByteSize::b(summary.total_size)));
+                    ui.separator();
+                    ui.label(format!("Patch notes: {}", summary.patch_notes_count));
+                    ui.separator();
+                    ui.label(format!(
+                        "Installations: {}",
+                        summary.statistics.unique_installs
+                    ));
+
+                    if ui.button_s("Refresh", 60., 1.).clicked() {
+                        self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Dashboard));
+                    }
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Period");
+
+                    let mut days = self.backend.dashboard_holder.days;
+
+                    egui::ComboBox::from_id_source("dashboard_period")
+                        .selected_text(format!("{days} days"))
+                        .show_ui(ui, |ui| {
+                            for v in DASHBOARD_PERIODS {
+                                ui.selectable_value(&mut days, v, format!("{v} days"));
+                            }
+                        });
+
+                    if days != self.backend.dashboard_holder.days {
+                        self.emit_event(FrontendEvent::RequestStatistics { days });
+                    }
+                });
+
+                let days = &summary.statistics.days;
+
+                ui.label("Downloads");
+                draw_chart(ui, "downloads", days, Color32::LIGHT_BLUE, |v| {
+                    v.downloads as f64
+                });
+
+                ui.label("Manifest fetches");
+                draw_chart(ui, "manifest_fetches", days, Color32::LIGHT_GREEN, |v| {
+                    v.manifest_fetches as f64
+                });
+
+                ui.label("Bandwidth, MB");
+                draw_chart(ui, "bandwidth", days, Color32::GOLD, |v| {
+                    v.bytes_served as f64 / 1024. / 1024.
+                });
+
+                ui.separator();
+
+                ui.columns(2, |columns| {
+                    columns[0].label(RichText::new("Top files").color(Color32::WHITE));
+                    for file in &summary.statistics.top_files {
+                        columns[0].horizontal(|ui| {
+                            ui.label(&file.path);
+                            ui.label(format!(
+                                "{} / {}",
+                                file.downloads,
+                                ByteSize::b(file.bytes_served)
+                            ));
+                        });
+                    }
+
+                    columns[1].label(RichText::new("Connected admins").color(Color32::WHITE));
+                    for admin in &summary.connected_admins {
+                        let name = self
+                            .backend
+                            .auth
+                            .admin_users
+                            .iter()
+                            .find(|v| v.id == admin.user_id)
+                            .map(|v| v.name.clone())
+                            .unwrap_or_else(|| format!("User {}", admin.user_id));
+
+                        columns[1].horizontal(|ui| {
+                            ui.label(name);
+                            ui.label(&admin.addr);
+                            ui.label(format!(
+                                "since {}",
+                                chrono::DateTime::from_timestamp(admin.connected_at, 0)
+                                    .unwrap()
+                                    .format("%d/%m/%y %H:%M")
+                            ));
+                        });
+                    }
+                });
+
+                ui.separator();
+
+                ui.label(RichText::new("Recent errors").color(Color32::WHITE));
+                for log in summary.recent_errors.iter().rev() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{}",
+                            chrono::DateTime::from_timestamp(log.time, 0)
+                                .unwrap()
+                                .format("%d %b %H:%M")
+                        ));
+                        ui.label(RichText::new(&log.producer).color(Color32::WHITE));
+                        ui.label(RichText::new(&log.log).color(log.level.as_color()));
+                    });
+                }
+            });
+    }
+}
+
+fn draw_chart(
+    ui: &mut Ui,
+    id: &str,
+    days: &[DayStatistics],
+    color: Color32,
+    value: impl Fn(&DayStatistics) -> f64,
+) {
+    let bars = days
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            Bar::new(i as f64, value(v)).name(
+                chrono::DateTime::from_timestamp(v.day, 0)
+                    .unwrap()
+                    .format("%d %b"),
+            )
+        })
+        .collect();
+
+    Plot::new(id)
+        .height(CHART_HEIGHT)
+        .allow_scroll(false)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(BarChart::new(bars).color(color))
+        });
+}
@@ -1,7 +1,8 @@
+use crate::backend::events::remove_leading_dot;
 use crate::backend::file_info_holder::{FileInfoHolder, FileSortBy, SortDir};
 use crate::backend::{FrontendEvent, Screen};
 use crate::frontend::dialog::Dialog;
-use crate::frontend::ui_kit::{icon, DrawCb, UiKit, DELETE_TOKEN, RESTORE_TOKEN};
+use crate::frontend::ui_kit::{icon, DrawCb, UiKit, DELETE_TOKEN, EDIT_TOKEN, RESTORE_TOKEN};
 use crate::frontend::Frontend;
 use bytesize::ByteSize;
 use eframe::epaint::Color32;
@@ -107,6 +108,8 @@ impl Frontend {
 
             ui.separator();
 
+            let mut dialog = None;
+
             ScrollArea::vertical().show(ui, |ui| {
                 for f in self.backend.file_info_holder.folders() {
                     let f1 = || {
@@ -121,7 +124,17 @@ impl Frontend {
                             dir: format!("{dir}/{}", f.name),
                         }))
                     };
-                    f.draw_cb(ui, (f1, f2));
+                    let f3 = || {
+                        let from = remove_leading_dot(&format!("{dir}/{}", f.name));
+
+                        dialog = Some(Dialog::Move {
+                            to: from.clone(),
+                            from,
+                            folder: true,
+                            error: None,
+                        })
+                    };
+                    f.draw_cb(ui, (f1, f2, f3));
 
                     ui.separator();
                 }
@@ -146,12 +159,26 @@ impl Frontend {
                                     name: f.name.clone(),
                                 })
                             },
+                            || {
+                                let from = remove_leading_dot(&format!("{dir}/{}", f.name));
+
+                                dialog = Some(Dialog::Move {
+                                    to: from.clone(),
+                                    from,
+                                    folder: false,
+                                    error: None,
+                                })
+                            },
                         ),
                     );
 
                     ui.separator();
                 }
             });
+
+            if let Some(dialog) = dialog {
+                self.show_dialog(dialog);
+            }
         });
     }
 }
@@ -193,8 +220,8 @@ impl FileInfoHolder {
     }
 }
 
-impl<F1: FnOnce(), F2: FnOnce()> DrawCb<(F1, F2)> for FileInfo {
-    fn draw_cb(&self, ui: &mut Ui, callback: (F1, F2)) {
+impl<F1: FnOnce(), F2: FnOnce(), F3: FnOnce()> DrawCb<(F1, F2, F3)> for FileInfo {
+    fn draw_cb(&self, ui: &mut Ui, callback: (F1, F2, F3)) {
         ui.horizontal(|ui| {
             ui.set_height(ROW_HEIGHT);
 
@@ -209,13 +236,19 @@ impl<F1: FnOnce(), F2: FnOnce()> DrawCb<(F1, F2)> for FileInfo {
 
                 let mut l = ui.label(if self.deleted {
                     RichText::new(text).color(Color32::GRAY).strikethrough()
+                } else if self.quarantined {
+                    RichText::new(text).color(Color32::RED)
                 } else {
                     RichText::new(text).color(Color32::WHITE)
                 });
 
-                if tooltip {
+                if tooltip || self.quarantined {
                     l.on_hover_ui(|ui| {
                         ui.label(&self.name);
+
+                        if self.quarantined {
+                            ui.label("Quarantined: the stored copy is broken, upload it again");
+                        }
                     });
                 }
             });
@@ -272,6 +305,14 @@ impl<F1: FnOnce(), F2: FnOnce()> DrawCb<(F1, F2)> for FileInfo {
 
             ui.separator();
 
+            if ui
+                .clickable_label(icon(EDIT_TOKEN).size(16.).color(Color32::LIGHT_GRAY))
+                .on_hover_text("Move or rename")
+                .clicked()
+            {
+                callback.2();
+            }
+
             if ui
                 .clickable_label(if self.deleted {
                     icon(RESTORE_TOKEN).size(16.).color(Color32::DARK_GREEN)
@@ -285,8 +326,8 @@ impl<F1: FnOnce(), F2: FnOnce()> DrawCb<(F1, F2)> for FileInfo {
         });
     }
 }
-impl<F1: FnOnce(), F2: FnOnce()> DrawCb<(F1, F2)> for FolderInfo {
-    fn draw_cb(&self, ui: &mut Ui, callback: (F1, F2)) {
+impl<F1: FnOnce(), F2: FnOnce(), F3: FnOnce()> DrawCb<(F1, F2, F3)> for FolderInfo {
+    fn draw_cb(&self, ui: &mut Ui, callback: (F1, F2, F3)) {
         ui.horizontal(|ui| {
             ui.set_height(ROW_HEIGHT);
 
@@ -367,6 +408,14 @@ impl<F1: FnOnce(), F2: FnOnce()> DrawCb<(F1, F2)> for FolderInfo {
 
             ui.separator();
 
+            if ui
+                .clickable_label(icon(EDIT_TOKEN).size(16.).color(Color32::LIGHT_GRAY))
+                .on_hover_text("Move or rename")
+                .clicked()
+            {
+                callback.2();
+            }
+
             if ui
                 .clickable_label(if self.deleted {
                     icon(RESTORE_TOKEN).size(16.).color(Color32::DARK_GREEN)
@@ -0,0 +1,38 @@
+use crate::backend::{FrontendEvent, Screen};
+use crate::frontend::Frontend;
+use eframe::epaint::Color32;
+use egui::{RichText, Ui};
+
+impl Frontend {
+    pub(crate) fn draw_login(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(40.);
+
+            ui.label(RichText::new("Authorization required").size(18.));
+
+            ui.add_space(10.);
+
+            if let Some(error) = &self.backend.auth.error {
+                ui.label(RichText::new(error).color(Color32::from_rgb(238, 62, 62)));
+
+                ui.add_space(10.);
+            }
+
+            if let Some(url) = self.backend.auth.login_url() {
+                ui.hyperlink_to(RichText::new("Log in with Telegram").size(16.), url);
+            }
+
+            ui.add_space(10.);
+
+            if ui
+                .link(format!(
+                    "Wrong server? Connected to {}",
+                    self.backend.servers.current_name()
+                ))
+                .clicked()
+            {
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Servers));
+            }
+        });
+    }
+}
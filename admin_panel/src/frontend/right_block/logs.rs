@@ -1,71 +1,217 @@
-use crate::frontend::ui_kit::{combo_box_row, AsColor};
+use crate::backend::{FrontendEvent, Screen};
+use crate::frontend::ui_kit::{combo_box_row, AsColor, UiKit};
 use crate::frontend::Frontend;
+use chrono::{Days, NaiveDate, Utc};
 use eframe::epaint::text::TextWrapMode;
 use eframe::epaint::Color32;
 use egui::{RichText, ScrollArea, Ui};
-use shared::admin_panel::{LogHolder, LogLevelFilter};
+use egui_extras::DatePickerButton;
+use shared::admin_panel::Log;
+use wasm_bindgen_futures::spawn_local;
+
+/// Dates picked for the logs query, applied with the "Apply" button
+pub(crate) struct LogDateRange {
+    from_enabled: bool,
+    from: NaiveDate,
+    to_enabled: bool,
+    /// Inclusive
+    to: NaiveDate,
+}
+
+impl Default for LogDateRange {
+    fn default() -> Self {
+        let today = Utc::now().date_naive();
+
+        Self {
+            from_enabled: false,
+            from: today,
+            to_enabled: false,
+            to: today,
+        }
+    }
+}
+
+impl LogDateRange {
+    fn from(&self) -> Option<i64> {
+        self.from_enabled.then(|| {
+            self.from
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        })
+    }
+
+    fn to(&self) -> Option<i64> {
+        self.to_enabled.then(|| {
+            (self.to + Days::new(1))
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        })
+    }
+}
 
 impl Frontend {
     pub(crate) fn draw_logs(&mut self, ui: &mut Ui) {
         ui.vertical(|ui| {
             ui.separator();
 
-            ui.horizontal(|ui| {
-                combo_box_row(ui, &mut self.backend.log_holder.level_filter, "Level");
-                ui.label("Producer");
-                egui::ComboBox::from_id_source(ui.next_auto_id())
-                    .selected_text(&self.backend.log_holder.producer_filter)
-                    .show_ui(ui, |ui| {
-                        ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
-                        ui.set_min_width(20.0);
-
-                        let mut c = self
-                            .backend
-                            .log_holder
-                            .producers
-                            .iter()
-                            .collect::<Vec<&String>>();
-                        c.sort();
-                        for t in c {
-                            ui.selectable_value(
-                                &mut self.backend.log_holder.producer_filter,
-                                t.clone(),
-                                t,
-                            );
+            self.draw_logs_filters(ui);
+
+            ui.separator();
+
+            self.draw_logs_controls(ui);
+
+            ui.separator();
+
+            ScrollArea::vertical()
+                .stick_to_bottom(self.backend.log_holder.live)
+                .show(ui, |ui| {
+                    ui.vertical(|ui| {
+                        for log in &self.backend.log_holder.server_logs {
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "{}",
+                                    chrono::DateTime::from_timestamp(log.time, 0)
+                                        .unwrap()
+                                        .format("%d %b %H:%M")
+                                ));
+                                ui.label(RichText::new(&log.producer).color(Color32::WHITE));
+                                ui.label(RichText::new(&log.log).color(log.level.as_color()));
+                            });
+
+                            ui.add_space(5.0);
                         }
                     });
-            });
+                });
 
             ui.separator();
+        });
+    }
+
+    fn draw_logs_filters(&mut self, ui: &mut Ui) {
+        let query = &mut self.backend.log_holder.query;
+
+        ui.horizontal(|ui| {
+            combo_box_row(ui, &mut query.min_level, "Min level");
+
+            ui.label("Producer");
+            egui::ComboBox::from_id_source(ui.next_auto_id())
+                .selected_text(query.producer.as_deref().unwrap_or("All"))
+                .show_ui(ui, |ui| {
+                    ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+                    ui.set_min_width(20.0);
 
-            ScrollArea::vertical().show(ui, |ui| {
-                ui.vertical(|ui| {
-                    for log in self.backend.log_holder.server_logs.iter().filter(|v| {
-                        let a = self.backend.log_holder.producer_filter == LogHolder::ALL
-                            || self.backend.log_holder.producer_filter == v.producer;
-
-                        let b = self.backend.log_holder.level_filter == LogLevelFilter::All
-                            || self.backend.log_holder.level_filter as u8 == v.level as u8;
-
-                        a && b
-                    }) {
-                        ui.horizontal(|ui| {
-                            ui.label(format!(
-                                "{}",
-                                chrono::DateTime::from_timestamp(log.time, 0)
-                                    .unwrap()
-                                    .format("%d %b %H:%M")
-                            ));
-                            ui.label(RichText::new(&log.producer).color(Color32::WHITE));
-                            ui.label(RichText::new(&log.log).color(log.level.as_color()));
-                        });
-
-                        ui.add_space(5.0);
+                    ui.selectable_value(&mut query.producer, None, "All");
+
+                    let mut c = self
+                        .backend
+                        .log_holder
+                        .producers
+                        .iter()
+                        .collect::<Vec<&String>>();
+                    c.sort();
+                    for t in c {
+                        ui.selectable_value(&mut query.producer, Some(t.clone()), t);
                     }
                 });
-            });
 
-            ui.separator();
+            ui.label("Search");
+            ui.text_edit_singleline(&mut query.search);
+        });
+
+        ui.horizontal(|ui| {
+            let range = &mut self.log_date_range;
+
+            ui.checkbox(&mut range.from_enabled, "From");
+            ui.add_enabled(
+                range.from_enabled,
+                DatePickerButton::new(&mut range.from).id_source("logs_from"),
+            );
+
+            ui.checkbox(&mut range.to_enabled, "To");
+            ui.add_enabled(
+                range.to_enabled,
+                DatePickerButton::new(&mut range.to).id_source("logs_to"),
+            );
         });
     }
+
+    fn draw_logs_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            if ui.button_s("Apply", 60., 1.).clicked() {
+                let query = &mut self.backend.log_holder.query;
+
+                query.from = self.log_date_range.from();
+                query.to = self.log_date_range.to();
+                query.skip = 0;
+
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Logs));
+            }
+
+            let holder = &mut self.backend.log_holder;
+
+            ui.label(format!(
+                "Logs {}-{} of {}",
+                (holder.query.skip + 1).min(holder.total),
+                (holder.query.skip + holder.query.take).min(holder.total),
+                holder.total
+            ));
+
+            if holder.query.skip + holder.query.take < holder.total
+                && ui.button_s("Older", 60., 1.).clicked()
+            {
+                holder.query.skip += holder.query.take;
+
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Logs));
+            }
+
+            let holder = &mut self.backend.log_holder;
+
+            if holder.query.skip > 0 && ui.button_s("Newer", 60., 1.).clicked() {
+                holder.query.skip = holder.query.skip.saturating_sub(holder.query.take);
+
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::Logs));
+            }
+
+            if ui
+                .checkbox(&mut self.backend.log_holder.live, "Live")
+                .changed()
+            {
+                self.emit_event(FrontendEvent::SubscribeLogs(self.backend.log_holder.live));
+            }
+
+            if ui.button_s("Export", 60., 1.).clicked() {
+                export_logs(self.backend.log_holder.server_logs.iter());
+            }
+        });
+    }
+}
+
+fn export_logs<'a>(logs: impl Iterator<Item = &'a Log>) {
+    let text = logs
+        .map(|log| {
+            format!(
+                "{} [{}] {}: {}\n",
+                chrono::DateTime::from_timestamp(log.time, 0)
+                    .unwrap()
+                    .format("%Y-%m-%d %H:%M:%S"),
+                log.level,
+                log.producer,
+                log.log
+            )
+        })
+        .collect::<String>();
+
+    spawn_local(async move {
+        if let Some(handle) = rfd::AsyncFileDialog::new()
+            .set_file_name("logs.txt")
+            .save_file()
+            .await
+        {
+            let _ = handle.write(text.as_bytes()).await;
+        }
+    });
 }
@@ -0,0 +1,154 @@
+use crate::backend::FrontendEvent;
+use crate::frontend::ui_kit::UiKit;
+use crate::frontend::Frontend;
+use bytesize::ByteSize;
+use eframe::epaint::Color32;
+use egui::{CollapsingHeader, RichText, ScrollArea, Ui};
+
+impl Frontend {
+    pub(crate) fn draw_maintenance(&mut self, ui: &mut Ui) {
+        let holder = &self.backend.maintenance;
+        let mut event = None;
+
+        ui.label(RichText::new("Garbage collection").color(Color32::WHITE));
+        ui.label("Purges files deleted longer than the retention period ago and unused blobs");
+
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(!holder.gc_running, |ui| {
+                if ui.button_s("Dry run", 80., 1.).clicked() {
+                    event = Some(FrontendEvent::CollectGarbage { dry_run: true });
+                }
+
+                if ui.button_s("Collect", 80., 1.).clicked() {
+                    event = Some(FrontendEvent::CollectGarbage { dry_run: false });
+                }
+            });
+
+            if holder.gc_running {
+                ui.spinner();
+            }
+        });
+
+        if let Some(report) = &holder.gc_report {
+            ui.separator();
+
+            ui.label(format!(
+                "{} at {}",
+                if report.dry_run {
+                    "Dry run"
+                } else {
+                    "Collected"
+                },
+                chrono::DateTime::from_timestamp(report.time, 0)
+                    .unwrap()
+                    .with_timezone(&chrono::Local)
+                    .format("%d/%m/%y %H:%M")
+            ));
+
+            ui.label(format!(
+                "Files: {} ({}), folders: {}, orphan blobs: {}",
+                report.purged_files.len(),
+                ByteSize::b(report.purged_size),
+                report.purged_folders.len(),
+                report.orphan_blobs.len()
+            ));
+
+            ScrollArea::vertical()
+                .id_source("gc_report")
+                .show(ui, |ui| {
+                    for (title, paths, color) in [
+                        ("Errors", &report.errors, Color32::RED),
+                        ("Files", &report.purged_files, Color32::GRAY),
+                        ("Folders", &report.purged_folders, Color32::GRAY),
+                        ("Orphan blobs", &report.orphan_blobs, Color32::GRAY),
+                    ] {
+                        if paths.is_empty() {
+                            continue;
+                        }
+
+                        CollapsingHeader::new(format!("{title} ({})", paths.len()))
+                            .id_source(title)
+                            .show(ui, |ui| {
+                                for v in paths {
+                                    ui.label(RichText::new(v).color(color));
+                                }
+                            });
+                    }
+                });
+        }
+
+        ui.separator();
+
+        ui.label(RichText::new("Integrity scrub").color(Color32::WHITE));
+        ui.label("Checks that every served file decompresses to its size and hash");
+
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(!holder.scrub_running, |ui| {
+                if ui.button_s("Scrub", 80., 1.).clicked() {
+                    event = Some(FrontendEvent::Scrub { quarantine: false });
+                }
+
+                if ui.button_s("Scrub and quarantine", 160., 1.).clicked() {
+                    event = Some(FrontendEvent::Scrub { quarantine: true });
+                }
+            });
+
+            if holder.scrub_running {
+                ui.spinner();
+            }
+        });
+
+        if let Some(report) = &holder.scrub_report {
+            ui.label(format!(
+                "Scrubbed at {}: {} files ({}), broken: {}, released: {}",
+                chrono::DateTime::from_timestamp(report.time, 0)
+                    .unwrap()
+                    .with_timezone(&chrono::Local)
+                    .format("%d/%m/%y %H:%M"),
+                report.checked,
+                ByteSize::b(report.checked_size),
+                report.broken.len(),
+                report.released.len()
+            ));
+
+            ScrollArea::vertical()
+                .id_source("scrub_report")
+                .show(ui, |ui| {
+                    if !report.broken.is_empty() {
+                        CollapsingHeader::new(format!(
+                            "{} ({})",
+                            if report.quarantine {
+                                "Quarantined"
+                            } else {
+                                "Broken"
+                            },
+                            report.broken.len()
+                        ))
+                        .id_source("scrub_broken")
+                        .show(ui, |ui| {
+                            for v in &report.broken {
+                                ui.label(
+                                    RichText::new(format!("{} - {}", v.path, v.problem))
+                                        .color(Color32::RED),
+                                );
+                            }
+                        });
+                    }
+
+                    if !report.released.is_empty() {
+                        CollapsingHeader::new(format!("Released ({})", report.released.len()))
+                            .id_source("scrub_released")
+                            .show(ui, |ui| {
+                                for v in &report.released {
+                                    ui.label(RichText::new(v).color(Color32::GRAY));
+                                }
+                            });
+                    }
+                });
+        }
+
+        if let Some(event) = event {
+            self.emit_event(event);
+        }
+    }
+}
@@ -1,6 +1,14 @@
+pub(crate) mod admin_users;
+mod audit;
+mod dashboard;
 mod file_info;
-mod logs;
-mod patchnotes;
+mod login;
+pub(crate) mod logs;
+mod maintenance;
+mod patch_note_history;
+pub(crate) mod patchnotes;
+pub(crate) mod servers;
+mod trash;
 
 use crate::frontend::Frontend;
 use egui::Ui;
@@ -13,8 +21,15 @@ pub enum RightBlockScreen {
     Dashboard,
     PatchNotes,
     EditPatchNote,
+    PatchNoteHistory,
     Files,
     Logs,
+    Audit,
+    Login,
+    AdminUsers,
+    Servers,
+    Maintenance,
+    Trash,
 }
 
 impl Frontend {
@@ -23,17 +38,31 @@ impl Frontend {
             ui.set_width(width);
 
             match &mut self.right_block_screen {
-                RightBlockScreen::Dashboard => {}
+                RightBlockScreen::Dashboard => self.draw_dashboard(ui),
 
                 RightBlockScreen::PatchNotes => self.draw_patch_notes(ui, PATCH_NOTE_WIDTH),
 
                 RightBlockScreen::EditPatchNote => self.draw_patch_note_editor(ui),
 
+                RightBlockScreen::PatchNoteHistory => self.draw_patch_note_history(ui),
+
                 RightBlockScreen::Files => {
                     self.draw_file_infos(ui, &self.backend.file_info_holder.current_dir.clone())
                 }
 
                 RightBlockScreen::Logs => self.draw_logs(ui),
+
+                RightBlockScreen::Audit => self.draw_audit(ui),
+
+                RightBlockScreen::Login => self.draw_login(ui),
+
+                RightBlockScreen::AdminUsers => self.draw_admin_users(ui),
+
+                RightBlockScreen::Servers => self.draw_servers(ui),
+
+                RightBlockScreen::Maintenance => self.draw_maintenance(ui),
+
+                RightBlockScreen::Trash => self.draw_trash(ui),
             }
         });
     }
@@ -0,0 +1,194 @@
+use crate::backend::{FrontendEvent, Screen};
+use crate::frontend::right_block::patchnotes::format_time;
+use crate::frontend::ui_kit::UiKit;
+use crate::frontend::Frontend;
+use egui::{Color32, RichText, ScrollArea, Ui};
+use shared::admin_panel::Locale;
+use similar::{DiffOp, TextDiff};
+use strum::IntoEnumIterator;
+
+const REVISIONS_WIDTH: f32 = 260.;
+
+const REMOVED_COLOR: Color32 = Color32::from_rgb(90, 30, 30);
+const ADDED_COLOR: Color32 = Color32::from_rgb(30, 70, 30);
+
+impl Frontend {
+    pub(crate) fn draw_patch_note_history(&mut self, ui: &mut Ui) {
+        let holder = &self.backend.patch_note_holder;
+        let id = holder.history_id;
+        let mut locale = self.revision_locale;
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Patch note #{id} history"));
+
+            if ui.button_s("Back", 60., 1.).clicked() {
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::EditPatchNote {
+                    id: Some(id),
+                }));
+            }
+
+            if ui.button_s("Refresh", 60., 1.).clicked() {
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::PatchNoteHistory {
+                    id,
+                }));
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(REVISIONS_WIDTH);
+
+                ScrollArea::vertical()
+                    .id_source("patch_note_revisions")
+                    .show(ui, |ui| {
+                        if holder.revisions.is_empty() {
+                            ui.label("No saved revisions");
+                        }
+
+                        for revision in &holder.revisions {
+                            let selected = holder
+                                .compared
+                                .as_ref()
+                                .is_some_and(|v| v.0.id == revision.id);
+
+                            if ui
+                                .selectable_label(
+                                    selected,
+                                    format!(
+                                        "{} {}",
+                                        format_time(revision.time),
+                                        self.admin_name(revision.author)
+                                    ),
+                                )
+                                .on_hover_text(&revision.title)
+                                .clicked()
+                            {
+                                self.emit_event(FrontendEvent::RequestPatchNoteRevision {
+                                    revision: revision.id,
+                                });
+                            }
+                        }
+                    });
+            });
+
+            ui.separator();
+
+            ui.vertical(|ui| {
+                let Some((revision, current)) = &holder.compared else {
+                    ui.label("Select a revision to compare it with the current text");
+                    return;
+                };
+
+                ui.horizontal(|ui| {
+                    for v in Locale::iter() {
+                        ui.selectable_value(&mut locale, v, v.to_string());
+                    }
+
+                    if ui.button_s("Restore", 70., 1.).clicked() {
+                        self.emit_event(FrontendEvent::RestorePatchNoteRevision {
+                            revision: revision.id,
+                        });
+                    }
+                });
+
+                let old = &revision.patch_note;
+                let old_body = old.body(locale).unwrap_or_default();
+                let new_body = current.body(locale).unwrap_or_default();
+
+                ui.columns(2, |columns| {
+                    columns[0].label(
+                        RichText::new(format!(
+                            "Revision {}: {}",
+                            format_time(revision.time),
+                            old.title
+                        ))
+                        .color(Color32::WHITE),
+                    );
+                    columns[1].label(
+                        RichText::new(format!("Current: {}", current.title)).color(Color32::WHITE),
+                    );
+                });
+
+                ui.separator();
+
+                ScrollArea::vertical()
+                    .id_source("patch_note_diff")
+                    .show(ui, |ui| draw_diff(ui, old_body, new_body));
+            });
+        });
+
+        self.revision_locale = locale;
+    }
+}
+
+/// Side by side line diff, changed lines are paired
+fn draw_diff(ui: &mut Ui, old: &str, new: &str) {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    let mut rows = vec![];
+
+    for op in diff.ops() {
+        let (old_range, new_range) = match *op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => {
+                for i in 0..len {
+                    rows.push((
+                        Some(old_lines[old_index + i]),
+                        Some(new_lines[new_index + i]),
+                        false,
+                    ));
+                }
+
+                continue;
+            }
+            DiffOp::Delete {
+                old_index, old_len, ..
+            } => (old_index..old_index + old_len, 0..0),
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => (0..0, new_index..new_index + new_len),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => (
+                old_index..old_index + old_len,
+                new_index..new_index + new_len,
+            ),
+        };
+
+        for i in 0..old_range.len().max(new_range.len()) {
+            rows.push((
+                old_range.clone().nth(i).map(|v| old_lines[v]),
+                new_range.clone().nth(i).map(|v| new_lines[v]),
+                true,
+            ));
+        }
+    }
+
+    // Columns per row keep both sides aligned when lines wrap
+    for (old, new, changed) in rows {
+        ui.columns(2, |columns| {
+            draw_diff_line(&mut columns[0], old, changed, REMOVED_COLOR);
+            draw_diff_line(&mut columns[1], new, changed, ADDED_COLOR);
+        });
+    }
+}
+
+fn draw_diff_line(ui: &mut Ui, line: Option<&str>, changed: bool, color: Color32) {
+    let text = RichText::new(line.unwrap_or_default().trim_end_matches('\n')).monospace();
+
+    ui.label(if changed && line.is_some() {
+        text.background_color(color)
+    } else {
+        text
+    });
+}
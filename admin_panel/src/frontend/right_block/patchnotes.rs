@@ -1,14 +1,175 @@
 use crate::backend::{FrontendEvent, Screen};
 use crate::frontend::easy_mark::easy_mark;
-use crate::frontend::ui_kit::{icon, UiKit, DELETE_TOKEN, EDIT_TOKEN};
+use crate::frontend::ui_kit::{icon, UiKit, DELETE_TOKEN, EDIT_TOKEN, RESTORE_TOKEN};
 use crate::frontend::Frontend;
+use chrono::{NaiveDate, NaiveTime, Utc};
 use eframe::emath::Align;
-use egui::{Color32, CursorIcon, Layout, ScrollArea, Ui};
+use egui::{Color32, CursorIcon, Layout, RichText, ScrollArea, Ui};
+use egui_extras::DatePickerButton;
+use shared::admin_panel::{Locale, PatchNote, PatchNoteStatus};
+use std::collections::BTreeMap;
+use wasm_bindgen_futures::spawn_local;
+
+const MEDIA_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "webp"];
+use strum::IntoEnumIterator;
+
+/// Patch note fields edited besides the text
+pub(crate) struct PatchNoteForm {
+    title: String,
+    /// `None` for new patch note
+    status: Option<PatchNoteStatus>,
+    scheduled: bool,
+    publish_date: NaiveDate,
+    /// `HH:MM`, UTC
+    publish_time: String,
+    /// Locale of the body in the editor
+    locale: Locale,
+    /// Bodies of other locales while one is in the editor
+    bodies: BTreeMap<Locale, String>,
+    pub(crate) media_error: Option<String>,
+}
+
+impl Default for PatchNoteForm {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl PatchNoteForm {
+    pub(crate) fn new(patch_note: Option<&PatchNote>) -> Self {
+        let publish_at = patch_note
+            .and_then(|v| v.publish_at)
+            .and_then(|v| chrono::DateTime::from_timestamp(v, 0))
+            .unwrap_or_else(Utc::now);
+
+        Self {
+            title: patch_note.map(|v| v.title.clone()).unwrap_or_default(),
+            status: patch_note.map(|v| v.status),
+            scheduled: patch_note.is_some_and(|v| v.publish_at.is_some()),
+            publish_date: publish_at.date_naive(),
+            publish_time: publish_at.format("%H:%M").to_string(),
+            locale: Locale::default(),
+            bodies: patch_note
+                .map(|v| v.translations.clone())
+                .unwrap_or_default(),
+            media_error: None,
+        }
+    }
+
+    /// Puts `code` of the current locale aside and returns the body of `locale`
+    fn switch_locale(&mut self, locale: Locale, code: &mut String) {
+        self.bodies.insert(self.locale, std::mem::take(code));
+        *code = self.bodies.remove(&locale).unwrap_or_default();
+        self.locale = locale;
+    }
+
+    fn is_missing(&self, locale: Locale, code: &str) -> bool {
+        let body = if locale == self.locale {
+            Some(code)
+        } else {
+            self.bodies.get(&locale).map(|v| v.as_str())
+        };
+
+        body.is_none_or(|v| v.trim().is_empty())
+    }
+
+    /// Default locale body and translations
+    fn split_bodies(&self, code: &str) -> (String, BTreeMap<Locale, String>) {
+        let mut translations = self.bodies.clone();
+        translations.insert(self.locale, code.to_string());
+
+        let data = translations.remove(&Locale::default()).unwrap_or_default();
+        translations.retain(|_, v| !v.trim().is_empty());
+
+        (data, translations)
+    }
+
+    fn publish_at(&self) -> Option<i64> {
+        if !self.scheduled {
+            return None;
+        }
+
+        let time = NaiveTime::parse_from_str(self.publish_time.trim(), "%H:%M").unwrap_or_default();
+
+        Some(self.publish_date.and_time(time).and_utc().timestamp())
+    }
+}
 
 impl Frontend {
     pub fn draw_patch_notes(&mut self, ui: &mut Ui, width: f32) {
         ui.vertical(|ui| {
-            ui.label(format!("Total: {}", self.backend.patch_note_holder.total));
+            let holder = &self.backend.patch_note_holder;
+            let mut query = holder.query.clone();
+
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Patch notes {}-{} of {}",
+                    (query.skip + 1).min(holder.total),
+                    (query.skip + query.take).min(holder.total),
+                    holder.total
+                ));
+
+                if query.skip > 0 && ui.button_s("Prev", 60., 1.).clicked() {
+                    self.emit_event(FrontendEvent::RequestOpenScreen(Screen::PatchNotes {
+                        skip: query.skip.saturating_sub(query.take),
+                    }));
+                }
+
+                if query.skip + query.take < holder.total && ui.button_s("Next", 60., 1.).clicked()
+                {
+                    self.emit_event(FrontendEvent::RequestOpenScreen(Screen::PatchNotes {
+                        skip: query.skip + query.take,
+                    }));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Status");
+                egui::ComboBox::from_id_source("patch_notes_status")
+                    .selected_text(query.status.map_or("All".to_string(), |v| v.to_string()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut query.status, None, "All");
+
+                        for v in PatchNoteStatus::iter() {
+                            ui.selectable_value(&mut query.status, Some(v), v.to_string());
+                        }
+                    });
+
+                ui.label("Author");
+                egui::ComboBox::from_id_source("patch_notes_author")
+                    .selected_text(
+                        query
+                            .author
+                            .map_or("All".to_string(), |v| self.admin_name(v)),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut query.author, None, "All");
+
+                        if let Some(user) = &self.backend.auth.user {
+                            ui.selectable_value(&mut query.author, Some(user.id), "Me");
+                        }
+
+                        for user in &self.backend.auth.admin_users {
+                            ui.selectable_value(&mut query.author, Some(user.id), &user.name);
+                        }
+                    });
+
+                ui.checkbox(&mut query.with_deleted, "Show deleted");
+            });
+
+            if (query.status, query.author, query.with_deleted)
+                != (
+                    holder.query.status,
+                    holder.query.author,
+                    holder.query.with_deleted,
+                )
+            {
+                self.backend.patch_note_holder.query = query;
+
+                self.emit_event(FrontendEvent::RequestOpenScreen(Screen::PatchNotes {
+                    skip: 0,
+                }));
+            }
 
             if ui.button_s("Create patch note", 0., 0.).clicked() {
                 self.emit_event(FrontendEvent::RequestOpenScreen(Screen::EditPatchNote {
@@ -28,7 +189,23 @@ impl Frontend {
                                 ui.set_width(width);
 
                                 ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
-                                    if ui
+                                    if patch_note.deleted {
+                                        if ui
+                                            .label(
+                                                icon(RESTORE_TOKEN)
+                                                    .size(16.)
+                                                    .color(Color32::DARK_GREEN),
+                                            )
+                                            .on_hover_cursor(CursorIcon::PointingHand)
+                                            .clicked()
+                                        {
+                                            self.emit_event(FrontendEvent::RestorePatchNote {
+                                                id: patch_note.id,
+                                            })
+                                        }
+
+                                        ui.label("Deleted");
+                                    } else if ui
                                         .label(
                                             icon(DELETE_TOKEN).size(16.).color(Color32::DARK_RED),
                                         )
@@ -55,6 +232,10 @@ impl Frontend {
                                             },
                                         ))
                                     }
+
+                                    ui.with_layout(Layout::left_to_right(Align::TOP), |ui| {
+                                        self.draw_patch_note_header(ui, patch_note);
+                                    });
                                 });
 
                                 egui::Frame::default()
@@ -88,14 +269,180 @@ impl Frontend {
 }
 
 impl Frontend {
+    pub(crate) fn admin_name(&self, id: u32) -> String {
+        self.backend
+            .auth
+            .admin_users
+            .iter()
+            .chain(self.backend.auth.user.iter())
+            .find(|v| v.id == id)
+            .map(|v| v.name.clone())
+            .unwrap_or_else(|| format!("User {id}"))
+    }
+
+    fn draw_patch_note_header(&self, ui: &mut Ui, patch_note: &PatchNote) {
+        ui.label(
+            RichText::new(format!("#{} {}", patch_note.id, patch_note.title)).color(Color32::WHITE),
+        );
+        ui.label(
+            RichText::new(patch_note.status.to_string()).color(match patch_note.status {
+                PatchNoteStatus::Draft => Color32::GRAY,
+                PatchNoteStatus::Published => Color32::LIGHT_GREEN,
+                PatchNoteStatus::Archived => Color32::DARK_GRAY,
+            }),
+        );
+        ui.label(self.admin_name(patch_note.author));
+
+        let missing = patch_note.missing_locales();
+        if !missing.is_empty() {
+            ui.label(
+                RichText::new(format!(
+                    "No {}",
+                    missing
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+                .color(Color32::LIGHT_RED),
+            )
+            .on_hover_text("Missing translations");
+        }
+
+        ui.label(format!("Updated {}", format_time(patch_note.updated_at)))
+            .on_hover_text(format!("Created {}", format_time(patch_note.created_at)));
+
+        if let Some(time) = patch_note.published_at {
+            ui.label(format!("Published {}", format_time(time)));
+        }
+
+        if let Some(time) = patch_note.publish_at {
+            ui.label(
+                RichText::new(format!("Scheduled {}", format_time(time))).color(Color32::GOLD),
+            );
+        }
+    }
+
     pub fn draw_patch_note_editor(&mut self, ui: &mut Ui) {
         ui.vertical(|ui| {
+            let form = &mut self.patch_note_form;
+
+            egui::Grid::new("patch_note_form").show(ui, |ui| {
+                ui.label("Title");
+                ui.text_edit_singleline(&mut form.title);
+                ui.end_row();
+
+                ui.checkbox(&mut form.scheduled, "Publish at (UTC)");
+                ui.add_enabled_ui(form.scheduled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DatePickerButton::new(&mut form.publish_date).id_source("publish_at"),
+                        );
+                        ui.add(
+                            egui::TextEdit::singleline(&mut form.publish_time).desired_width(50.),
+                        );
+                    });
+                });
+                ui.end_row();
+
+                ui.label("Language");
+                ui.horizontal(|ui| {
+                    for locale in Locale::iter() {
+                        let missing = form.is_missing(locale, &self.markup_editor.code);
+
+                        let text = RichText::new(locale.to_string());
+                        let text = if missing {
+                            text.color(Color32::LIGHT_RED)
+                        } else {
+                            text
+                        };
+
+                        let response = ui.selectable_label(form.locale == locale, text);
+                        let response = if missing {
+                            response.on_hover_text("Missing translation")
+                        } else {
+                            response
+                        };
+
+                        if response.clicked() && form.locale != locale {
+                            form.switch_locale(locale, &mut self.markup_editor.code);
+                        }
+                    }
+                });
+                ui.end_row();
+            });
+
+            if let (Some(id), Some(status)) = (self.markup_editor.edit_id, form.status) {
+                ui.horizontal(|ui| {
+                    if ui.button_s("History", 70., 1.).clicked() {
+                        self.emit_event(FrontendEvent::RequestOpenScreen(
+                            Screen::PatchNoteHistory { id },
+                        ));
+                    }
+
+                    ui.label(format!("Status: {status}"));
+
+                    for v in PatchNoteStatus::iter().filter(|v| *v != status) {
+                        let text = match v {
+                            PatchNoteStatus::Draft => "To draft",
+                            PatchNoteStatus::Published => "Publish",
+                            PatchNoteStatus::Archived => "Archive",
+                        };
+
+                        if ui.button_s(text, 70., 1.).clicked() {
+                            self.emit_event(FrontendEvent::SetPatchNoteStatus { id, status: v });
+                        }
+                    }
+                });
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button_s("Add image", 80., 1.).clicked() {
+                    let t = self.to_backend.clone();
+
+                    spawn_local(async move {
+                        if let Some(file) = rfd::AsyncFileDialog::new()
+                            .add_filter("Images", &MEDIA_EXTENSIONS)
+                            .pick_file()
+                            .await
+                        {
+                            let data = file.read().await;
+
+                            t.send(FrontendEvent::UploadMedia {
+                                name: file.file_name(),
+                                data,
+                            })
+                            .unwrap();
+                        }
+                    })
+                }
+
+                if let Some(error) = &self.patch_note_form.media_error {
+                    ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+                }
+            });
+
+            ui.separator();
+
             if self.markup_editor.ui(ui).clicked() {
+                let (data, translations) =
+                    self.patch_note_form.split_bodies(&self.markup_editor.code);
+
                 self.emit_event(FrontendEvent::SavePatchNote {
                     id: self.markup_editor.edit_id,
-                    data: self.markup_editor.code.clone(),
+                    title: self.patch_note_form.title.clone(),
+                    data,
+                    translations,
+                    publish_at: self.patch_note_form.publish_at(),
                 })
             };
         });
     }
 }
+
+pub(crate) fn format_time(time: i64) -> String {
+    chrono::DateTime::from_timestamp(time, 0)
+        .unwrap()
+        .format("%d/%m/%y %H:%M")
+        .to_string()
+}
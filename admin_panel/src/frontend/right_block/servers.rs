@@ -0,0 +1,108 @@
+use crate::backend::FrontendEvent;
+use crate::frontend::ui_kit::UiKit;
+use crate::frontend::Frontend;
+use eframe::epaint::Color32;
+use egui::{RichText, Ui};
+
+#[derive(Default)]
+pub(crate) struct ServerForm {
+    pub(crate) name: String,
+    pub(crate) url: String,
+    pub(crate) error: Option<String>,
+}
+
+impl Frontend {
+    pub(crate) fn draw_servers(&mut self, ui: &mut Ui) {
+        let servers = &self.backend.servers;
+        let mut form = std::mem::take(&mut self.server_form);
+
+        ui.vertical(|ui| {
+            ui.label(
+                RichText::new(format!(
+                    "Connected to {} ({})",
+                    servers.current_name(),
+                    servers.current
+                ))
+                .color(Color32::WHITE),
+            );
+
+            if !servers.is_saved() {
+                ui.label(
+                    RichText::new("The server comes from the page link and is not saved")
+                        .color(Color32::from_rgb(238, 62, 62)),
+                );
+
+                if ui.button_s("Save it", 100., 1.).clicked() {
+                    form.url = servers.current.clone();
+                }
+            }
+
+            ui.separator();
+
+            egui::Grid::new("servers").show(ui, |ui| {
+                if !servers.is_saved() {
+                    ui.label(RichText::new("From link").color(Color32::WHITE));
+                    ui.label(&servers.current);
+                    ui.label("Connected");
+                    ui.end_row();
+                }
+
+                for (name, url, removable) in
+                    std::iter::once(("This site", &servers.origin_url, false)).chain(
+                        servers
+                            .servers
+                            .iter()
+                            .map(|v| (v.name.as_str(), &v.url, true)),
+                    )
+                {
+                    let current = *url == servers.current;
+
+                    ui.label(RichText::new(name).color(if current {
+                        Color32::WHITE
+                    } else {
+                        Color32::GRAY
+                    }));
+                    ui.label(url);
+
+                    if current {
+                        ui.label("Connected");
+                    } else if ui.button_s("Connect", 70., 1.).clicked() {
+                        self.emit_event(FrontendEvent::SelectServer { url: url.clone() });
+                    }
+
+                    if removable && ui.button_s("Remove", 70., 1.).clicked() {
+                        self.emit_event(FrontendEvent::RemoveServer { url: url.clone() });
+                    }
+
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+
+            egui::Grid::new("server_form").show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut form.name);
+                ui.end_row();
+
+                ui.label("Url");
+                ui.text_edit_singleline(&mut form.url)
+                    .on_hover_text("wss://example.com/ws, an http(s) address works too");
+                ui.end_row();
+            });
+
+            if ui.button_s("Add server", 100., 1.).clicked() {
+                self.emit_event(FrontendEvent::AddServer {
+                    name: form.name.clone(),
+                    url: form.url.clone(),
+                });
+            }
+
+            if let Some(error) = &form.error {
+                ui.label(RichText::new(error).color(Color32::from_rgb(238, 62, 62)));
+            }
+        });
+
+        self.server_form = form;
+    }
+}
@@ -0,0 +1,145 @@
+use crate::backend::FrontendEvent;
+use crate::frontend::dialog::Dialog;
+use crate::frontend::ui_kit::{icon, UiKit, DELETE_TOKEN, RESTORE_TOKEN};
+use crate::frontend::Frontend;
+use bytesize::ByteSize;
+use eframe::epaint::Color32;
+use egui::{RichText, ScrollArea, Ui};
+
+fn format_time(time: Option<i64>) -> String {
+    time.and_then(|v| chrono::DateTime::from_timestamp(v, 0))
+        .map(|v| {
+            v.with_timezone(&chrono::Local)
+                .format("%d/%m/%y %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|| "-".to_string())
+}
+
+impl Frontend {
+    pub(crate) fn draw_trash(&mut self, ui: &mut Ui) {
+        let mut event = None;
+        let mut dialog = None;
+
+        let holder = &mut self.backend.trash;
+
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Deleted entries: {} ({}), selected: {}",
+                holder.entries.len(),
+                ByteSize::b(holder.size()),
+                holder.selected.len()
+            ));
+
+            if ui.button_s("Select all", 80., 1.).clicked() {
+                holder.selected = holder.entries.iter().map(|v| v.item.clone()).collect();
+            }
+
+            if ui.button_s("Select none", 80., 1.).clicked() {
+                holder.selected.clear();
+            }
+
+            ui.add_enabled_ui(!holder.selected.is_empty(), |ui| {
+                if ui.button_s("Restore", 80., 1.).clicked() {
+                    event = Some(FrontendEvent::RestoreFromTrash(
+                        holder.selected.iter().cloned().collect(),
+                    ));
+                }
+
+                if ui.button_s("Purge", 80., 1.).clicked() {
+                    dialog = Some(Dialog::PurgeFromTrash {
+                        items: holder.selected.iter().cloned().collect(),
+                    });
+                }
+            });
+        });
+
+        ui.label(
+            "Purge removes entries permanently, others are purged automatically at the shown time",
+        );
+
+        for v in &holder.errors {
+            ui.label(RichText::new(v).color(Color32::RED));
+        }
+
+        ui.separator();
+
+        ScrollArea::vertical().id_source("trash").show(ui, |ui| {
+            for entry in &holder.entries {
+                ui.horizontal(|ui| {
+                    let mut selected = holder.selected.contains(&entry.item);
+
+                    if ui.checkbox(&mut selected, "").changed() {
+                        if selected {
+                            holder.selected.insert(entry.item.clone());
+                        } else {
+                            holder.selected.remove(&entry.item);
+                        }
+                    }
+
+                    ui.scope(|ui| {
+                        ui.set_width(300.);
+
+                        ui.label(if entry.item.folder {
+                            RichText::new(format!("{}/", entry.item.path))
+                                .color(Color32::LIGHT_BLUE)
+                        } else {
+                            RichText::new(&entry.item.path).color(Color32::WHITE)
+                        });
+                    });
+
+                    ui.scope(|ui| {
+                        ui.set_width(70.);
+                        ui.label(ByteSize::b(entry.size).to_string());
+                    });
+
+                    ui.scope(|ui| {
+                        ui.set_width(200.);
+                        ui.label(format!(
+                            "{} by {}",
+                            format_time(entry.deleted_at),
+                            match (&entry.deleted_by_name, entry.deleted_by) {
+                                (Some(name), _) => name.clone(),
+                                (None, Some(id)) => format!("User {id}"),
+                                (None, None) => "-".to_string(),
+                            }
+                        ));
+                    });
+
+                    ui.scope(|ui| {
+                        ui.set_width(130.);
+                        ui.label(format!("Purge at {}", format_time(entry.purge_at)));
+                    });
+
+                    if ui
+                        .clickable_label(icon(RESTORE_TOKEN).size(16.).color(Color32::DARK_GREEN))
+                        .on_hover_text("Restore")
+                        .clicked()
+                    {
+                        event = Some(FrontendEvent::RestoreFromTrash(vec![entry.item.clone()]));
+                    }
+
+                    if ui
+                        .clickable_label(icon(DELETE_TOKEN).size(16.).color(Color32::DARK_RED))
+                        .on_hover_text("Purge")
+                        .clicked()
+                    {
+                        dialog = Some(Dialog::PurgeFromTrash {
+                            items: vec![entry.item.clone()],
+                        });
+                    }
+                });
+
+                ui.separator();
+            }
+        });
+
+        if let Some(dialog) = dialog {
+            self.show_dialog(dialog);
+        }
+
+        if let Some(event) = event {
+            self.emit_event(event);
+        }
+    }
+}
@@ -3,16 +3,16 @@ mod backend;
 mod frontend;
 
 use crate::app::App;
+use crate::backend::auth::parse_tg_auth_result;
 use crate::backend::Backend;
 
+use crate::frontend::easy_mark::set_media_base_url;
 use crate::frontend::setup_custom_fonts;
 pub use eframe::{WebLogger, WebOptions, WebRunner};
 use gloo_timers::future::TimeoutFuture;
 use std::sync::mpsc::channel;
 use wasm_bindgen_futures::spawn_local;
 
-const WS_SERVER: &str = "ws://127.0.0.1:3000/ws";
-
 /**
 TODO:
  - Real work with files
@@ -31,6 +31,7 @@ fn main() {
                 web_options,
                 Box::new(|cc| {
                     setup_custom_fonts(&cc.egui_ctx);
+                    egui_extras::install_image_loaders(&cc.egui_ctx);
 
                     let ctx = cc.egui_ctx.clone();
 
@@ -42,8 +43,26 @@ fn main() {
                         }
                     });
 
+                    let location = web_sys::window().map(|w| w.location());
+
+                    let origin = location
+                        .as_ref()
+                        .and_then(|l| l.origin().ok())
+                        .unwrap_or_default();
+
+                    let pending_login = location
+                        .as_ref()
+                        .and_then(|l| l.hash().ok())
+                        .and_then(|h| parse_tg_auth_result(&h));
+
+                    if let (Some(location), Some(_)) = (&location, &pending_login) {
+                        let _ = location.set_hash("");
+                    }
+
                     let (sender, receiver) = channel();
-                    let mut backend = Backend::new(receiver);
+                    let mut backend = Backend::new(receiver, origin, pending_login);
+
+                    set_media_base_url(&cc.egui_ctx, backend.servers.http_url());
 
                     backend.debug("Started...");
 
@@ -1,45 +1,332 @@
-use std::cmp::Ordering;
 use shared::file::{
     compress_in_mem, hash_of, ServerFileInfo, ServerFolderInfo, COMPRESSED_FOLDER_NAME,
-    ROOT_FOLDER_INFO_FILE_NAME,
+    PREPARED_PREFIX, ROOT_FOLDER_INFO_FILE_NAME, ROOT_FOLDER_INFO_VERSION,
 };
+use shared::storage::{BlobStorage, FsStorage, S3Config, S3Storage};
+use shared::versioned::Versioned;
+use std::collections::HashSet;
 use std::env;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::os::unix::fs::MetadataExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::Instant;
+use tokio::runtime::Runtime;
 use walkdir::WalkDir;
 
-fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() < 2 {
-        panic!("Error: No path provided!\nUsage: preparator \"path/to/folder\"");
+const MB: f64 = 1024. * 1024.;
+
+const USAGE: &str = "Usage: preparator [--incremental] [--threads N] \"path/to/folder\"";
+
+struct Args {
+    path_to_folder: String,
+    /// Reuse the list of the previous run and its blobs, only changed files are compressed
+    incremental: bool,
+    /// Files hashed and compressed at once, all cores by default
+    threads: usize,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut path_to_folder = None;
+        let mut incremental = false;
+        let mut threads = std::thread::available_parallelism().map_or(1, |v| v.get());
+
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--incremental" => incremental = true,
+                "--threads" => {
+                    threads = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|v| *v > 0)
+                        .unwrap_or_else(|| panic!("Error: --threads needs a number!\n{USAGE}"))
+                }
+                _ if arg.starts_with("--") => panic!("Error: Unknown option {arg}!\n{USAGE}"),
+                _ => path_to_folder = Some(arg),
+            }
+        }
+
+        let Some(path_to_folder) = path_to_folder else {
+            panic!("Error: No path provided!\n{USAGE}");
+        };
+
+        Self {
+            path_to_folder,
+            incremental,
+            threads,
+        }
     }
+}
 
-    let current_dir = env::current_dir().unwrap();
+/// Files which were compressed, reused as is, reused after their hash was checked and deleted
+#[derive(Default)]
+struct Summary {
+    compressed: u32,
+    unchanged: u32,
+    rehashed: u32,
+    deleted: u32,
+    /// Bytes of the files which were read
+    read: u64,
+    /// Bytes of the compressed files before and after compression
+    original: u64,
+    written: u64,
+}
 
-    let path_to_folder = &args[1];
+/// A file which has to be read, the previous entry is reused if its hash is the same
+struct Job {
+    rel_path: String,
+    path: PathBuf,
+    size: u64,
+    modified_at: i64,
+    previous: Option<ServerFileInfo>,
+    /// The blob of the previous entry is in the storage
+    blob_exists: bool,
+}
 
-    if !Path::new(path_to_folder).exists() {
-        panic!("Folder {path_to_folder} not exists!")
+struct Processed {
+    rel_path: String,
+    info: ServerFileInfo,
+    /// `None` if the file was not compressed
+    compressed_size: Option<u64>,
+}
+
+/// Compressed files are staged, the server moves them to their keys when it merges the list
+fn staged_key(rel_path: &str) -> String {
+    format!("{PREPARED_PREFIX}{rel_path}")
+}
+
+/// Errors name the file, so a failed run tells what to fix
+fn process(
+    job: Job,
+    storage: &dyn BlobStorage,
+    runtime: &Runtime,
+    now: i64,
+) -> Result<Processed, String> {
+    let mut bytes = Vec::with_capacity(job.size as usize);
+
+    File::open(&job.path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| format!("Can't read {:?}: {e}", job.path))?;
+
+    let hash = hash_of(&bytes);
+
+    match &job.previous {
+        // Touched, but the content is the same
+        Some(info) if job.blob_exists && info.hash == hash => {
+            println!("{} - {} unchanged", job.rel_path, hash);
+
+            Ok(Processed {
+                rel_path: job.rel_path,
+                info: ServerFileInfo {
+                    size: bytes.len() as u64,
+                    source_modified_at: Some(job.modified_at),
+                    deleted: false,
+                    deleted_at: None,
+                    deleted_by: None,
+                    ..info.clone()
+                },
+                compressed_size: None,
+            })
+        }
+        previous => {
+            let mut compressed = vec![];
+
+            compress_in_mem(&bytes, &mut compressed)
+                .map_err(|e| format!("Can't compress {:?}: {e}", job.path))?;
+
+            let compressed_size = compressed.len() as u64;
+
+            runtime
+                .block_on(storage.put_bytes(&staged_key(&job.rel_path), compressed))
+                .map_err(|e| format!("Can't store {}: {e}", job.rel_path))?;
+
+            println!("{} - {}", job.rel_path, hash);
+
+            Ok(Processed {
+                info: ServerFileInfo {
+                    hash,
+                    size: bytes.len() as u64,
+                    created_at: previous.as_ref().map_or(now, |v| v.created_at),
+                    updated_at: now,
+                    skip_hash_check: previous.as_ref().is_some_and(|v| v.skip_hash_check),
+                    deleted: false,
+                    updated_by: 0,
+                    deleted_at: None,
+                    deleted_by: None,
+                    quarantined: false,
+                    source_modified_at: Some(job.modified_at),
+                },
+                rel_path: job.rel_path,
+                compressed_size: Some(compressed_size),
+            })
+        }
+    }
+}
+
+/// Processes the jobs on `threads` workers, results are in the order of the jobs
+fn process_all(
+    jobs: Vec<Job>,
+    threads: usize,
+    storage: &dyn BlobStorage,
+    runtime: &Runtime,
+    now: i64,
+) -> Vec<Result<Processed, String>> {
+    let count = jobs.len();
+    let jobs = Mutex::new(jobs.into_iter().enumerate());
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::scope(|s| {
+        for _ in 0..threads.min(count) {
+            let tx = tx.clone();
+            let jobs = &jobs;
+
+            s.spawn(move || loop {
+                let Some((i, job)) = jobs.lock().unwrap().next() else {
+                    break;
+                };
+
+                tx.send((i, process(job, storage, runtime, now))).unwrap();
+            });
+        }
+    });
+
+    drop(tx);
+
+    let mut results: Vec<_> = rx.into_iter().collect();
+
+    // Workers finish in any order
+    results.sort_by_key(|(i, _)| *i);
+
+    results.into_iter().map(|(_, v)| v).collect()
+}
+
+/// Reads the list written by the previous run, `None` if there is none
+fn load_root_folder(path: &Path) -> Option<ServerFolderInfo> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => panic!("Can't read {path:?}: {e}"),
+    };
+
+    // Lists of version 1 were written without the envelope
+    let document =
+        ron::de::from_str::<Versioned<ServerFolderInfo>>(&text).unwrap_or_else(|_| Versioned {
+            version: 1,
+            data: ron::de::from_str(&text).unwrap(),
+        });
+
+    if document.version > ROOT_FOLDER_INFO_VERSION {
+        panic!(
+            "{path:?} version {} is newer than supported {ROOT_FOLDER_INFO_VERSION}, update the preparator",
+            document.version
+        );
+    }
+
+    Some(document.data)
+}
+
+/// Gets the folder, creates it and its parents if needed. Deleted ones are restored
+fn folder_mut<'a>(root: &'a mut ServerFolderInfo, path: &str) -> &'a mut ServerFolderInfo {
+    let mut current_folder = root;
+
+    for name in path.split('/').filter(|v| !v.is_empty()) {
+        current_folder = current_folder.folders.entry(name.to_string()).or_default();
+
+        current_folder.deleted = false;
+        current_folder.deleted_at = None;
+        current_folder.deleted_by = None;
+    }
+
+    current_folder
+}
+
+/// Marks files and folders which are not in the source folder anymore as deleted by the server
+fn mark_vanished(
+    folder: &mut ServerFolderInfo,
+    path: &str,
+    seen: &HashSet<String>,
+    now: i64,
+    summary: &mut Summary,
+) {
+    let join = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{path}/{name}")
+        }
+    };
+
+    for (name, file) in &mut folder.files {
+        if !file.deleted && !seen.contains(&join(name)) {
+            println!("{} - deleted", join(name));
+
+            file.deleted = true;
+            file.deleted_at = Some(now);
+            file.deleted_by = Some(0);
+            summary.deleted += 1;
+        }
     }
 
-    let root_folder_path = current_dir.join(COMPRESSED_FOLDER_NAME);
+    for (name, child) in &mut folder.folders {
+        if child.deleted {
+            continue;
+        }
+
+        if !seen.contains(&join(name)) {
+            println!("{} - deleted folder", join(name));
+
+            child.deleted = true;
+            child.deleted_at = Some(now);
+            child.deleted_by = Some(0);
 
-    let mut root_folder = ServerFolderInfo::default();
+            continue;
+        }
 
-    if root_folder_path.is_dir() {
-        std::fs::remove_dir_all(&root_folder_path).unwrap();
+        mark_vanished(child, &join(name), seen, now, summary);
     }
+}
 
-    std::fs::create_dir(&root_folder_path).unwrap();
+/// Lists the source folder, hashes and compresses the files which changed since `previous`
+///
+/// `previous` is the list of the last run, a run without it compresses every file. The server
+/// keeps the blobs of merged lists, so only staged blobs of earlier runs are removed. Every file
+/// is tried, errors of the failed ones are returned together
+fn prepare(
+    path_to_folder: &str,
+    previous: Option<ServerFolderInfo>,
+    threads: usize,
+    storage: &dyn BlobStorage,
+    runtime: &Runtime,
+    now: i64,
+) -> Result<(ServerFolderInfo, Summary), Vec<String>> {
+    let (mut root_folder, blobs) = match previous {
+        Some(root_folder) => {
+            let blobs: HashSet<String> = runtime
+                .block_on(storage.list(""))
+                .unwrap()
+                .into_iter()
+                .collect();
+
+            (root_folder, blobs)
+        }
+        None => {
+            for key in runtime.block_on(storage.list(PREPARED_PREFIX)).unwrap() {
+                runtime.block_on(storage.delete(&key)).unwrap();
+            }
 
-    let mut current_folder = &mut root_folder;
-    let mut current_depth = 1;
+            (ServerFolderInfo::default(), HashSet::new())
+        }
+    };
 
-    for entry in
-        WalkDir::new(path_to_folder).sort_by(|a, b| a.path().is_dir().cmp(&b.path().is_dir()))
-    {
+    let mut seen = HashSet::new();
+    let mut summary = Summary::default();
+    let mut jobs = vec![];
+
+    for entry in WalkDir::new(path_to_folder).sort_by_file_name() {
         let Ok(entry) = entry else { continue };
 
         if entry.depth() == 0 {
@@ -54,99 +341,459 @@ fn main() {
             .trim_matches('/')
             .to_string();
 
-        match entry.depth().cmp(&current_depth) {
-            Ordering::Less => {
-                println!("Relocate required");
-
-                current_folder = &mut root_folder;
-
-                let chain: Vec<_> = rel_path.split('/').collect();
-
-                for folder_name in &chain[0..chain.len() - 1] {
-                    println!("relocating to {}", folder_name);
-
-                    current_folder = current_folder.folders.get_mut(*folder_name).unwrap();
-                }
-                current_depth = entry.depth();
-            }
-            Ordering::Equal => {}
-            Ordering::Greater => {
-                let parent = entry.path().parent().unwrap();
-
-                println!(
-                    "deeper to {}",
-                    parent.file_name().unwrap().to_str().unwrap()
-                );
-
-                current_folder = current_folder
-                    .folders
-                    .get_mut(parent.file_name().unwrap().to_str().unwrap())
-                    .unwrap();
-                current_depth = entry.depth();
-            }
-        }
+        seen.insert(rel_path.clone());
 
         if entry.path().is_dir() {
             println!("{} - folder", rel_path);
-            current_folder.folders.insert(
-                entry.file_name().to_str().unwrap().to_string(),
-                ServerFolderInfo::default(),
-            );
+
+            folder_mut(&mut root_folder, &rel_path);
 
             continue;
         }
 
-        let compressed_path = root_folder_path.join(&rel_path);
-        let compressed_folder = compressed_path.parent().unwrap();
+        let (folder_path, file_name) = rel_path.rsplit_once('/').unwrap_or(("", &rel_path));
+        let folder = folder_mut(&mut root_folder, folder_path);
 
-        if !compressed_folder.is_dir() {
-            std::fs::create_dir_all(compressed_folder).unwrap();
-        }
+        let metadata = entry.metadata().unwrap();
+        let size = metadata.len();
+        let modified_at = metadata.mtime();
 
-        let mut file = File::open(entry.path()).unwrap();
+        let previous = folder.files.get(file_name).cloned();
+        // A file without its blob is compressed again, it is either merged or still staged
+        let blob_exists = blobs.contains(&rel_path) || blobs.contains(&staged_key(&rel_path));
 
-        let mut bytes = Vec::with_capacity(file.metadata().unwrap().size() as usize);
+        if blob_exists
+            && previous.as_ref().is_some_and(|v| {
+                !v.deleted && v.size == size && v.source_modified_at == Some(modified_at)
+            })
+        {
+            summary.unchanged += 1;
 
-        file.read_to_end(&mut bytes).unwrap();
+            continue;
+        }
 
-        let hash = hash_of(&bytes);
+        jobs.push(Job {
+            rel_path,
+            path: entry.into_path(),
+            size,
+            modified_at,
+            previous,
+            blob_exists,
+        });
+    }
 
-        let mut file = File::create(compressed_path).unwrap();
+    println!("Processing {} files on {threads} threads", jobs.len());
 
-        compress_in_mem(&bytes, &mut file).unwrap();
+    let mut errors = vec![];
 
-        println!("{} - {}", rel_path, hash);
+    for processed in process_all(jobs, threads, storage, runtime, now) {
+        let processed = match processed {
+            Ok(v) => v,
+            Err(e) => {
+                errors.push(e);
 
-        let info = ServerFileInfo {
-            hash,
-            size: file.metadata().unwrap().size(),
-            created_at: chrono::Utc::now().timestamp(),
-            updated_at: chrono::Utc::now().timestamp(),
-            skip_hash_check: false,
-            deleted: false,
+                continue;
+            }
         };
 
-        current_folder
+        let rel_path = processed.rel_path;
+        let (folder_path, file_name) = rel_path.rsplit_once('/').unwrap_or(("", &rel_path));
+
+        summary.read += processed.info.size;
+
+        match processed.compressed_size {
+            Some(compressed_size) => {
+                summary.compressed += 1;
+                summary.original += processed.info.size;
+                summary.written += compressed_size;
+            }
+            None => summary.rehashed += 1,
+        }
+
+        folder_mut(&mut root_folder, folder_path)
             .files
-            .insert(entry.file_name().to_str().unwrap().to_string(), info);
+            .insert(file_name.to_string(), processed.info);
     }
 
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    mark_vanished(&mut root_folder, "", &seen, now, &mut summary);
+
     root_folder.calc_size();
 
+    Ok((root_folder, summary))
+}
+
+fn to_ron(root_folder: ServerFolderInfo) -> String {
+    let document = Versioned {
+        version: ROOT_FOLDER_INFO_VERSION,
+        data: root_folder,
+    };
+
+    ron::ser::to_string_pretty(&document, ron::ser::PrettyConfig::default()).unwrap()
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let current_dir = env::current_dir().unwrap();
+
+    let path_to_folder = &args.path_to_folder;
+
+    if !Path::new(path_to_folder).exists() {
+        panic!("Folder {path_to_folder} not exists!")
+    }
+
+    // Workers upload from their own threads
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // Same storage as the server: S3 when `S3_ENDPOINT` is set, `./compressed` otherwise
+    let storage: Box<dyn BlobStorage> = match S3Config::from_env() {
+        Some(config) => Box::new(S3Storage::new(config).unwrap()),
+        None => Box::new(FsStorage::new(current_dir.join(COMPRESSED_FOLDER_NAME))),
+    };
+
     let out_path = current_dir.join(Path::new("./database").join(ROOT_FOLDER_INFO_FILE_NAME));
 
-    let mut file = File::create(out_path.clone()).unwrap();
+    let previous = args.incremental.then(|| {
+        load_root_folder(&out_path).unwrap_or_else(|| {
+            println!("No file list at {out_path:?}, every file is compressed");
+
+            ServerFolderInfo::default()
+        })
+    });
+
+    let now = chrono::Utc::now().timestamp();
+
+    let started = Instant::now();
+
+    let (root_folder, summary) = match prepare(
+        path_to_folder,
+        previous,
+        args.threads,
+        storage.as_ref(),
+        &runtime,
+        now,
+    ) {
+        Ok(v) => v,
+        Err(errors) => {
+            for e in &errors {
+                eprintln!("{e}");
+            }
 
-    file.write_all(
-        ron::ser::to_string_pretty(&root_folder, ron::ser::PrettyConfig::default())
-            .unwrap()
-            .as_bytes(),
-    )
-    .unwrap();
+            panic!(
+                "{} files failed, the file list is not written",
+                errors.len()
+            );
+        }
+    };
+
+    let elapsed = started.elapsed().as_secs_f64();
 
+    // The server never sees a partially written list
+    let tmp_path = out_path.with_extension("ron.tmp");
+    let mut file = File::create(&tmp_path).unwrap();
+
+    file.write_all(to_ron(root_folder).as_bytes()).unwrap();
+    file.sync_all().unwrap();
+    std::fs::rename(&tmp_path, &out_path).unwrap();
+
+    println!(
+        "Compressed: {}, unchanged: {}, unchanged after hashing: {}, deleted: {}",
+        summary.compressed, summary.unchanged, summary.rehashed, summary.deleted
+    );
+    println!(
+        "Read {:.1} MB in {elapsed:.1}s ({:.1} MB/s), compressed {:.1} MB to {:.1} MB (ratio {:.3})",
+        summary.read as f64 / MB,
+        summary.read as f64 / MB / elapsed.max(f64::EPSILON),
+        summary.original as f64 / MB,
+        summary.written as f64 / MB,
+        summary.written as f64 / summary.original.max(1) as f64,
+    );
     println!(
-        "Compressed files folder: {}",
-        root_folder_path.to_str().unwrap()
+        "Compressed files: {}, staged under {PREPARED_PREFIX} until the server merges the list",
+        storage.describe()
     );
     println!("Generated file list: {}", out_path.to_str().unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::storage::MemoryStorage;
+    use std::time::{Duration, SystemTime};
+
+    struct Fixture {
+        dir: PathBuf,
+        storage: MemoryStorage,
+        runtime: Runtime,
+    }
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &[u8])]) -> Self {
+            let dir = env::temp_dir().join(format!("preparator_{}_{name}", std::process::id()));
+
+            let _ = std::fs::remove_dir_all(&dir);
+
+            for (path, data) in files {
+                self::write(&dir, path, data);
+            }
+
+            Self {
+                dir,
+                storage: MemoryStorage::default(),
+                runtime: tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap(),
+            }
+        }
+
+        fn source(&self) -> String {
+            self.dir.to_string_lossy().to_string()
+        }
+
+        fn run(&self, previous: Option<ServerFolderInfo>, now: i64) -> (ServerFolderInfo, Summary) {
+            self.run_on(previous, 4, now)
+        }
+
+        fn run_on(
+            &self,
+            previous: Option<ServerFolderInfo>,
+            threads: usize,
+            now: i64,
+        ) -> (ServerFolderInfo, Summary) {
+            prepare(
+                &self.source(),
+                previous,
+                threads,
+                &self.storage,
+                &self.runtime,
+                now,
+            )
+            .unwrap()
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.runtime.block_on(self.storage.list("")).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn write(dir: &Path, path: &str, data: &[u8]) {
+        let path = dir.join(path);
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn set_modified(dir: &Path, path: &str, secs: u64) {
+        File::options()
+            .write(true)
+            .open(dir.join(path))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn file<'a>(root: &'a ServerFolderInfo, path: &str) -> &'a ServerFileInfo {
+        let (folder_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+        let mut folder = root;
+        for name in folder_path.split('/').filter(|v| !v.is_empty()) {
+            folder = &folder.folders[name];
+        }
+
+        &folder.files[name]
+    }
+
+    #[test]
+    fn only_changed_files_are_compressed() {
+        let fixture = Fixture::new(
+            "changed",
+            &[("a.bin", b"a"), ("data/b.bin", b"b"), ("data/c.bin", b"c")],
+        );
+
+        let (root, summary) = fixture.run(None, 100);
+
+        assert_eq!(summary.compressed, 3);
+        assert_eq!(
+            fixture.keys(),
+            vec![
+                ".prepared/a.bin",
+                ".prepared/data/b.bin",
+                ".prepared/data/c.bin"
+            ]
+        );
+
+        // Touched with the same content, and changed
+        set_modified(&fixture.dir, "data/b.bin", 1_000_000);
+        write(&fixture.dir, "data/c.bin", b"cc");
+
+        let (next, summary) = fixture.run(Some(root.clone()), 200);
+
+        assert_eq!(
+            (summary.unchanged, summary.rehashed, summary.compressed),
+            (1, 1, 1)
+        );
+
+        let a = file(&next, "a.bin");
+        assert_eq!((a.created_at, a.updated_at), (100, 100));
+
+        let b = file(&next, "data/b.bin");
+        assert_eq!(b.hash, file(&root, "data/b.bin").hash);
+        assert_eq!((b.created_at, b.updated_at), (100, 100));
+        assert_eq!(b.source_modified_at, Some(1_000_000));
+
+        let c = file(&next, "data/c.bin");
+        assert_eq!(c.hash, hash_of(b"cc"));
+        assert_eq!((c.created_at, c.updated_at), (100, 200));
+        assert_eq!(
+            fixture
+                .runtime
+                .block_on(fixture.storage.get_bytes(".prepared/data/c.bin"))
+                .unwrap()
+                .len() as u64,
+            summary.written
+        );
+    }
+
+    #[test]
+    fn files_without_blobs_are_compressed_again() {
+        let fixture = Fixture::new("blobs", &[("a.bin", b"a"), ("b.bin", b"b")]);
+
+        let (root, _) = fixture.run(None, 100);
+
+        // Merged by the server, then lost
+        fixture
+            .runtime
+            .block_on(fixture.storage.copy(".prepared/a.bin", "a.bin"))
+            .unwrap();
+        for key in fixture.keys() {
+            if key.starts_with(PREPARED_PREFIX) {
+                fixture
+                    .runtime
+                    .block_on(fixture.storage.delete(&key))
+                    .unwrap();
+            }
+        }
+
+        let (next, summary) = fixture.run(Some(root), 200);
+
+        assert_eq!((summary.unchanged, summary.compressed), (1, 1));
+        assert_eq!(file(&next, "b.bin").created_at, 100);
+        assert_eq!(fixture.keys(), vec![".prepared/b.bin", "a.bin"]);
+    }
+
+    #[test]
+    fn vanished_entries_are_marked_deleted() {
+        let fixture = Fixture::new(
+            "vanished",
+            &[("a.bin", b"a"), ("b.bin", b"b"), ("old/sub/c.bin", b"c")],
+        );
+
+        let (root, _) = fixture.run(None, 100);
+
+        std::fs::remove_file(fixture.dir.join("b.bin")).unwrap();
+        std::fs::remove_dir_all(fixture.dir.join("old")).unwrap();
+
+        let (next, summary) = fixture.run(Some(root), 200);
+
+        assert_eq!(summary.deleted, 1);
+
+        let b = file(&next, "b.bin");
+        assert!(b.deleted);
+        assert_eq!((b.deleted_at, b.deleted_by), (Some(200), Some(0)));
+
+        let old = &next.folders["old"];
+        assert!(old.deleted);
+        assert_eq!((old.deleted_at, old.deleted_by), (Some(200), Some(0)));
+        // Contents of a deleted folder are left as they were
+        assert!(!old.folders["sub"].deleted);
+
+        assert!(!file(&next, "a.bin").deleted);
+
+        // Coming back restores the entries
+        write(&fixture.dir, "b.bin", b"b");
+        write(&fixture.dir, "old/sub/c.bin", b"c");
+
+        let (restored, _) = fixture.run(Some(next), 300);
+
+        assert!(!file(&restored, "b.bin").deleted);
+        assert_eq!(file(&restored, "b.bin").created_at, 100);
+        assert!(!restored.folders["old"].deleted);
+        assert!(!file(&restored, "old/sub/c.bin").deleted);
+    }
+
+    #[test]
+    fn full_runs_keep_merged_blobs() {
+        let fixture = Fixture::new("full", &[("a.bin", b"a")]);
+
+        fixture
+            .runtime
+            .block_on(fixture.storage.put_bytes("uploaded.bin", vec![1]))
+            .unwrap();
+        fixture
+            .runtime
+            .block_on(fixture.storage.put_bytes(".prepared/stale.bin", vec![1]))
+            .unwrap();
+
+        fixture.run(None, 100);
+
+        assert_eq!(fixture.keys(), vec![".prepared/a.bin", "uploaded.bin"]);
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_list() {
+        let files: Vec<_> = (0..40)
+            .map(|i| (format!("d{}/f{i}.bin", i % 5), vec![i as u8; 100 + i * 37]))
+            .collect();
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, data)| (path.as_str(), data.as_slice()))
+            .collect();
+
+        let fixture = Fixture::new("threads", &files);
+
+        let (single, _) = fixture.run_on(None, 1, 100);
+        let single_blobs = fixture.keys();
+
+        for threads in [2, 8, 64] {
+            let (many, summary) = fixture.run_on(None, threads, 100);
+
+            assert_eq!(summary.compressed, 40);
+            assert_eq!(to_ron(many), to_ron(single.clone()));
+            assert_eq!(fixture.keys(), single_blobs);
+        }
+    }
+
+    #[test]
+    fn failed_files_are_named() {
+        let fixture = Fixture::new("failed", &[]);
+
+        let job = Job {
+            rel_path: "gone.bin".to_string(),
+            path: fixture.dir.join("gone.bin"),
+            size: 1,
+            modified_at: 0,
+            previous: None,
+            blob_exists: false,
+        };
+
+        let error = process(job, &fixture.storage, &fixture.runtime, 100)
+            .err()
+            .unwrap();
+
+        assert!(error.contains("gone.bin"), "{error}");
+    }
+}
@@ -1,23 +1,31 @@
 mod packet_handler;
 use crate::admin_panel::packet_handler::HandleClientPacket;
+use crate::config::Config;
+use crate::db::Database;
+use crate::log::LogStorage;
+use crate::telegram::Telegram;
 use axum::extract::ws::{Message, WebSocket};
 use axum::extract::{ConnectInfo, WebSocketUpgrade};
 use axum::response::IntoResponse;
 use axum_extra::TypedHeader;
 use futures_util::{SinkExt, StreamExt};
-use shared::admin_panel::{ClientPacket, ServerPacket};
+use shared::admin_panel::{ClientPacket, ConnectedAdmin, ServerPacket, TgLoginData};
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
 use tokio::spawn;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc::Sender;
-use tracing::{debug, error};
+use tokio::task::AbortHandle;
+use tracing::{debug, error, info};
 
 pub async fn admin_socket_handler(
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<headers::UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> impl IntoResponse {
-    let ws = ws.max_message_size(500 * 1024 * 1024);
+    let ws = ws.max_message_size(Config::instance().limits.max_ws_message_size);
 
     let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
         user_agent.to_string()
@@ -31,6 +39,156 @@ pub async fn admin_socket_handler(
     ws.on_upgrade(move |socket| handle_socket(socket, addr))
 }
 
+/// addr -> (user_id, connected_at)
+static CONNECTED_ADMINS: Mutex<BTreeMap<SocketAddr, (u32, i64)>> = Mutex::new(BTreeMap::new());
+
+pub(crate) fn connected_admins() -> Vec<ConnectedAdmin> {
+    CONNECTED_ADMINS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(addr, (user_id, connected_at))| ConnectedAdmin {
+            addr: addr.to_string(),
+            user_id: *user_id,
+            connected_at: *connected_at,
+        })
+        .collect()
+}
+
+/// Connected admin, passed to every packet handler
+#[derive(Clone, Debug)]
+pub(crate) struct AdminSession {
+    pub(crate) addr: SocketAddr,
+    /// Id of the admin, `0` while the admin is not authenticated
+    pub(crate) user_id: u32,
+    /// Task pushing new server logs to the admin
+    log_subscription: Arc<Mutex<Option<AbortHandle>>>,
+}
+
+impl AdminSession {
+    /// Without Telegram integration there is no way to log in, so everyone is allowed
+    fn is_authorized(&self) -> bool {
+        self.user_id != 0 || Telegram::instance().is_none()
+    }
+
+    async fn login(&mut self, data: TgLoginData, to_client: &Sender<ServerPacket>) {
+        let Some(telegram) = Telegram::instance() else {
+            let _ = to_client
+                .send(ServerPacket::AuthFailed(
+                    "Telegram integration is disabled".to_string(),
+                ))
+                .await;
+
+            return;
+        };
+
+        if let Err(e) = telegram.verify_login(&data) {
+            error!("{} failed to log in: {e}", self.addr);
+
+            let _ = to_client
+                .send(ServerPacket::AuthFailed(e.to_string()))
+                .await;
+
+            return;
+        }
+
+        let db = Database::instance();
+
+        let user = if let Some(user) = db.admin_user_by_tg_id(data.id).await {
+            user
+        } else if db.admin_users().await.is_empty()
+            && Config::instance().bootstrap_admin == Some(data.id)
+        {
+            info!("First admin {} registered via Telegram", data.id);
+
+            match db
+                .add_admin_user(data.first_name.clone(), Some(data.tg_user()))
+                .await
+            {
+                Ok(v) => v,
+                Err(e) => {
+                    let _ = to_client
+                        .send(ServerPacket::AuthFailed(e.to_string()))
+                        .await;
+
+                    return;
+                }
+            }
+        } else {
+            if Config::instance().bootstrap_admin.is_none() && db.admin_users().await.is_empty() {
+                info!(
+                    "No admins and no bootstrap_admin, Telegram account {} can't log in",
+                    data.id
+                );
+            }
+
+            let _ = to_client
+                .send(ServerPacket::AuthFailed(format!(
+                    "Telegram account {} is not linked to any admin",
+                    data.id
+                )))
+                .await;
+
+            return;
+        };
+
+        info!("{} logged in as {}", self.addr, user.name);
+
+        self.user_id = user.id;
+
+        if let Some(v) = CONNECTED_ADMINS.lock().unwrap().get_mut(&self.addr) {
+            v.0 = user.id;
+        }
+
+        let _ = to_client.send(ServerPacket::LoggedIn(user)).await;
+    }
+
+    pub(crate) fn subscribe_logs(&self, to_client: Sender<ServerPacket>) {
+        /// Max logs sent in one packet
+        const BATCH_SIZE: usize = 100;
+
+        let mut subscription = self.log_subscription.lock().unwrap();
+
+        if subscription.is_some() {
+            return;
+        }
+
+        let mut new_logs = LogStorage::instance().subscribe();
+
+        let task = spawn(async move {
+            loop {
+                let log = match new_logs.recv().await {
+                    Ok(v) => v,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+
+                let mut logs = vec![log];
+
+                while logs.len() < BATCH_SIZE {
+                    let Ok(log) = new_logs.try_recv() else {
+                        break;
+                    };
+
+                    logs.push(log);
+                }
+
+                if to_client.send(ServerPacket::NewLogs(logs)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        *subscription = Some(task.abort_handle());
+    }
+
+    pub(crate) fn unsubscribe_logs(&self) {
+        if let Some(task) = self.log_subscription.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+}
+
 async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
     // send a ping (unsupported by some browsers) just to kick things off and get a response
     if socket.send(Message::Ping(vec![1, 2, 3])).await.is_ok() {
@@ -44,6 +202,25 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
 
     let (to_client, mut listener) = tokio::sync::mpsc::channel::<ServerPacket>(10);
 
+    let mut session = AdminSession {
+        addr: who,
+        user_id: 0,
+        log_subscription: Default::default(),
+    };
+
+    CONNECTED_ADMINS
+        .lock()
+        .unwrap()
+        .insert(who, (0, chrono::Utc::now().timestamp()));
+
+    if let Some(telegram) = Telegram::instance() {
+        let _ = to_client
+            .send(ServerPacket::AuthRequired {
+                bot_id: telegram.bot_id(),
+            })
+            .await;
+    }
+
     let (mut write, mut read) = socket.split();
 
     spawn(async move {
@@ -66,7 +243,7 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
 
     while let Some(msg) = read.next().await {
         match msg {
-            Ok(msg) => match process_message(msg, who, to_client.clone()).await {
+            Ok(msg) => match process_message(msg, &mut session, to_client.clone()).await {
                 ControlFlow::Continue(_) => continue,
                 ControlFlow::Break(_) => break,
             },
@@ -79,15 +256,21 @@ async fn handle_socket(mut socket: WebSocket, who: SocketAddr) {
         }
     }
 
+    session.unsubscribe_logs();
+
+    CONNECTED_ADMINS.lock().unwrap().remove(&who);
+
     debug!("Drop read!");
 }
 
 /// helper to print contents of messages to stdout. Has special treatment for Close.
 async fn process_message(
     msg: Message,
-    who: SocketAddr,
+    session: &mut AdminSession,
     to_client: Sender<ServerPacket>,
 ) -> ControlFlow<(), ()> {
+    let who = session.addr;
+
     match msg {
         Message::Text(t) => {
             debug!(">>> {who} sent text {t}");
@@ -96,12 +279,24 @@ async fn process_message(
             debug!(">>> New packet!");
 
             match ClientPacket::from_bin(&data) {
+                Ok(ClientPacket::TelegramLogin(data)) => {
+                    session.login(data, &to_client).await;
+                }
+                Ok(_) if !session.is_authorized() => {
+                    let _ = to_client
+                        .send(ServerPacket::AuthFailed(
+                            "Authorization required".to_string(),
+                        ))
+                        .await;
+                }
                 Ok(packet) => {
                     if let ClientPacket::FileList { .. } = packet {
                         debug!(">>> {packet:?}");
                     }
 
-                    spawn(async move { packet.handle(to_client).await });
+                    let session = session.clone();
+
+                    spawn(async move { packet.handle(session, to_client).await });
 
                     return ControlFlow::Continue(());
                 }
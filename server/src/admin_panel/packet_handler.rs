@@ -1,20 +1,36 @@
+use crate::admin_panel::{connected_admins, AdminSession};
+use crate::config::Config;
 use crate::db::Database;
-use crate::file_updater::FileHolder;
+use crate::file_updater::{file_info, folder_info, FileHolder};
+use crate::gc::{collect_garbage, report_summary};
+use crate::log::LogStorage;
+use crate::media::save_media;
+use crate::patch_notes::published_notification;
+use crate::scrub::{last_report, scrub, scrub_summary};
+use crate::statistics::Statistics;
+use crate::telegram::Telegram;
+use crate::trash::{purge, restore, trash_entries};
+use serde::Serialize;
 use shared::admin_panel::{
-    ClientPacket, FileInfo, FolderInfo, Log, LogLevel, PatchNote, ServerPacket,
+    AuditAction, ClientPacket, DashboardSummary, FileInfo, FolderInfo, LogLevel, LogQuery,
+    PatchNoteStatus, ServerPacket, TrashItem,
 };
+use shared::file::ServerFolderInfo;
 use tokio::sync::mpsc::Sender;
+use tokio::task::spawn_blocking;
 use tracing::log::debug;
 
+const RECENT_ERRORS_COUNT: u32 = 10;
+
 pub(crate) trait HandleClientPacket {
-    async fn handle(self, to_client: Sender<ServerPacket>);
+    async fn handle(self, session: AdminSession, to_client: Sender<ServerPacket>);
 }
 
 impl HandleClientPacket for ClientPacket {
-    async fn handle(self, to_client: Sender<ServerPacket>) {
+    async fn handle(self, session: AdminSession, to_client: Sender<ServerPacket>) {
         match self {
             ClientPacket::FileList { dir } => {
-                let Some((folders, files)) = FileHolder::get_folder_and_file_infos(&dir).await
+                let Some((folders, files)) = FileHolder::instance().get_folder_and_file_infos(&dir)
                 else {
                     return;
                 };
@@ -29,11 +45,29 @@ impl HandleClientPacket for ClientPacket {
             }
 
             ClientPacket::CreateFolder { dir, name } => {
-                FileHolder::create_folder(&dir, &name).await;
+                let path = join_path(&dir, &name);
+
+                let created = FileHolder::instance()
+                    .create_folder(&dir, &name, session.user_id)
+                    .await;
+                let Some(created) = saved(created, &to_client).await else {
+                    return;
+                };
+
+                if let Some(change) = created {
+                    session
+                        .audit(
+                            AuditAction::CreateFolder,
+                            path.clone(),
+                            folder_snapshot(&change.before, &path),
+                            folder_snapshot(&change.after, &path),
+                        )
+                        .await;
+                }
 
                 debug!(">>> Created folder {name} in dir: {dir}");
 
-                let Some((folders, files)) = FileHolder::get_folder_and_file_infos(&dir).await
+                let Some((folders, files)) = FileHolder::instance().get_folder_and_file_infos(&dir)
                 else {
                     return;
                 };
@@ -48,11 +82,27 @@ impl HandleClientPacket for ClientPacket {
             }
 
             ClientPacket::RemoveFile { dir, name } => {
-                FileHolder::delete_file(&dir, &name).await;
+                let deleted = FileHolder::instance()
+                    .delete_file(&dir, &name, session.user_id)
+                    .await;
+                let Some(deleted) = saved(deleted, &to_client).await else {
+                    return;
+                };
+
+                if let Some(change) = deleted {
+                    session
+                        .audit(
+                            AuditAction::RemoveFile,
+                            join_path(&dir, &name),
+                            file_snapshot(&change.before, &dir, &name),
+                            file_snapshot(&change.after, &dir, &name),
+                        )
+                        .await;
+                }
 
                 debug!(">>> Deleted file {name} in dir: {dir}");
 
-                let Some((folders, files)) = FileHolder::get_folder_and_file_infos(&dir).await
+                let Some((folders, files)) = FileHolder::instance().get_folder_and_file_infos(&dir)
                 else {
                     return;
                 };
@@ -67,15 +117,29 @@ impl HandleClientPacket for ClientPacket {
             }
 
             ClientPacket::RemoveFolder { dir, name } => {
-                if dir.is_empty() {
-                    FileHolder::delete_folder(&name).await;
-                } else {
-                    FileHolder::delete_folder(&format!("{dir}/{name}")).await;
+                let path = join_path(&dir, &name);
+
+                let deleted = FileHolder::instance()
+                    .delete_folder(&path, session.user_id)
+                    .await;
+                let Some(deleted) = saved(deleted, &to_client).await else {
+                    return;
+                };
+
+                if let Some(change) = deleted {
+                    session
+                        .audit(
+                            AuditAction::RemoveFolder,
+                            path.clone(),
+                            folder_snapshot(&change.before, &path),
+                            folder_snapshot(&change.after, &path),
+                        )
+                        .await;
                 }
 
                 debug!(">>> Deleted folder {name} in dir {dir}");
 
-                let Some((folders, files)) = FileHolder::get_folder_and_file_infos(&dir).await
+                let Some((folders, files)) = FileHolder::instance().get_folder_and_file_infos(&dir)
                 else {
                     return;
                 };
@@ -89,6 +153,117 @@ impl HandleClientPacket for ClientPacket {
                     .await;
             }
 
+            ClientPacket::MoveFile { from, to } => {
+                let (from_dir, from_name) = from.rsplit_once('/').unwrap_or(("", &from));
+                let (to_dir, to_name) = to.rsplit_once('/').unwrap_or(("", &to));
+
+                let moved = match FileHolder::instance()
+                    .move_file(&from, &to, session.user_id)
+                    .await
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        let _ = to_client
+                            .send(ServerPacket::MoveFailed {
+                                from,
+                                to,
+                                folder: false,
+                                reason: e.to_string(),
+                            })
+                            .await;
+
+                        return;
+                    }
+                };
+
+                if let Some(change) = moved {
+                    session
+                        .audit(
+                            AuditAction::MoveFile,
+                            format!("{from} -> {to}"),
+                            file_snapshot(&change.before, from_dir, from_name),
+                            file_snapshot(&change.after, to_dir, to_name),
+                        )
+                        .await;
+                }
+
+                debug!(">>> Moved file {from} to {to}");
+
+                send_file_list(from_dir, &to_client).await;
+            }
+
+            ClientPacket::MoveFolder { from, to } => {
+                let moved = match FileHolder::instance()
+                    .move_folder(&from, &to, session.user_id)
+                    .await
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        let _ = to_client
+                            .send(ServerPacket::MoveFailed {
+                                from,
+                                to,
+                                folder: true,
+                                reason: e.to_string(),
+                            })
+                            .await;
+
+                        return;
+                    }
+                };
+
+                if let Some(change) = moved {
+                    session
+                        .audit(
+                            AuditAction::MoveFolder,
+                            format!("{from} -> {to}"),
+                            folder_snapshot(&change.before, &from),
+                            folder_snapshot(&change.after, &to),
+                        )
+                        .await;
+                }
+
+                debug!(">>> Moved folder {from} to {to}");
+
+                send_file_list(from.rsplit_once('/').map_or("", |v| v.0), &to_client).await;
+            }
+
+            ClientPacket::Trash => send_trash(vec![], &to_client).await,
+
+            ClientPacket::RestoreFromTrash { items } => {
+                let errors = restore(FileHolder::instance(), &items, session.user_id).await;
+
+                session
+                    .audit(
+                        AuditAction::RestoreFromTrash,
+                        trash_target(&items),
+                        None,
+                        None,
+                    )
+                    .await;
+
+                debug!(">>> Restored {} entries from trash", items.len());
+
+                send_trash(errors, &to_client).await;
+            }
+
+            ClientPacket::PurgeFromTrash { items } => {
+                let errors = purge(FileHolder::instance(), &items).await;
+
+                session
+                    .audit(
+                        AuditAction::PurgeFromTrash,
+                        trash_target(&items),
+                        None,
+                        None,
+                    )
+                    .await;
+
+                debug!(">>> Purged {} entries from trash", items.len());
+
+                send_trash(errors, &to_client).await;
+            }
+
             ClientPacket::AddFile {
                 id,
                 dir,
@@ -97,70 +272,313 @@ impl HandleClientPacket for ClientPacket {
             } => {
                 let _ = to_client.send(ServerPacket::FileUploaded { id }).await;
 
-                FileHolder::add_file(&dir, &name, file).await;
+                let change = match FileHolder::instance()
+                    .add_file(&dir, &name, file, session.user_id)
+                    .await
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        let _ = to_client
+                            .send(ServerPacket::FileFailed {
+                                id,
+                                reason: e.to_string(),
+                            })
+                            .await;
+
+                        return;
+                    }
+                };
+
+                session
+                    .audit(
+                        AuditAction::AddFile,
+                        join_path(&dir, &name),
+                        file_snapshot(&change.before, &dir, &name),
+                        file_snapshot(&change.after, &dir, &name),
+                    )
+                    .await;
+
+                Telegram::notify(format!("File {} uploaded", join_path(&dir, &name)));
 
                 debug!(">>> File {name} added to dir {dir}");
 
                 let _ = to_client.send(ServerPacket::FileProceeded { id }).await;
             }
 
-            ClientPacket::PatchNotes { take, skip } => {
-                let patch_notes = Database::instance().patch_notes().await;
+            ClientPacket::PatchNotes(query) => {
+                let (total, patch_notes) = Database::instance().patch_notes(&query).await;
 
                 let _ = to_client
                     .send(ServerPacket::PatchNotes {
-                        take: patch_notes.len() as u32,
-                        skip: 0,
-                        total: patch_notes.len() as u32,
+                        take: query.take,
+                        skip: query.skip,
+                        total,
                         patch_notes,
                     })
                     .await;
             }
 
-            ClientPacket::SavePatchNote { id, data } => {
-                Database::instance().update_patch_note(id, data).await;
+            ClientPacket::SavePatchNote {
+                id,
+                title,
+                data,
+                translations,
+                publish_at,
+            } => {
+                let updated = Database::instance()
+                    .update_patch_note(id, session.user_id, title, data, translations, publish_at)
+                    .await;
+                let Some(Some((before, after))) = saved(updated, &to_client).await else {
+                    return;
+                };
                 debug!(">>> Edit patch note {id}!");
+
+                session
+                    .audit(
+                        AuditAction::SavePatchNote,
+                        id.to_string(),
+                        Some(snapshot(&before)),
+                        Some(snapshot(&after)),
+                    )
+                    .await;
+            }
+
+            ClientPacket::SetPatchNoteStatus { id, status } => {
+                let changed = Database::instance().set_patch_note_status(id, status).await;
+                let Some(Some((before, after))) = saved(changed, &to_client).await else {
+                    return;
+                };
+
+                debug!(">>> Patch note {id} status changed to {status}!");
+
+                session
+                    .audit(
+                        AuditAction::ChangePatchNoteStatus,
+                        id.to_string(),
+                        Some(snapshot(&before)),
+                        Some(snapshot(&after)),
+                    )
+                    .await;
+
+                if status == PatchNoteStatus::Published {
+                    Telegram::notify(published_notification(id, &after.title));
+                }
+
+                let _ = to_client.send(ServerPacket::OpenPatchNote(after)).await;
             }
 
             ClientPacket::DeletePatchNote { id } => {
+                let changed = Database::instance().set_patch_note_deleted(id, true).await;
+                let Some(Some((before, after))) = saved(changed, &to_client).await else {
+                    return;
+                };
+
                 debug!(">>> Delete patch note {id}!");
+
+                session
+                    .audit(
+                        AuditAction::DeletePatchNote,
+                        id.to_string(),
+                        Some(snapshot(&before)),
+                        Some(snapshot(&after)),
+                    )
+                    .await;
+            }
+
+            ClientPacket::RestorePatchNote { id } => {
+                let changed = Database::instance().set_patch_note_deleted(id, false).await;
+                let Some(Some((before, after))) = saved(changed, &to_client).await else {
+                    return;
+                };
+
+                debug!(">>> Restore patch note {id}!");
+
+                session
+                    .audit(
+                        AuditAction::RestorePatchNote,
+                        id.to_string(),
+                        Some(snapshot(&before)),
+                        Some(snapshot(&after)),
+                    )
+                    .await;
             }
 
-            ClientPacket::AddPatchNote { data } => {
+            ClientPacket::AddPatchNote {
+                title,
+                data,
+                translations,
+                publish_at,
+            } => {
                 debug!(">>> Create patch note");
-                let patch_note = Database::instance().add_patch_note(data).await;
+                let added = Database::instance()
+                    .add_patch_note(session.user_id, title, data, translations, publish_at)
+                    .await;
+                let Some(patch_note) = saved(added, &to_client).await else {
+                    return;
+                };
+
+                session
+                    .audit(
+                        AuditAction::AddPatchNote,
+                        patch_note.id.to_string(),
+                        None,
+                        Some(snapshot(&patch_note)),
+                    )
+                    .await;
 
                 let _ = to_client
                     .send(ServerPacket::OpenPatchNote(patch_note))
                     .await;
             }
 
-            ClientPacket::Logs => {
+            ClientPacket::Logs { query, subscribe } => {
+                let Ok((total, producers, logs)) =
+                    spawn_blocking(move || LogStorage::instance().query(&query)).await
+                else {
+                    return;
+                };
+
                 let _ = to_client
-                    .send(ServerPacket::Logs(vec![Log {
-                        level: LogLevel::Info,
-                        producer: "Monitor".to_string(),
-                        log: "bla bla".to_string(),
-                        time: chrono::Local::now().timestamp(),
-                    }]))
+                    .send(ServerPacket::Logs {
+                        total,
+                        producers,
+                        logs,
+                    })
                     .await;
+
+                if subscribe {
+                    session.subscribe_logs(to_client);
+                } else {
+                    session.unsubscribe_logs();
+                }
             }
 
             ClientPacket::RequestEditPatchNote { id } => {
+                let Some(patch_note) = Database::instance().patch_note(id).await else {
+                    return;
+                };
+
+                let _ = to_client
+                    .send(ServerPacket::OpenPatchNote(patch_note))
+                    .await;
+            }
+
+            ClientPacket::PatchNoteRevisions { id } => {
+                let revisions = Database::instance().patch_note_revisions(id).await;
+
+                let _ = to_client
+                    .send(ServerPacket::PatchNoteRevisions { id, revisions })
+                    .await;
+            }
+
+            ClientPacket::PatchNoteRevision { revision } => {
+                let Some(revision) = Database::instance().patch_note_revision(revision).await
+                else {
+                    return;
+                };
+
+                let Some(current) = Database::instance()
+                    .patch_note(revision.patch_note.id)
+                    .await
+                else {
+                    return;
+                };
+
                 let _ = to_client
-                    .send(ServerPacket::OpenPatchNote(PatchNote {
+                    .send(ServerPacket::PatchNoteRevision { revision, current })
+                    .await;
+            }
+
+            ClientPacket::RestorePatchNoteRevision { revision } => {
+                let Some(revision) = Database::instance().patch_note_revision(revision).await
+                else {
+                    return;
+                };
+
+                let id = revision.patch_note.id;
+
+                // The schedule is not a part of revisions
+                let Some(publish_at) = Database::instance()
+                    .patch_note(id)
+                    .await
+                    .map(|v| v.publish_at)
+                else {
+                    return;
+                };
+
+                let updated = Database::instance()
+                    .update_patch_note(
                         id,
-                        data: TEMP.to_string(),
-                    }))
+                        session.user_id,
+                        revision.patch_note.title,
+                        revision.patch_note.data,
+                        revision.patch_note.translations,
+                        publish_at,
+                    )
                     .await;
+                let Some(Some((before, after))) = saved(updated, &to_client).await else {
+                    return;
+                };
+                debug!(">>> Restore patch note {id} revision {}!", revision.id);
+
+                session
+                    .audit(
+                        AuditAction::RestorePatchNoteRevision,
+                        id.to_string(),
+                        Some(snapshot(&before)),
+                        Some(snapshot(&after)),
+                    )
+                    .await;
+
+                let _ = to_client.send(ServerPacket::OpenPatchNote(after)).await;
             }
 
+            ClientPacket::UploadMedia { name, data } => match save_media(&name, &data).await {
+                Ok(url) => {
+                    debug!(">>> Media {name} saved as {url}");
+
+                    session
+                        .audit(
+                            AuditAction::UploadMedia,
+                            url.clone(),
+                            None,
+                            Some(name.clone()),
+                        )
+                        .await;
+
+                    let _ = to_client
+                        .send(ServerPacket::MediaUploaded { name, url })
+                        .await;
+                }
+                Err(reason) => {
+                    let _ = to_client
+                        .send(ServerPacket::MediaUploadFailed { name, reason })
+                        .await;
+                }
+            },
+
             ClientPacket::SkipFileHashCheck { dir, name } => {
-                FileHolder::toggle_hash_check(&dir, &name).await;
+                let toggled = FileHolder::instance()
+                    .toggle_hash_check(&dir, &name, session.user_id)
+                    .await;
+                let Some(toggled) = saved(toggled, &to_client).await else {
+                    return;
+                };
+
+                if let Some(change) = toggled {
+                    session
+                        .audit(
+                            AuditAction::SkipFileHashCheck,
+                            join_path(&dir, &name),
+                            file_snapshot(&change.before, &dir, &name),
+                            file_snapshot(&change.after, &dir, &name),
+                        )
+                        .await;
+                }
 
                 debug!(">>> Toggled hash check for file {name} in dir: {dir}");
 
-                let Some((folders, files)) = FileHolder::get_folder_and_file_infos(&dir).await
+                let Some((folders, files)) = FileHolder::instance().get_folder_and_file_infos(&dir)
                 else {
                     return;
                 };
@@ -173,15 +591,277 @@ impl HandleClientPacket for ClientPacket {
                     })
                     .await;
             }
+
+            ClientPacket::AuditLog { take, skip } => {
+                let (total, records) = Database::instance().audit_records(take, skip).await;
+
+                let _ = to_client
+                    .send(ServerPacket::AuditLog {
+                        take,
+                        skip,
+                        total,
+                        records,
+                    })
+                    .await;
+            }
+
+            // handled by the socket, because it changes the session
+            ClientPacket::TelegramLogin(_) => {}
+
+            ClientPacket::Statistics { days } => {
+                let _ = to_client
+                    .send(ServerPacket::Statistics(
+                        Statistics::instance().series(days).await,
+                    ))
+                    .await;
+            }
+
+            ClientPacket::Dashboard { days } => {
+                let tree = FileHolder::instance().snapshot();
+                let (total_size, files_count) = (tree.size, tree.files_count);
+
+                let recent_errors = spawn_blocking(|| {
+                    LogStorage::instance()
+                        .query(&LogQuery {
+                            min_level: LogLevel::Error,
+                            take: RECENT_ERRORS_COUNT,
+                            ..Default::default()
+                        })
+                        .2
+                })
+                .await
+                .unwrap_or_default();
+
+                let _ = to_client
+                    .send(ServerPacket::Dashboard(DashboardSummary {
+                        total_size,
+                        files_count,
+                        patch_notes_count: Database::instance().patch_notes_count().await,
+                        connected_admins: connected_admins(),
+                        recent_errors,
+                        statistics: Statistics::instance().series(days).await,
+                    }))
+                    .await;
+            }
+
+            ClientPacket::CollectGarbage { dry_run } => {
+                let report = collect_garbage(
+                    FileHolder::instance(),
+                    Config::instance().gc.retention_days,
+                    chrono::Utc::now().timestamp(),
+                    dry_run,
+                )
+                .await;
+
+                if !dry_run {
+                    session
+                        .audit(
+                            AuditAction::CollectGarbage,
+                            String::new(),
+                            None,
+                            Some(report_summary(&report)),
+                        )
+                        .await;
+                }
+
+                debug!(">>> Garbage collection: {}", report_summary(&report));
+
+                let _ = to_client.send(ServerPacket::GcReport(report)).await;
+            }
+
+            ClientPacket::LastScrubReport => {
+                let _ = to_client
+                    .send(ServerPacket::ScrubReport(last_report()))
+                    .await;
+            }
+
+            ClientPacket::Scrub { quarantine } => {
+                let report = scrub(FileHolder::instance(), quarantine).await;
+
+                if quarantine {
+                    session
+                        .audit(
+                            AuditAction::Scrub,
+                            String::new(),
+                            None,
+                            Some(scrub_summary(&report)),
+                        )
+                        .await;
+                }
+
+                debug!(">>> Integrity scrub: {}", scrub_summary(&report));
+
+                let _ = to_client
+                    .send(ServerPacket::ScrubReport(Some(report)))
+                    .await;
+            }
+
+            ClientPacket::AdminUsers => {
+                let _ = to_client
+                    .send(ServerPacket::AdminUsers(
+                        Database::instance().admin_users().await,
+                    ))
+                    .await;
+            }
+
+            ClientPacket::CreateAdminUser { name, tg_user } => {
+                let added = Database::instance().add_admin_user(name, tg_user).await;
+                let Some(user) = saved(added, &to_client).await else {
+                    return;
+                };
+
+                debug!(">>> Created admin {}", user.name);
+
+                session
+                    .audit(
+                        AuditAction::CreateAdminUser,
+                        user.id.to_string(),
+                        None,
+                        Some(snapshot(&user)),
+                    )
+                    .await;
+
+                let _ = to_client
+                    .send(ServerPacket::AdminUsers(
+                        Database::instance().admin_users().await,
+                    ))
+                    .await;
+            }
+
+            ClientPacket::LinkTelegram { user_id, tg_user } => {
+                let linked = Database::instance().link_telegram(user_id, tg_user).await;
+                let Some(linked) = saved(linked, &to_client).await else {
+                    return;
+                };
+
+                if let Some((before, after)) = linked {
+                    session
+                        .audit(
+                            AuditAction::LinkTelegram,
+                            user_id.to_string(),
+                            Some(snapshot(&before)),
+                            Some(snapshot(&after)),
+                        )
+                        .await;
+                }
+
+                let _ = to_client
+                    .send(ServerPacket::AdminUsers(
+                        Database::instance().admin_users().await,
+                    ))
+                    .await;
+            }
         }
     }
 }
 
-impl FileHolder {
-    async fn get_folder_and_file_infos(dir: &str) -> Option<(Vec<FolderInfo>, Vec<FileInfo>)> {
-        let instance = Self::instance().await;
+impl AdminSession {
+    async fn audit(
+        &self,
+        action: AuditAction,
+        target: String,
+        before: Option<String>,
+        after: Option<String>,
+    ) {
+        // Failures are logged by the commit
+        let _ = Database::instance()
+            .add_audit_record(
+                self.user_id,
+                self.addr.to_string(),
+                action,
+                target,
+                before,
+                after,
+            )
+            .await;
+    }
+}
+
+/// Returns the value of a change, or tells the client that the change was not saved
+async fn saved<T>(result: anyhow::Result<T>, to_client: &Sender<ServerPacket>) -> Option<T> {
+    match result {
+        Ok(v) => Some(v),
+        Err(e) => {
+            let _ = to_client
+                .send(ServerPacket::ActionFailed(e.to_string()))
+                .await;
+
+            None
+        }
+    }
+}
+
+async fn send_file_list(dir: &str, to_client: &Sender<ServerPacket>) {
+    let Some((folders, files)) = FileHolder::instance().get_folder_and_file_infos(dir) else {
+        return;
+    };
+
+    let _ = to_client
+        .send(ServerPacket::FileList {
+            dir: dir.to_string(),
+            files,
+            folders,
+        })
+        .await;
+}
 
-        let info = instance.folder_info(dir)?;
+async fn send_trash(errors: Vec<String>, to_client: &Sender<ServerPacket>) {
+    let entries = trash_entries(
+        &FileHolder::instance().snapshot(),
+        &Database::instance().admin_users().await,
+    );
+
+    let _ = to_client
+        .send(ServerPacket::Trash { entries, errors })
+        .await;
+}
+
+fn trash_target(items: &[TrashItem]) -> String {
+    items
+        .iter()
+        .map(|v| v.path.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+fn snapshot<T: Serialize>(v: &T) -> String {
+    ron::ser::to_string(v).unwrap_or_default()
+}
+
+fn file_snapshot(tree: &ServerFolderInfo, dir: &str, name: &str) -> Option<String> {
+    file_info(tree, dir, name).map(snapshot)
+}
+
+/// Snapshot of the folder itself, without nested files and folders
+fn folder_snapshot(tree: &ServerFolderInfo, path: &str) -> Option<String> {
+    let v = folder_info(tree, path)?;
+
+    Some(snapshot(&ServerFolderInfo {
+        size: v.size,
+        files_count: v.files_count,
+        created_at: v.created_at,
+        updated_at: v.updated_at,
+        updated_by: v.updated_by,
+        deleted: v.deleted,
+        deleted_at: v.deleted_at,
+        deleted_by: v.deleted_by,
+        files: Default::default(),
+        folders: Default::default(),
+    }))
+}
+
+impl FileHolder {
+    fn get_folder_and_file_infos(&self, dir: &str) -> Option<(Vec<FolderInfo>, Vec<FileInfo>)> {
+        let tree = self.snapshot();
+        let info = folder_info(&tree, dir)?;
 
         let mut folders = Vec::with_capacity(info.folders.len());
         let mut files = Vec::with_capacity(info.files.len());
@@ -192,7 +872,7 @@ impl FileHolder {
                 size: v.size,
                 created: v.created_at,
                 modified_at: v.updated_at,
-                updated_by: 0,
+                updated_by: v.updated_by,
                 deleted: v.deleted,
             })
         }
@@ -203,30 +883,13 @@ impl FileHolder {
                 size: v.size,
                 created: v.created_at,
                 modified_at: v.updated_at,
-                updated_by: 0,
+                updated_by: v.updated_by,
                 skip_hash_check: v.skip_hash_check,
                 deleted: v.deleted,
+                quarantined: v.quarantined,
             })
         }
 
         Some((folders, files))
     }
 }
-
-static TEMP: &str = r#"
-# [MarkupLanguage](https://docs.github.com/en/get-started/writing-on-github/getting-started-with-writing-and-formatting-on-github/basic-writing-and-formatting-syntax)
-----------------
-
-# At a glance
-- inline text:
-  - normal, `code`, **strong**, ~~strikethrough~~, *italics*, ***strong italics***
-  - `\` escapes the next character
-  - [hyperlink](https://github.com/emilk/egui)
-  - Embedded URL: <https://github.com/emilk/egui>
-- `# ` header
-- `---` separator (horizontal line)
-- `> ` quote
-- `- ` bullet list
-- `1. ` numbered list
-- \`\`\` code fence
-"#;
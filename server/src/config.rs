@@ -0,0 +1,515 @@
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use shared::storage::{BlobStorage, FsStorage, S3Config, S3Storage};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing_subscriber::filter::LevelFilter;
+
+static INSTANCE: OnceLock<Config> = OnceLock::new();
+
+/// Read from the working directory when `--config` is not given, defaults are used if it is absent
+const DEFAULT_CONFIG_FILE: &str = "server.ron";
+
+/// Command line flags, each of them can be set by its environment variable too
+///
+/// Priority: flag, environment variable, config file, default
+#[derive(Parser, Debug, Default)]
+#[command(about = "Game files and patch notes server")]
+pub struct Cli {
+    /// RON config file
+    #[arg(long, short, env = "SERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on, can be repeated
+    #[arg(long, env = "SERVER_LISTEN", value_delimiter = ',')]
+    pub listen: Vec<SocketAddr>,
+
+    /// Base url of feed links
+    #[arg(long, env = "PUBLIC_URL")]
+    pub public_url: Option<String>,
+
+    #[arg(long, env = "SERVER_DATABASE_DIR")]
+    pub database_dir: Option<PathBuf>,
+
+    #[arg(long, env = "SERVER_COMPRESSED_DIR")]
+    pub compressed_dir: Option<PathBuf>,
+
+    #[arg(long, env = "SERVER_MEDIA_DIR")]
+    pub media_dir: Option<PathBuf>,
+
+    /// Console log level, `RUST_LOG` has priority
+    #[arg(long, env = "SERVER_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Telegram id allowed to log in as the first admin
+    #[arg(long, env = "SERVER_BOOTSTRAP_ADMIN")]
+    pub bootstrap_admin: Option<u64>,
+
+    /// Validates and prints the resulting config and exits, nothing is created on disk
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub listen: Vec<SocketAddr>,
+    /// Base url of feed links, they are relative to the server root if it is not set
+    pub public_url: Option<String>,
+    /// Telegram id which becomes the first admin on login while there are no admins. Without it
+    /// the first admin can only be created while Telegram integration is disabled
+    pub bootstrap_admin: Option<u64>,
+    pub dirs: DirsConfig,
+    /// Compressed game files go to this bucket instead of `dirs.compressed`
+    pub s3: Option<S3Config>,
+    pub limits: LimitsConfig,
+    pub log: LogConfig,
+    pub gc: GcConfig,
+    pub scrub: ScrubConfig,
+    pub features: FeaturesConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DirsConfig {
+    /// Patch notes, admins, audit, statistics, logs and the file list
+    pub database: PathBuf,
+    /// Compressed game files, unless `s3` is set
+    pub compressed: PathBuf,
+    /// Patch note images
+    pub media: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Bytes, limits uploaded files size
+    pub max_ws_message_size: usize,
+    /// Bytes
+    pub max_media_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Console log level
+    pub level: String,
+    /// Level of logs stored for the admin panel
+    pub stored_level: String,
+    /// Daily log files kept
+    pub retention_days: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GcConfig {
+    /// Deleted files and folders stay in the trash this long before they are purged
+    pub retention_days: u32,
+    /// Period of the background collection
+    pub interval_hours: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScrubConfig {
+    /// Check every stored game file against its size and hash when the server starts
+    pub on_startup: bool,
+    /// Stop serving files which failed the startup scrub, admins choose for their own runs
+    pub quarantine: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FeaturesConfig {
+    /// Login and notifications, also needs `TELEGRAM_BOT_TOKEN`
+    pub telegram: bool,
+    /// `/manifest` and `/files` for game clients
+    pub game_client: bool,
+    /// Public patch note endpoints, feeds and media
+    pub public_patch_notes: bool,
+    pub publish_scheduler: bool,
+    /// Background garbage collection of game files, admins can run it anyway
+    pub gc: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: vec![SocketAddr::from(([127, 0, 0, 1], 3000))],
+            public_url: None,
+            bootstrap_admin: None,
+            dirs: Default::default(),
+            s3: None,
+            limits: Default::default(),
+            log: Default::default(),
+            gc: Default::default(),
+            scrub: Default::default(),
+            features: Default::default(),
+        }
+    }
+}
+
+impl Default for DirsConfig {
+    fn default() -> Self {
+        Self {
+            database: PathBuf::from("./database"),
+            compressed: PathBuf::from("./compressed"),
+            media: PathBuf::from("./media"),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_ws_message_size: 500 * 1024 * 1024,
+            max_media_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            stored_level: "info".to_string(),
+            retention_days: 30,
+        }
+    }
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            interval_hours: 24,
+        }
+    }
+}
+
+impl Default for ScrubConfig {
+    fn default() -> Self {
+        Self {
+            on_startup: true,
+            quarantine: false,
+        }
+    }
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            telegram: true,
+            game_client: true,
+            public_patch_notes: true,
+            publish_scheduler: true,
+            gc: true,
+        }
+    }
+}
+
+impl Config {
+    /// Config set by [`Config::init`] or the default one
+    pub fn instance<'a>() -> &'a Config {
+        INSTANCE.get_or_init(Config::default)
+    }
+
+    /// Must be called before any other singleton is used
+    pub fn init(config: Config) {
+        if INSTANCE.set(config).is_err() {
+            panic!("Config is already initialized");
+        }
+    }
+
+    pub fn load(cli: &Cli) -> anyhow::Result<Config> {
+        let path = cli
+            .config
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid config file {path:?}: {e}"))?,
+            Err(_) if cli.config.is_none() => Config::default(),
+            Err(e) => return Err(anyhow::anyhow!("Can't read config file {path:?}: {e}")),
+        };
+
+        config.apply(cli);
+
+        Ok(config)
+    }
+
+    fn parse(text: &str) -> Result<Config, ron::error::SpannedError> {
+        ron::de::from_str(text)
+    }
+
+    fn apply(&mut self, cli: &Cli) {
+        if !cli.listen.is_empty() {
+            self.listen = cli.listen.clone();
+        }
+        if let Some(v) = &cli.public_url {
+            self.public_url = Some(v.clone());
+        }
+        if let Some(v) = &cli.database_dir {
+            self.dirs.database = v.clone();
+        }
+        if let Some(v) = &cli.compressed_dir {
+            self.dirs.compressed = v.clone();
+        }
+        if let Some(v) = &cli.media_dir {
+            self.dirs.media = v.clone();
+        }
+        if let Some(v) = &cli.log_level {
+            self.log.level = v.clone();
+        }
+        if let Some(v) = cli.bootstrap_admin {
+            self.bootstrap_admin = Some(v);
+        }
+    }
+
+    /// Checks values and that data directories are writable, all problems are reported at once
+    ///
+    /// Missing directories are created and written to only with `create_dirs`, otherwise they are
+    /// only looked at, so `--check` leaves the disk as it was
+    pub fn validate(&self, create_dirs: bool) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        if self.listen.is_empty() {
+            errors.push("listen: at least one address is required".to_string());
+        }
+
+        if let Some(url) = &self.public_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!("public_url: {url} is not an http(s) url"));
+            }
+        }
+
+        let mut dirs = vec![
+            ("database", &self.dirs.database),
+            ("media", &self.dirs.media),
+        ];
+
+        // Blobs go to the bucket otherwise
+        if self.s3.is_none() {
+            dirs.push(("compressed", &self.dirs.compressed));
+        }
+
+        for (name, dir) in dirs {
+            let checked = if create_dirs {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("can't create {dir:?}: {e}"))
+                    .and_then(|_| {
+                        check_writable(dir).map_err(|e| format!("{dir:?} is not writable: {e}"))
+                    })
+            } else {
+                check_permissions(dir)
+            };
+
+            if let Err(e) = checked {
+                errors.push(format!("dirs.{name}: {e}"));
+            }
+        }
+
+        if self.limits.max_ws_message_size == 0 {
+            errors.push("limits.max_ws_message_size: must be positive".to_string());
+        }
+
+        if self.limits.max_media_size == 0
+            || self.limits.max_media_size > self.limits.max_ws_message_size
+        {
+            errors.push(
+                "limits.max_media_size: must be positive and fit into max_ws_message_size"
+                    .to_string(),
+            );
+        }
+
+        for (name, level) in [
+            ("level", &self.log.level),
+            ("stored_level", &self.log.stored_level),
+        ] {
+            if LevelFilter::from_str(level).is_err() {
+                errors.push(format!("log.{name}: unknown level {level}"));
+            }
+        }
+
+        if let Err(e) = self.blob_storage() {
+            errors.push(format!("s3: {e}"));
+        }
+
+        if self.log.retention_days == 0 {
+            errors.push("log.retention_days: must be positive".to_string());
+        }
+
+        if self.gc.interval_hours == 0 {
+            errors.push("gc.interval_hours: must be positive".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Invalid configuration:\n\t{}",
+                errors.join("\n\t")
+            ))
+        }
+    }
+
+    /// S3 credentials can be left out of the file and passed by `S3_ACCESS_KEY_ID` and
+    /// `S3_SECRET_ACCESS_KEY`
+    pub fn blob_storage(&self) -> anyhow::Result<Box<dyn BlobStorage>> {
+        Ok(match &self.s3 {
+            Some(s3) => Box::new(S3Storage::new(s3.clone().with_env_credentials())?),
+            None => Box::new(FsStorage::new(&self.dirs.compressed)),
+        })
+    }
+
+    pub fn stored_log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log.stored_level).unwrap_or(LevelFilter::INFO)
+    }
+
+    pub fn info(&self) -> String {
+        format!(
+            "Config:\n\tListen: {:?}\n\tDatabase: {:?}\n\tCompressed: {:?}\n\tMedia: {:?}\n",
+            self.listen, self.dirs.database, self.dirs.compressed, self.dirs.media
+        )
+    }
+}
+
+/// Creates and removes a probe file, permission bits don't account for ACLs and read-only mounts
+/// Read-only check of an existing directory, used when nothing may be written
+fn check_permissions(dir: &Path) -> Result<(), String> {
+    let metadata = std::fs::metadata(dir).map_err(|e| format!("can't read {dir:?}: {e}"))?;
+
+    if !metadata.is_dir() {
+        return Err(format!("{dir:?} is not a directory"));
+    }
+
+    if metadata.permissions().readonly() {
+        return Err(format!("{dir:?} is read-only"));
+    }
+
+    Ok(())
+}
+
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".write_check_{}", uuid::Uuid::new_v4()));
+
+    std::fs::File::create(&probe)?;
+    std::fs::remove_file(probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        let dir = std::env::temp_dir().join(format!("config_{}", uuid::Uuid::new_v4()));
+
+        // Only the data directories are moved out of the working directory
+        let config = Config {
+            dirs: DirsConfig {
+                database: dir.join("database"),
+                compressed: dir.join("compressed"),
+                media: dir.join("media"),
+            },
+            ..Config::default()
+        };
+
+        config.validate(true).unwrap();
+        config.validate(false).unwrap();
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn check_writes_nothing() {
+        let dir = std::env::temp_dir().join(format!("config_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("database")).unwrap();
+        std::fs::create_dir_all(dir.join("media")).unwrap();
+
+        let mut config = Config {
+            dirs: DirsConfig {
+                database: dir.join("database"),
+                compressed: dir.join("compressed"),
+                media: dir.join("media"),
+            },
+            ..Config::default()
+        };
+
+        let error = config.validate(false).unwrap_err().to_string();
+        assert!(error.contains("dirs.compressed"), "{error}");
+
+        // The bucket replaces the compressed directory
+        config.s3 = Some(S3Config {
+            endpoint: "http://127.0.0.1:9000".to_string(),
+            bucket: "files".to_string(),
+            access_key: "key".to_string(),
+            secret_key: "secret".to_string(),
+            ..Default::default()
+        });
+
+        config.validate(false).unwrap();
+
+        for name in ["database", "media"] {
+            assert_eq!(std::fs::read_dir(dir.join(name)).unwrap().count(), 0);
+        }
+        assert!(!dir.join("compressed").exists());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn file_is_overridden_by_flags() {
+        let mut config = Config::parse(
+            "(listen: [\"0.0.0.0:8080\"], limits: (max_media_size: 1024), log: (level: \"debug\"))",
+        )
+        .unwrap();
+
+        assert_eq!(config.limits.max_ws_message_size, 500 * 1024 * 1024);
+        assert_eq!(config.log.stored_level, "info");
+
+        config.apply(&Cli::parse_from([
+            "server",
+            "--listen",
+            "127.0.0.1:1,127.0.0.1:2",
+        ]));
+
+        assert_eq!(config.listen.len(), 2);
+        assert_eq!(config.limits.max_media_size, 1024);
+
+        config.log.level = "loud".to_string();
+        config.limits.max_media_size = usize::MAX;
+        config.dirs = DirsConfig {
+            database: std::env::temp_dir(),
+            compressed: std::env::temp_dir(),
+            media: std::env::temp_dir(),
+        };
+
+        let error = config.validate(false).unwrap_err().to_string();
+
+        assert!(error.contains("log.level"));
+        assert!(error.contains("limits.max_media_size"));
+        assert!(!error.contains("public_url"));
+        assert!(!error.contains("dirs."));
+
+        let missing = std::env::temp_dir().join(format!("config_{}", uuid::Uuid::new_v4()));
+        config.dirs.media = missing.clone();
+
+        let error = config.validate(false).unwrap_err().to_string();
+
+        assert!(error.contains("dirs.media") && error.contains("can't read"));
+        assert!(!missing.exists());
+
+        assert!(!config
+            .validate(true)
+            .unwrap_err()
+            .to_string()
+            .contains("dirs."));
+        assert!(missing.is_dir());
+
+        let _ = std::fs::remove_dir(missing);
+    }
+}
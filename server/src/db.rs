@@ -1,127 +1,731 @@
-use serde::{Deserialize, Serialize};
-use shared::admin_panel::PatchNote;
-use std::collections::HashMap;
-use std::hash::Hash;
-use std::io::Write;
-use std::sync::OnceLock;
+use crate::store::{next_id_key, Store, Table, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use shared::admin_panel::{
+    AdminUser, AuditAction, AuditRecord, Locale, PatchNote, PatchNoteQuery, PatchNoteRevision,
+    PatchNoteRevisionInfo, PatchNoteStatus, TgUser,
+};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
 use tokio::sync::RwLock;
+use tokio::task::spawn_blocking;
 use tracing::error;
 
-trait NextId {
-    fn next_id(&self) -> Self;
+/// Items of a store table kept in memory, changes are staged into a [`Transaction`]
+struct ItemHolder<V> {
+    table: Table,
+    next_id: u32,
+    items: HashMap<u32, V>,
 }
 
-impl NextId for u32 {
-    fn next_id(&self) -> Self {
-        self + 1
+impl<V: Serialize + DeserializeOwned> ItemHolder<V> {
+    fn load(store: &Store, table: Table) -> anyhow::Result<Self> {
+        let mut items = HashMap::new();
+
+        for (key, item) in store.load(table)? {
+            let id = key
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid {table:?} id {key}"))?;
+
+            items.insert(id, item);
+        }
+
+        let next_id = match store.get(Table::Meta, &next_id_key(table))? {
+            Some(v) => v,
+            None => items.keys().max().map_or(0, |v| v + 1),
+        };
+
+        Ok(Self {
+            table,
+            next_id,
+            items,
+        })
+    }
+
+    fn stage(&self) -> Staged<V> {
+        Staged {
+            table: self.table,
+            next_id: self.next_id,
+            items: vec![],
+        }
+    }
+
+    /// Applies the staged changes once they are committed
+    fn apply(&mut self, staged: Staged<V>) {
+        self.next_id = staged.next_id;
+
+        for (id, item) in staged.items {
+            self.items.insert(id, item);
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
-struct PatchNoteHolder<K: NextId + Eq + Hash + Clone, V> {
-    next_id: K,
-    items: HashMap<K, V>,
+/// Changes of an [`ItemHolder`], the holder is not changed until [`ItemHolder::apply`]
+struct Staged<V> {
+    table: Table,
+    next_id: u32,
+    items: Vec<(u32, V)>,
 }
 
-impl<K: NextId + Eq + Hash + Clone + Serialize, V: Serialize> PatchNoteHolder<K, V> {
-    fn add(&mut self, item: V) {
-        let mut id = self.next_id.next_id();
+impl<V: Serialize> Staged<V> {
+    fn add(&mut self, item: V, tx: &mut Transaction) {
+        let id = self.next_id;
 
-        std::mem::swap(&mut id, &mut self.next_id);
+        self.next_id += 1;
 
-        self.items.insert(id, item);
-    }
+        tx.put(self.table, id, &item);
+        tx.put(Table::Meta, next_id_key(self.table), &self.next_id);
 
-    fn dump(&self, path: &str) -> anyhow::Result<()> {
-        let Ok(mut file) = std::fs::File::create(path) else {
-            return Err(anyhow::anyhow!("Can't open file {path} for write!"));
-        };
+        self.items.push((id, item));
+    }
 
-        file.write_all(
-            ron::ser::to_string_pretty(&self, ron::ser::PrettyConfig::default())
-                .unwrap()
-                .as_bytes(),
-        )?;
+    fn save(&mut self, id: u32, item: V, tx: &mut Transaction) {
+        tx.put(self.table, id, &item);
 
-        Ok(())
+        self.items.push((id, item));
     }
 }
 
+fn add_revision(
+    revisions: &mut Staged<PatchNoteRevision>,
+    author: u32,
+    time: i64,
+    patch_note: &PatchNote,
+    tx: &mut Transaction,
+) {
+    let revision = PatchNoteRevision {
+        id: revisions.next_id,
+        author,
+        time,
+        patch_note: patch_note.clone(),
+    };
+
+    revisions.add(revision, tx);
+}
+
 static INSTANCE: OnceLock<Database> = OnceLock::new();
-static DATABASE_DIR: &str = "./database";
 
-#[derive(Default)]
 pub struct Database {
-    dir: String,
-    patch_notes: RwLock<PatchNoteHolder<u32, PatchNote>>,
+    store: Arc<Store>,
+    patch_notes: RwLock<ItemHolder<PatchNote>>,
+    patch_note_revisions: RwLock<ItemHolder<PatchNoteRevision>>,
+    audit: RwLock<ItemHolder<AuditRecord>>,
+    admin_users: RwLock<ItemHolder<AdminUser>>,
 }
 
 impl Database {
     pub fn instance<'a>() -> &'a Database {
-        INSTANCE.get_or_init(|| Self::load(DATABASE_DIR))
+        INSTANCE.get_or_init(|| {
+            Self::load(Store::instance()).unwrap_or_else(|e| panic!("Can't load database: {e}"))
+        })
+    }
+
+    /// Callers apply their changes in memory only after the commit succeeds
+    async fn commit(&self, tx: Transaction) -> anyhow::Result<()> {
+        let store = self.store.clone();
+
+        let res = spawn_blocking(move || store.commit(tx))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|v| v);
+
+        if let Err(e) = &res {
+            error!("Database commit failed: {e}");
+        }
+
+        res
     }
 
     pub async fn info(&self) -> String {
         let v = self.patch_notes.read().await;
 
-        format!("Database:\n\tTotal patchnotes: {}\n", &v.items.len())
+        let a = self.audit.read().await;
+
+        format!(
+            "Database:\n\tTotal patchnotes: {}\n\tTotal audit records: {}\n\tTotal admins: {}\n",
+            &v.items.len(),
+            &a.items.len(),
+            &self.admin_users.read().await.items.len()
+        )
     }
 
-    pub async fn add_patch_note(&self, data: String) -> PatchNote {
+    pub async fn add_audit_record(
+        &self,
+        user_id: u32,
+        addr: String,
+        action: AuditAction,
+        target: String,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> anyhow::Result<()> {
+        let mut holder = self.audit.write().await;
+        let mut staged = holder.stage();
+
+        let record = AuditRecord {
+            id: staged.next_id,
+            user_id,
+            addr,
+            time: chrono::Utc::now().timestamp(),
+            action,
+            target,
+            before,
+            after,
+        };
+
+        let mut tx = Transaction::default();
+        staged.add(record, &mut tx);
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+
+        Ok(())
+    }
+
+    /// Returns (total, records), newest first
+    pub async fn audit_records(&self, take: u32, skip: u32) -> (u32, Vec<AuditRecord>) {
+        let holder = self.audit.read().await;
+
+        let mut res: Vec<_> = holder.items.values().collect();
+
+        res.sort_by_key(|v| Reverse(v.id));
+
+        (
+            res.len() as u32,
+            res.into_iter()
+                .skip(skip as usize)
+                .take(take as usize)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    pub async fn add_patch_note(
+        &self,
+        author: u32,
+        title: String,
+        data: String,
+        translations: BTreeMap<Locale, String>,
+        publish_at: Option<i64>,
+    ) -> anyhow::Result<PatchNote> {
         let mut holder = self.patch_notes.write().await;
+        let mut revisions = self.patch_note_revisions.write().await;
+
+        let mut staged = holder.stage();
+        let mut staged_revisions = revisions.stage();
+
+        let now = chrono::Utc::now().timestamp();
 
         let patch_note = PatchNote {
-            id: holder.next_id,
+            id: staged.next_id,
+            title,
             data,
+            translations,
+            status: PatchNoteStatus::Draft,
+            author,
+            created_at: now,
+            updated_at: now,
+            published_at: None,
+            publish_at,
+            deleted: false,
         };
 
-        holder.add(patch_note.clone());
+        let mut tx = Transaction::default();
+        staged.add(patch_note.clone(), &mut tx);
 
-        holder
-            .dump(&format!("{}/patchnotes.ron", self.dir))
-            .unwrap();
+        add_revision(&mut staged_revisions, author, now, &patch_note, &mut tx);
 
-        patch_note
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+        revisions.apply(staged_revisions);
+
+        Ok(patch_note)
     }
 
-    pub async fn update_patch_note(&self, id: u32, data: String) {
+    /// Previous text stays in the patch note revisions. Returns (before, after), `None` if there
+    /// is no such patch note
+    pub async fn update_patch_note(
+        &self,
+        id: u32,
+        author: u32,
+        title: String,
+        data: String,
+        translations: BTreeMap<Locale, String>,
+        publish_at: Option<i64>,
+    ) -> anyhow::Result<Option<(PatchNote, PatchNote)>> {
         let mut holder = self.patch_notes.write().await;
+        let mut revisions = self.patch_note_revisions.write().await;
+
+        let Some(v) = holder.items.get(&id) else {
+            return Ok(None);
+        };
+
+        let before = v.clone();
+
+        let mut staged = holder.stage();
+        let mut staged_revisions = revisions.stage();
+        let mut tx = Transaction::default();
 
-        if let Some(v) = holder.items.get_mut(&id) {
-            v.data = data;
+        // Patch notes created before revisions have no history yet
+        if !revisions.items.values().any(|r| r.patch_note.id == id) {
+            add_revision(&mut staged_revisions, v.author, v.updated_at, v, &mut tx);
         }
 
-        holder
-            .dump(&format!("{}/patchnotes.ron", self.dir))
-            .unwrap();
+        let now = chrono::Utc::now().timestamp();
+
+        let patch_note = PatchNote {
+            title,
+            data,
+            translations,
+            publish_at,
+            updated_at: now,
+            ..v.clone()
+        };
+
+        add_revision(&mut staged_revisions, author, now, &patch_note, &mut tx);
+        staged.save(id, patch_note.clone(), &mut tx);
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+        revisions.apply(staged_revisions);
+
+        Ok(Some((before, patch_note)))
     }
 
-    pub async fn patch_notes(&self) -> Vec<PatchNote> {
-        let holder = self.patch_notes.read().await;
+    /// Newest first
+    pub async fn patch_note_revisions(&self, id: u32) -> Vec<PatchNoteRevisionInfo> {
+        let holder = self.patch_note_revisions.read().await;
+
+        let mut res: Vec<_> = holder
+            .items
+            .values()
+            .filter(|v| v.patch_note.id == id)
+            .collect();
+
+        res.sort_by_key(|v| Reverse(v.id));
+
+        res.into_iter().map(PatchNoteRevisionInfo::from).collect()
+    }
+
+    pub async fn patch_note_revision(&self, revision: u32) -> Option<PatchNoteRevision> {
+        self.patch_note_revisions
+            .read()
+            .await
+            .items
+            .get(&revision)
+            .cloned()
+    }
+
+    pub async fn admin_users(&self) -> Vec<AdminUser> {
+        let holder = self.admin_users.read().await;
 
         let mut res: Vec<_> = holder.items.values().cloned().collect();
 
-        res.sort_by(|a, b| a.id.cmp(&b.id));
+        res.sort_by_key(|v| v.id);
 
         res
     }
 
-    fn load(dir: &str) -> Self {
-        let patch_notes = if let Ok(file) = std::fs::File::open(format!("./{dir}/patchnotes.ron")) {
-            if let Ok(v) = ron::de::from_reader(file) {
-                v
-            } else {
-                error!("Corrupted database file: ./{dir}/patchnotes.ron");
+    pub async fn admin_user_by_tg_id(&self, tg_id: u64) -> Option<AdminUser> {
+        let holder = self.admin_users.read().await;
 
-                Default::default()
-            }
-        } else {
-            Default::default()
+        holder
+            .items
+            .values()
+            .find(|v| v.tg_user.as_ref().is_some_and(|u| u.id == tg_id))
+            .cloned()
+    }
+
+    /// Admin ids start from 1, 0 is reserved for not authenticated admins
+    pub async fn add_admin_user(
+        &self,
+        name: String,
+        tg_user: Option<TgUser>,
+    ) -> anyhow::Result<AdminUser> {
+        let mut holder = self.admin_users.write().await;
+        let mut staged = holder.stage();
+
+        staged.next_id = staged.next_id.max(1);
+
+        let user = AdminUser {
+            id: staged.next_id,
+            name,
+            tg_user,
+        };
+
+        let mut tx = Transaction::default();
+        staged.add(user.clone(), &mut tx);
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+
+        Ok(user)
+    }
+
+    /// Returns (before, after), `None` if there is no such user or the link is the same
+    pub async fn link_telegram(
+        &self,
+        user_id: u32,
+        tg_user: Option<TgUser>,
+    ) -> anyhow::Result<Option<(AdminUser, AdminUser)>> {
+        let mut holder = self.admin_users.write().await;
+
+        let Some(before) = holder.items.get(&user_id).cloned() else {
+            return Ok(None);
         };
 
-        Self {
-            patch_notes: RwLock::new(patch_notes),
-            dir: dir.to_string(),
+        if before.tg_user == tg_user {
+            return Ok(None);
         }
+
+        let user = AdminUser {
+            tg_user,
+            ..before.clone()
+        };
+
+        let mut staged = holder.stage();
+        let mut tx = Transaction::default();
+        staged.save(user_id, user.clone(), &mut tx);
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+
+        Ok(Some((before, user)))
+    }
+
+    pub async fn patch_note(&self, id: u32) -> Option<PatchNote> {
+        self.patch_notes.read().await.items.get(&id).cloned()
+    }
+
+    /// Returns (before, after), `None` if there is no such patch note
+    pub async fn set_patch_note_status(
+        &self,
+        id: u32,
+        status: PatchNoteStatus,
+    ) -> anyhow::Result<Option<(PatchNote, PatchNote)>> {
+        let mut holder = self.patch_notes.write().await;
+
+        let Some(before) = holder.items.get(&id).cloned() else {
+            return Ok(None);
+        };
+
+        let mut v = before.clone();
+        set_status(&mut v, status, chrono::Utc::now().timestamp());
+
+        let mut staged = holder.stage();
+        let mut tx = Transaction::default();
+        staged.save(id, v.clone(), &mut tx);
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+
+        Ok(Some((before, v)))
+    }
+
+    /// Publishes drafts with passed [`PatchNote::publish_at`], returns published patch notes
+    pub async fn publish_scheduled_patch_notes(&self, now: i64) -> anyhow::Result<Vec<PatchNote>> {
+        let mut holder = self.patch_notes.write().await;
+
+        let mut res = vec![];
+
+        for v in holder.items.values() {
+            if v.status == PatchNoteStatus::Draft
+                && !v.deleted
+                && v.publish_at.is_some_and(|t| t <= now)
+            {
+                let mut v = v.clone();
+
+                set_status(&mut v, PatchNoteStatus::Published, now);
+
+                res.push(v);
+            }
+        }
+
+        let mut staged = holder.stage();
+        let mut tx = Transaction::default();
+        for v in &res {
+            staged.save(v.id, v.clone(), &mut tx);
+        }
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+
+        Ok(res)
+    }
+
+    /// Soft delete, returns (before, after), `None` if there is no such patch note or it is
+    /// already in this state
+    pub async fn set_patch_note_deleted(
+        &self,
+        id: u32,
+        deleted: bool,
+    ) -> anyhow::Result<Option<(PatchNote, PatchNote)>> {
+        let mut holder = self.patch_notes.write().await;
+
+        let Some(before) = holder
+            .items
+            .get(&id)
+            .filter(|v| v.deleted != deleted)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+
+        let v = PatchNote {
+            deleted,
+            ..before.clone()
+        };
+
+        let mut staged = holder.stage();
+        let mut tx = Transaction::default();
+        staged.save(id, v.clone(), &mut tx);
+
+        self.commit(tx).await?;
+
+        holder.apply(staged);
+
+        Ok(Some((before, v)))
+    }
+
+    /// Not deleted patch notes count
+    pub async fn patch_notes_count(&self) -> u32 {
+        self.patch_notes
+            .read()
+            .await
+            .items
+            .values()
+            .filter(|v| !v.deleted)
+            .count() as u32
+    }
+
+    /// Returns (total, patch_notes), newest first
+    pub async fn patch_notes(&self, query: &PatchNoteQuery) -> (u32, Vec<PatchNote>) {
+        let holder = self.patch_notes.read().await;
+
+        let mut res: Vec<_> = holder.items.values().filter(|v| query.matches(v)).collect();
+
+        res.sort_by_key(|v| Reverse(v.id));
+
+        (
+            res.len() as u32,
+            res.into_iter()
+                .skip(query.skip as usize)
+                .take(query.take as usize)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Returns (total, patch_notes), last published first
+    pub async fn published_patch_notes(&self, take: u32, skip: u32) -> (u32, Vec<PatchNote>) {
+        let holder = self.patch_notes.read().await;
+
+        let mut res: Vec<_> = holder
+            .items
+            .values()
+            .filter(|v| v.status == PatchNoteStatus::Published && !v.deleted)
+            .collect();
+
+        res.sort_by_key(|v| Reverse((v.published_at, v.id)));
+
+        (
+            res.len() as u32,
+            res.into_iter()
+                .skip(skip as usize)
+                .take(take as usize)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    fn load(store: Arc<Store>) -> anyhow::Result<Self> {
+        Ok(Self {
+            patch_notes: RwLock::new(ItemHolder::load(&store, Table::PatchNotes)?),
+            patch_note_revisions: RwLock::new(ItemHolder::load(&store, Table::PatchNoteRevisions)?),
+            audit: RwLock::new(ItemHolder::load(&store, Table::Audit)?),
+            admin_users: RwLock::new(ItemHolder::load(&store, Table::AdminUsers)?),
+            store,
+        })
+    }
+}
+
+/// Publishing clears the schedule, first publication time is kept
+fn set_status(patch_note: &mut PatchNote, status: PatchNoteStatus, now: i64) {
+    if status == PatchNoteStatus::Published {
+        patch_note.published_at.get_or_insert(now);
+        patch_note.publish_at = None;
+    }
+
+    patch_note.status = status;
+    patch_note.updated_at = now;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scheduled_patch_notes_are_published() {
+        let dir = std::env::temp_dir().join(format!("database_{}", uuid::Uuid::new_v4()));
+
+        let db = Database::load(Arc::new(Store::open(&dir).unwrap())).unwrap();
+
+        let scheduled = db
+            .add_patch_note(
+                1,
+                "Release".to_string(),
+                "".to_string(),
+                BTreeMap::new(),
+                Some(100),
+            )
+            .await
+            .unwrap();
+        let later = db
+            .add_patch_note(
+                1,
+                "Next".to_string(),
+                "".to_string(),
+                BTreeMap::new(),
+                Some(200),
+            )
+            .await
+            .unwrap();
+        db.add_patch_note(
+            2,
+            "Draft".to_string(),
+            "".to_string(),
+            BTreeMap::new(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let published = db.publish_scheduled_patch_notes(150).await.unwrap();
+
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].id, scheduled.id);
+        assert_eq!(published[0].published_at, Some(150));
+        assert_eq!(published[0].publish_at, None);
+
+        let (total, drafts) = db
+            .patch_notes(&PatchNoteQuery {
+                take: 10,
+                skip: 0,
+                with_deleted: false,
+                status: Some(PatchNoteStatus::Draft),
+                author: Some(1),
+            })
+            .await;
+
+        assert_eq!(total, 1);
+        assert_eq!(drafts[0].id, later.id);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn saves_keep_revisions() {
+        let dir = std::env::temp_dir().join(format!("database_{}", uuid::Uuid::new_v4()));
+
+        let db = Database::load(Arc::new(Store::open(&dir).unwrap())).unwrap();
+
+        let patch_note = db
+            .add_patch_note(
+                1,
+                "".to_string(),
+                "First".to_string(),
+                BTreeMap::new(),
+                None,
+            )
+            .await
+            .unwrap();
+        let (before, after) = db
+            .update_patch_note(
+                patch_note.id,
+                2,
+                "".to_string(),
+                "Second".to_string(),
+                BTreeMap::new(),
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            (before.data.as_str(), after.data.as_str()),
+            ("First", "Second")
+        );
+
+        // Nothing changes, so nothing is audited
+        assert!(db
+            .set_patch_note_deleted(patch_note.id, false)
+            .await
+            .unwrap()
+            .is_none());
+
+        let revisions = db.patch_note_revisions(patch_note.id).await;
+
+        assert_eq!(
+            revisions.iter().map(|v| v.author).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+
+        let first = db.patch_note_revision(revisions[1].id).await.unwrap();
+        assert_eq!(first.patch_note.data, "First");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn failed_commit_keeps_items() {
+        let dir = std::env::temp_dir().join(format!("database_{}", uuid::Uuid::new_v4()));
+
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let db = Database::load(store.clone()).unwrap();
+
+        let patch_note = db
+            .add_patch_note(
+                1,
+                "".to_string(),
+                "First".to_string(),
+                BTreeMap::new(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        store.break_journal();
+
+        assert!(db
+            .update_patch_note(
+                patch_note.id,
+                2,
+                "".to_string(),
+                "Second".to_string(),
+                BTreeMap::new(),
+                None,
+            )
+            .await
+            .is_err());
+        assert!(db.add_admin_user("Admin".to_string(), None).await.is_err());
+
+        assert_eq!(db.patch_note(patch_note.id).await.unwrap().data, "First");
+        assert_eq!(db.patch_note_revisions(patch_note.id).await.len(), 1);
+        assert!(db.admin_users().await.is_empty());
+        assert!(db
+            .set_patch_note_deleted(patch_note.id, true)
+            .await
+            .is_err());
+        assert!(!db.patch_note(patch_note.id).await.unwrap().deleted);
+
+        let _ = std::fs::remove_dir_all(dir);
     }
 }
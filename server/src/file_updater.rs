@@ -1,325 +1,1437 @@
+//! Game files tree and compressed blobs
+//!
+//! Readers take an immutable snapshot of the tree and never wait for writers. Writers build the
+//! next tree and swap it in, they are serialized by a short lock which is never held during blob
+//! I/O. Blob writes and the tree update of the same path are ordered by a per-path lock.
+use crate::config::Config;
 use crate::log::app_log;
-use shared::file::{compress_in_mem, hash_of, ServerFileInfo, COMPRESSED_FOLDER_NAME};
-use shared::file::{ServerFolderInfo, ROOT_FOLDER_INFO_FILE_NAME};
-use std::io::Write;
-use std::path::Path;
-use std::sync::OnceLock;
-use tokio::io::{AsyncWrite, AsyncWriteExt};
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::store::{Store, Table, Transaction};
+use arc_swap::ArcSwap;
+use shared::file::ServerFolderInfo;
+use shared::file::{compress_in_mem, hash_of, ServerFileInfo, PREPARED_PREFIX};
+use shared::storage::{blob_key, BlobStorage};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, Weak};
+use tokio::sync::{Mutex, OwnedMutexGuard};
 use tokio::task::spawn_blocking;
 use tracing::log::Level;
 use tracing::{error, info};
 
-static INSTANCE: OnceLock<RwLock<FileHolder>> = OnceLock::new();
+static INSTANCE: OnceLock<FileHolder> = OnceLock::new();
+static STORAGE: OnceLock<Box<dyn BlobStorage>> = OnceLock::new();
 
-pub struct FileHolder {
-    pub root_folder: ServerFolderInfo,
+/// Compressed game files, a directory or a bucket depending on the config
+pub fn storage() -> &'static dyn BlobStorage {
+    STORAGE
+        .get_or_init(|| {
+            Config::instance()
+                .blob_storage()
+                .expect("Storage config is checked on startup")
+        })
+        .as_ref()
 }
 
-impl FileHolder {
-    pub fn dump(&self) {
-        let mut file =
-            match std::fs::File::create(format!("./database/{ROOT_FOLDER_INFO_FILE_NAME}")) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Can't create file: {e}");
+/// Path and record of a [`Table::Folders`] or [`Table::Files`] row
+pub(crate) type Row<T> = (String, T);
+
+/// Flattens the tree into [`Table::Folders`] and [`Table::Files`] rows keyed by path
+///
+/// Folder rows keep only the folder's own fields, children are separate rows
+pub(crate) fn tree_rows(
+    root: &ServerFolderInfo,
+) -> (Vec<Row<ServerFolderInfo>>, Vec<Row<ServerFileInfo>>) {
+    fn walk(
+        path: &str,
+        folder: &ServerFolderInfo,
+        folders: &mut Vec<Row<ServerFolderInfo>>,
+        files: &mut Vec<Row<ServerFileInfo>>,
+    ) {
+        for (name, file) in &folder.files {
+            files.push((blob_key(path, name), file.clone()));
+        }
 
-                    return;
-                }
-            };
+        for (name, child) in &folder.folders {
+            let child_path = blob_key(path, name);
 
-        if let Err(e) = file.write_all(
-            ron::ser::to_string_pretty(&self.root_folder, ron::ser::PrettyConfig::default())
-                .unwrap()
-                .as_bytes(),
-        ) {
-            error!("Can't write updater metadata: {e}");
+            folders.push((child_path.clone(), folder_row(child)));
+            walk(&child_path, child, folders, files);
         }
     }
 
-    fn file_info(&self, folder_path: &str, file_name: &str) -> Option<&ServerFileInfo> {
-        self.folder_info(folder_path)?.files.get(file_name)
+    let mut folders = vec![];
+    let mut files = vec![];
+
+    walk("", root, &mut folders, &mut files);
+
+    (folders, files)
+}
+
+fn folder_row(folder: &ServerFolderInfo) -> ServerFolderInfo {
+    ServerFolderInfo {
+        files: Default::default(),
+        folders: Default::default(),
+        ..folder.clone()
+    }
+}
+
+/// `folder/sub/file` -> (`folder/sub`, `file`)
+fn split_path(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Builds the tree back from the rows of [`tree_rows`]
+fn tree_from_rows(
+    mut folders: Vec<Row<ServerFolderInfo>>,
+    files: Vec<Row<ServerFileInfo>>,
+) -> anyhow::Result<ServerFolderInfo> {
+    let mut root = ServerFolderInfo::default();
+
+    // Parents go before their children
+    folders.sort_by_key(|(path, _)| path.matches('/').count());
+
+    for (path, folder) in folders {
+        let (parent, name) = split_path(&path);
+
+        folder_info_mut(&mut root, parent)
+            .ok_or_else(|| anyhow::anyhow!("No parent folder of {path}"))?
+            .folders
+            .insert(name.to_string(), folder);
     }
 
-    fn file_info_mut(&mut self, folder_path: &str, file_name: &str) -> Option<&mut ServerFileInfo> {
-        self.folder_info_mut(folder_path)?.files.get_mut(file_name)
+    for (path, file) in files {
+        let (parent, name) = split_path(&path);
+
+        folder_info_mut(&mut root, parent)
+            .ok_or_else(|| anyhow::anyhow!("No folder of {path}"))?
+            .files
+            .insert(name.to_string(), file);
     }
 
-    /// returns (mut_ref_to_file_info, was_file_info_just_created)
-    fn get_or_create_file_info(
-        &mut self,
-        folder_path: &str,
-        file_name: &str,
-    ) -> (&mut ServerFileInfo, bool) {
-        let mut current_folder = &mut self.root_folder;
-        if !folder_path.is_empty() {
-            for folder in folder_path.split('/') {
-                if !current_folder.folders.contains_key(folder) {
-                    current_folder
-                        .folders
-                        .insert(folder.to_string(), ServerFolderInfo::default());
-                }
+    root.calc_size();
 
-                current_folder = current_folder.folders.get_mut(folder).unwrap();
-            }
-        }
+    Ok(root)
+}
 
-        let exists = current_folder.files.contains_key(file_name);
+pub fn folder_info<'a>(root: &'a ServerFolderInfo, path: &str) -> Option<&'a ServerFolderInfo> {
+    let mut current_folder = root;
 
-        if !exists {
-            current_folder
-                .files
-                .insert(file_name.to_string(), ServerFileInfo::default());
-        }
+    if path.is_empty() {
+        return Some(current_folder);
+    }
 
-        (current_folder.files.get_mut(file_name).unwrap(), !exists)
+    for f in path.split('/') {
+        current_folder = current_folder.folders.get(f)?;
     }
 
-    pub fn folder_info_mut(&mut self, path: &str) -> Option<&mut ServerFolderInfo> {
-        let mut current_folder = &mut self.root_folder;
+    Some(current_folder)
+}
+
+fn folder_info_mut<'a>(
+    root: &'a mut ServerFolderInfo,
+    path: &str,
+) -> Option<&'a mut ServerFolderInfo> {
+    let mut current_folder = root;
 
-        if path.is_empty() {
-            return Some(current_folder);
-        }
+    if path.is_empty() {
+        return Some(current_folder);
+    }
+
+    for f in path.split('/') {
+        current_folder = current_folder.folders.get_mut(f)?;
+    }
 
-        for f in path.split('/') {
-            current_folder = current_folder.folders.get_mut(f)?;
+    Some(current_folder)
+}
+
+pub fn file_info<'a>(
+    root: &'a ServerFolderInfo,
+    folder_path: &str,
+    file_name: &str,
+) -> Option<&'a ServerFileInfo> {
+    folder_info(root, folder_path)?.files.get(file_name)
+}
+
+fn file_info_mut<'a>(
+    root: &'a mut ServerFolderInfo,
+    folder_path: &str,
+    file_name: &str,
+) -> Option<&'a mut ServerFileInfo> {
+    folder_info_mut(root, folder_path)?.files.get_mut(file_name)
+}
+
+fn get_or_create_folder_info<'a>(
+    root: &'a mut ServerFolderInfo,
+    path: &str,
+) -> &'a mut ServerFolderInfo {
+    let mut current_folder = root;
+    if !path.is_empty() {
+        for folder in path.split('/') {
+            current_folder = current_folder
+                .folders
+                .entry(folder.to_string())
+                .or_default();
         }
+    }
 
-        Some(current_folder)
+    current_folder
+}
+
+/// returns (mut_ref_to_file_info, was_file_info_just_created)
+fn get_or_create_file_info<'a>(
+    root: &'a mut ServerFolderInfo,
+    folder_path: &str,
+    file_name: &str,
+) -> (&'a mut ServerFileInfo, bool) {
+    let current_folder = get_or_create_folder_info(root, folder_path);
+
+    let exists = current_folder.files.contains_key(file_name);
+
+    (
+        current_folder
+            .files
+            .entry(file_name.to_string())
+            .or_default(),
+        !exists,
+    )
+}
+
+/// A file or a folder is at the path
+fn entry_exists(root: &ServerFolderInfo, path: &str) -> bool {
+    let (folder_path, name) = split_path(path);
+
+    folder_info(root, folder_path)
+        .is_some_and(|v| v.files.contains_key(name) || v.folders.contains_key(name))
+}
+
+/// Checks the path of a moved entry, it is a key of its rows and blobs
+fn check_move_path(path: &str) -> anyhow::Result<()> {
+    if path
+        .split('/')
+        .any(|v| v.is_empty() || v == "." || v == "..")
+    {
+        return Err(anyhow::anyhow!("Invalid path {path}"));
     }
 
-    pub fn folder_info(&self, path: &str) -> Option<&ServerFolderInfo> {
-        let mut current_folder = &self.root_folder;
+    Ok(())
+}
+
+/// The entry `name` in `folder_path` is a single segment, other names break the rows on reload
+fn check_entry_path(folder_path: &str, name: &str) -> anyhow::Result<()> {
+    if !folder_path.is_empty() {
+        check_move_path(folder_path)?;
+    }
+
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(anyhow::anyhow!("Invalid name {name:?}"));
+    }
+
+    Ok(())
+}
 
-        if path.is_empty() {
-            return Some(current_folder);
+/// Deleted at or before `cutoff`, entries deleted by older versions have no time and are kept
+pub(crate) fn expired(deleted: bool, deleted_at: Option<i64>, cutoff: i64) -> bool {
+    deleted && deleted_at.is_some_and(|v| v <= cutoff)
+}
+
+/// The file or one of its folders is [`expired`]
+fn file_expired(root: &ServerFolderInfo, folder_path: &str, file_name: &str, cutoff: i64) -> bool {
+    let Some(file) = file_info(root, folder_path, file_name) else {
+        return false;
+    };
+
+    let mut current_folder = root;
+
+    if !folder_path.is_empty() {
+        for name in folder_path.split('/') {
+            let Some(folder) = current_folder.folders.get(name) else {
+                return false;
+            };
+
+            if expired(folder.deleted, folder.deleted_at, cutoff) {
+                return true;
+            }
+
+            current_folder = folder;
         }
+    }
+
+    expired(file.deleted, file.deleted_at, cutoff)
+}
+
+fn has_files(folder: &ServerFolderInfo) -> bool {
+    !folder.files.is_empty() || folder.folders.values().any(has_files)
+}
 
-        for f in path.split('/') {
-            current_folder = current_folder.folders.get(f)?;
+/// The closest deleted folder on `path` including itself, entries under it are in the trash
+pub(crate) fn deleted_ancestor(root: &ServerFolderInfo, path: &str) -> Option<String> {
+    let mut folder = root;
+    let mut ancestor = None;
+
+    for (i, name) in path.split('/').enumerate() {
+        folder = folder.folders.get(name)?;
+
+        if folder.deleted {
+            ancestor = Some(path.split('/').take(i + 1).collect::<Vec<_>>().join("/"));
         }
+    }
 
-        Some(current_folder)
+    ancestor
+}
+
+/// Stages the rows of every folder on the path, sizes and update times change along it
+fn stage_folders(root: &ServerFolderInfo, folder_path: &str, tx: &mut Transaction) {
+    if folder_path.is_empty() {
+        return;
     }
 
-    pub async fn instance<'a>() -> RwLockReadGuard<'a, FileHolder> {
-        INSTANCE
-            .get_or_init(|| RwLock::new(Self::new()))
-            .read()
-            .await
+    let mut path = String::new();
+    let mut current_folder = root;
+
+    for name in folder_path.split('/') {
+        let Some(folder) = current_folder.folders.get(name) else {
+            return;
+        };
+
+        path = blob_key(&path, name);
+        tx.put(Table::Folders, &path, &folder_row(folder));
+
+        current_folder = folder;
     }
+}
 
-    pub async fn instance_mut<'a>() -> RwLockWriteGuard<'a, FileHolder> {
-        INSTANCE
-            .get_or_init(|| RwLock::new(Self::new()))
-            .write()
-            .await
+fn stage_file(root: &ServerFolderInfo, folder_path: &str, file_name: &str, tx: &mut Transaction) {
+    if let Some(file) = file_info(root, folder_path, file_name) {
+        tx.put(Table::Files, blob_key(folder_path, file_name), file);
+    }
+
+    stage_folders(root, folder_path, tx);
+}
+
+fn hashes(files: &[Row<ServerFileInfo>]) -> HashMap<&str, &str> {
+    files
+        .iter()
+        .map(|(path, file)| (path.as_str(), file.hash.as_str()))
+        .collect()
+}
+
+/// A missing blob stays missing at the new path, the scrub reports it
+async fn copy_blob(storage: &dyn BlobStorage, from: &str, to: &str) -> anyhow::Result<()> {
+    match storage.copy(from, to).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(anyhow::anyhow!("Can't copy blob {from} to {to}: {e}")),
+    }
+}
+
+/// Trees around one committed change, audit entries are built from them
+pub struct TreeChange {
+    pub before: Arc<ServerFolderInfo>,
+    pub after: Arc<ServerFolderInfo>,
+}
+
+pub struct FileHolder {
+    tree: ArcSwap<ServerFolderInfo>,
+    /// Serializes tree updates, held only while the next tree is built and committed
+    writer: Mutex<()>,
+    /// Blob path -> lock of its writers, entries are dropped with the last guard
+    paths: std::sync::Mutex<HashMap<String, Weak<Mutex<()>>>>,
+    store: Arc<Store>,
+    storage: &'static dyn BlobStorage,
+}
+
+impl FileHolder {
+    pub fn instance() -> &'static FileHolder {
+        INSTANCE.get_or_init(|| {
+            Self::load(Store::instance(), storage())
+                .unwrap_or_else(|e| panic!("Can't load the files tree: {e}"))
+        })
+    }
+
+    pub(crate) fn load(
+        store: Arc<Store>,
+        storage: &'static dyn BlobStorage,
+    ) -> anyhow::Result<Self> {
+        let root_folder = tree_from_rows(store.load(Table::Folders)?, store.load(Table::Files)?)?;
+
+        if root_folder.folders.is_empty() && root_folder.files.is_empty() {
+            info!("No game files were found!");
+        }
+
+        Ok(Self {
+            tree: ArcSwap::from_pointee(root_folder),
+            writer: Mutex::new(()),
+            paths: Default::default(),
+            store,
+            storage,
+        })
+    }
+
+    /// Files tree at this moment, later changes are not visible in it
+    pub fn snapshot(&self) -> Arc<ServerFolderInfo> {
+        self.tree.load_full()
     }
 
     pub fn info(&self) -> String {
         format!(
-            "File Holder:\n\tTotal files: {}\n",
-            self.root_folder.files_count
+            "File Holder:\n\tTotal files: {}\n\tStorage: {}\n",
+            self.snapshot().files_count,
+            self.storage.describe()
         )
     }
 
-    fn new() -> Self {
-        let root_folder = if let Ok(file) =
-            std::fs::File::open(format!("./database/{ROOT_FOLDER_INFO_FILE_NAME}"))
-        {
-            if let Ok(d) = ron::de::from_reader::<std::fs::File, ServerFolderInfo>(file) {
-                d
-            } else {
-                error!("Corrupted root folder info: ./database/{ROOT_FOLDER_INFO_FILE_NAME}");
+    /// Writers of a path wait for each other, other paths are not affected
+    async fn lock_path(&self, path: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut paths = self.paths.lock().unwrap();
 
-                ServerFolderInfo::default()
-            }
-        } else {
-            info!("No root folder info was found!");
+            paths.retain(|_, v| v.strong_count() > 0);
 
-            ServerFolderInfo::default()
+            match paths.get(path).and_then(Weak::upgrade) {
+                Some(v) => v,
+                None => {
+                    let v = Arc::new(Mutex::new(()));
+                    paths.insert(path.to_string(), Arc::downgrade(&v));
+                    v
+                }
+            }
         };
 
-        Self { root_folder }
+        lock.lock_owned().await
     }
-}
 
-trait AsyncOps {
-    /// Writes opcode, which means that **raw file** will be sent
-    ///
-    /// Flush **is not** called!
-    async fn write_file_prefix(&mut self) -> tokio::io::Result<usize>;
-    /// Writes opcode, which means that **packet** will be sent
-    ///
-    /// Flush **is not** called!
-    async fn write_packet_prefix(&mut self) -> tokio::io::Result<usize>;
-}
+    /// Locks the paths in the same order for every caller, so two moves can't deadlock
+    async fn lock_paths(&self, paths: &[&str]) -> Vec<OwnedMutexGuard<()>> {
+        let mut paths = paths.to_vec();
+
+        paths.sort();
+        paths.dedup();
+
+        let mut guards = Vec::with_capacity(paths.len());
+
+        for path in paths {
+            guards.push(self.lock_path(path).await);
+        }
 
-impl<W: AsyncWrite + Unpin> AsyncOps for W {
-    async fn write_file_prefix(&mut self) -> tokio::io::Result<usize> {
-        self.write(&[0x0]).await
+        guards
     }
-    async fn write_packet_prefix(&mut self) -> tokio::io::Result<usize> {
-        self.write(&[0x1]).await
+
+    /// Applies `change` to a copy of the tree, commits the staged rows and publishes the copy
+    ///
+    /// Nothing is changed when `change` returns `false` or the commit fails
+    async fn update(
+        &self,
+        change: impl FnOnce(&mut ServerFolderInfo, &mut Transaction) -> bool,
+    ) -> anyhow::Result<bool> {
+        Ok(self.update_tree(change).await?.is_some())
+    }
+
+    /// Like [`FileHolder::update`], returns the trees around the change
+    async fn update_tree(
+        &self,
+        change: impl FnOnce(&mut ServerFolderInfo, &mut Transaction) -> bool,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        let _writer = self.writer.lock().await;
+
+        let before = self.tree.load_full();
+        let mut tree = ServerFolderInfo::clone(&before);
+        let mut tx = Transaction::default();
+
+        if !change(&mut tree, &mut tx) {
+            return Ok(None);
+        }
+
+        let store = self.store.clone();
+
+        let committed = spawn_blocking(move || store.commit(tx))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|v| v);
+
+        if let Err(e) = committed {
+            error!("Can't write updater metadata: {e}");
+
+            return Err(anyhow::anyhow!("Can't save the change: {e}"));
+        }
+
+        let after = Arc::new(tree);
+
+        self.tree.store(after.clone());
+
+        Ok(Some(TreeChange { before, after }))
     }
 }
 
 impl FileHolder {
-    pub async fn create_folder(parent_folder_path: &str, new_folder_name: &str) -> bool {
-        let Ok(_) = std::fs::create_dir(
-            Path::new(COMPRESSED_FOLDER_NAME)
-                .join(parent_folder_path)
-                .join(new_folder_name),
-        ) else {
-            return false;
-        };
-
-        let mut instance = Self::instance_mut().await;
+    pub async fn create_folder(
+        &self,
+        parent_folder_path: &str,
+        new_folder_name: &str,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        check_entry_path(parent_folder_path, new_folder_name)?;
+
+        let path = blob_key(parent_folder_path, new_folder_name);
+        let _path = self.lock_path(&path).await;
+
+        self.update_tree(|tree, tx| {
+            // Folders exist only in the tree, blob keys are full paths
+            let Some(f) = folder_info_mut(tree, parent_folder_path) else {
+                return false;
+            };
 
-        let Some(f) = instance.folder_info_mut(parent_folder_path) else {
-            return false;
-        };
+            if f.folders.contains_key(new_folder_name) || f.files.contains_key(new_folder_name) {
+                return false;
+            }
 
-        f.folders
-            .insert(new_folder_name.to_string(), ServerFolderInfo::default());
+            f.folders.insert(
+                new_folder_name.to_string(),
+                ServerFolderInfo {
+                    updated_by: user_id,
+                    ..Default::default()
+                },
+            );
 
-        instance.dump();
+            stage_folders(tree, &path, tx);
 
-        true
+            true
+        })
+        .await
     }
 
-    pub async fn add_file(folder_path: &str, file_name: &str, bytes: Vec<u8>) -> bool {
+    pub async fn add_file(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        bytes: Vec<u8>,
+        user_id: u32,
+    ) -> anyhow::Result<TreeChange> {
+        check_entry_path(folder_path, file_name)?;
+
         let initial_size = bytes.len();
 
-        let Ok((hash, compressed_bytes)) = spawn_blocking(move || {
+        let (hash, compressed_bytes) = spawn_blocking(move || {
             let mut out = vec![];
             compress_in_mem(&bytes, &mut out).unwrap();
             (hash_of(&bytes), out)
         })
+        .await?;
+
+        let key = blob_key(folder_path, file_name);
+
+        // The blob and its tree entry are replaced by one upload at a time
+        let _path = self.lock_path(&key).await;
+
+        if let Err(e) = self.storage.put_bytes(&key, compressed_bytes).await {
+            app_log(Level::Error, &format!("Can't store file {key}: {e}")).await;
+
+            return Err(anyhow::anyhow!("Can't store file {key}: {e}"));
+        }
+
+        let change = self
+            .update_tree(|tree, tx| {
+                let now = chrono::Utc::now().timestamp();
+                let (file_info, just_created) =
+                    get_or_create_file_info(tree, folder_path, file_name);
+
+                if just_created {
+                    file_info.created_at = now;
+                }
+
+                file_info.hash = hash;
+                file_info.size = initial_size as u64;
+                file_info.updated_at = now;
+                file_info.updated_by = user_id;
+                file_info.deleted = false;
+                file_info.deleted_at = None;
+                file_info.deleted_by = None;
+                file_info.quarantined = false;
+                file_info.source_modified_at = None;
+
+                tree.calc_size();
+
+                stage_file(tree, folder_path, file_name, tx);
+
+                true
+            })
+            .await?;
+
+        change.ok_or_else(|| anyhow::anyhow!("{key} was not changed"))
+    }
+
+    /// Deletes the file or restores a deleted one
+    pub async fn delete_file(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        self.set_file_deleted(folder_path, file_name, None, user_id)
+            .await
+    }
+
+    /// Restores the file from the trash, files which are not deleted are left as is
+    pub async fn restore_file(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        user_id: u32,
+    ) -> anyhow::Result<bool> {
+        let restored = self
+            .set_file_deleted(folder_path, file_name, Some(false), user_id)
+            .await?;
+
+        Ok(restored.is_some())
+    }
+
+    /// `None` toggles the deletion
+    async fn set_file_deleted(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        deleted: Option<bool>,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        let _path = self.lock_path(&blob_key(folder_path, file_name)).await;
+        let mut deleted_parent = None;
+
+        let changed = self
+            .update_tree(|tree, tx| {
+                let parent = deleted_ancestor(tree, folder_path);
+
+                let Some(file_info) = file_info_mut(tree, folder_path, file_name) else {
+                    return false;
+                };
+
+                let deleted = deleted.unwrap_or(!file_info.deleted);
+
+                if deleted == file_info.deleted {
+                    return false;
+                }
+
+                if !deleted && parent.is_some() {
+                    deleted_parent = parent;
+
+                    return false;
+                }
+
+                file_info.deleted = deleted;
+                file_info.deleted_at = deleted.then(|| chrono::Utc::now().timestamp());
+                file_info.deleted_by = deleted.then_some(user_id);
+                file_info.updated_by = user_id;
+
+                stage_file(tree, folder_path, file_name, tx);
+
+                true
+            })
+            .await?;
+
+        match deleted_parent {
+            Some(parent) => Err(anyhow::anyhow!("{parent} is deleted, restore it first")),
+            None => Ok(changed),
+        }
+    }
+
+    pub async fn toggle_hash_check(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        let _path = self.lock_path(&blob_key(folder_path, file_name)).await;
+
+        self.update_tree(|tree, tx| {
+            let Some(file_info) = file_info_mut(tree, folder_path, file_name) else {
+                return false;
+            };
+
+            file_info.skip_hash_check = !file_info.skip_hash_check;
+            file_info.updated_by = user_id;
+
+            stage_file(tree, folder_path, file_name, tx);
+
+            true
+        })
         .await
-        else {
-            return false;
-        };
+    }
 
-        let mut instance = Self::instance_mut().await;
+    /// Deletes the folder or restores a deleted one
+    pub async fn delete_folder(
+        &self,
+        folder_path: &str,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        self.set_folder_deleted(folder_path, None, user_id).await
+    }
 
-        let Ok(mut file) =
-            std::fs::File::create(Path::new("./compressed").join(folder_path).join(file_name))
-        else {
-            app_log(Level::Error, &format!("Can't create file {folder_path}")).await;
+    /// Restores the folder from the trash, its deleted files and subfolders stay deleted
+    pub async fn restore_folder(&self, folder_path: &str, user_id: u32) -> anyhow::Result<bool> {
+        let restored = self
+            .set_folder_deleted(folder_path, Some(false), user_id)
+            .await?;
 
-            return false;
-        };
+        Ok(restored.is_some())
+    }
 
-        let Ok(_) = file.write_all(&compressed_bytes) else {
-            app_log(
-                Level::Error,
-                &format!("Can't write to created file {folder_path}"),
-            )
-            .await;
+    /// `None` toggles the deletion
+    async fn set_folder_deleted(
+        &self,
+        folder_path: &str,
+        deleted: Option<bool>,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        let _path = self.lock_path(folder_path).await;
+        let mut deleted_parent = None;
+
+        let changed = self
+            .update_tree(|tree, tx| {
+                let parent = folder_path
+                    .rsplit_once('/')
+                    .and_then(|(parent, _)| deleted_ancestor(tree, parent));
+
+                let Some(folder_info) = folder_info_mut(tree, folder_path) else {
+                    return false;
+                };
+
+                let deleted = deleted.unwrap_or(!folder_info.deleted);
+
+                if folder_path.is_empty() || deleted == folder_info.deleted {
+                    return false;
+                }
 
-            return false;
-        };
+                if !deleted && parent.is_some() {
+                    deleted_parent = parent;
 
-        let (file_info, just_created) = instance.get_or_create_file_info(folder_path, file_name);
+                    return false;
+                }
 
-        if just_created {
-            file_info.created_at = chrono::Utc::now().timestamp();
+                folder_info.deleted = deleted;
+                folder_info.deleted_at = deleted.then(|| chrono::Utc::now().timestamp());
+                folder_info.deleted_by = deleted.then_some(user_id);
+                folder_info.updated_by = user_id;
+
+                stage_folders(tree, folder_path, tx);
+
+                true
+            })
+            .await?;
+
+        match deleted_parent {
+            Some(parent) => Err(anyhow::anyhow!("{parent} is deleted, restore it first")),
+            None => Ok(changed),
         }
+    }
 
-        file_info.hash = hash;
-        file_info.size = initial_size as u64;
-        file_info.updated_at = chrono::Utc::now().timestamp();
-        file_info.deleted = false;
+    /// Sets whether the file is served, unless it was replaced since its blob with `hash` was read
+    pub async fn set_quarantined(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        hash: &str,
+        quarantined: bool,
+    ) -> anyhow::Result<bool> {
+        let _path = self.lock_path(&blob_key(folder_path, file_name)).await;
 
-        instance.root_folder.calc_size();
+        self.update(|tree, tx| {
+            let Some(file_info) = file_info_mut(tree, folder_path, file_name) else {
+                return false;
+            };
+
+            if file_info.hash != hash || file_info.quarantined == quarantined {
+                return false;
+            }
+
+            file_info.quarantined = quarantined;
 
-        instance.dump();
+            stage_file(tree, folder_path, file_name, tx);
 
-        true
+            true
+        })
+        .await
     }
 
-    pub async fn delete_file(folder_path: &str, file_name: &str) -> bool {
-        let mut instance = Self::instance_mut().await;
+    /// Sets the size of a file recorded by an older preparator, unless it was replaced since
+    pub async fn set_size(
+        &self,
+        folder_path: &str,
+        file_name: &str,
+        hash: &str,
+        size: u64,
+    ) -> anyhow::Result<bool> {
+        let _path = self.lock_path(&blob_key(folder_path, file_name)).await;
 
-        let Some(file_info) = instance.file_info_mut(folder_path, file_name) else {
-            return false;
-        };
+        self.update(|tree, tx| {
+            let Some(file_info) = file_info_mut(tree, folder_path, file_name) else {
+                return false;
+            };
+
+            if file_info.hash != hash || file_info.size == size {
+                return false;
+            }
 
-        file_info.deleted = !file_info.deleted;
+            file_info.size = size;
 
-        instance.dump();
+            tree.calc_size();
 
-        true
+            stage_file(tree, folder_path, file_name, tx);
+
+            true
+        })
+        .await
     }
 
-    pub async fn toggle_hash_check(folder_path: &str, file_name: &str) -> bool {
-        let mut instance = Self::instance_mut().await;
+    /// Puts a file of the preparator list into the tree with its staged blob
+    ///
+    /// A missing staged blob leaves the blob at the key as is, older preparators wrote it there
+    pub async fn merge_prepared_file(
+        &self,
+        path: &str,
+        file: &ServerFileInfo,
+    ) -> anyhow::Result<bool> {
+        let (folder_path, file_name) = split_path(path);
 
-        let Some(file_info) = instance.file_info_mut(folder_path, file_name) else {
-            return false;
-        };
+        check_entry_path(folder_path, file_name)?;
+
+        let _path = self.lock_path(path).await;
+
+        copy_blob(self.storage, &format!("{PREPARED_PREFIX}{path}"), path).await?;
+
+        self.update(|tree, tx| {
+            let (file_info, just_created) = get_or_create_file_info(tree, folder_path, file_name);
+
+            if just_created {
+                file_info.created_at = file.created_at;
+                file_info.skip_hash_check = file.skip_hash_check;
+            }
+
+            file_info.hash = file.hash.clone();
+            file_info.size = file.size;
+            file_info.updated_at = chrono::Utc::now().timestamp();
+            file_info.updated_by = 0;
+            file_info.deleted = false;
+            file_info.deleted_at = None;
+            file_info.deleted_by = None;
+            file_info.quarantined = false;
+            file_info.source_modified_at = file.source_modified_at;
+
+            tree.calc_size();
+
+            stage_file(tree, folder_path, file_name, tx);
+
+            true
+        })
+        .await
+    }
+
+    /// Deletes a file which is not in the preparator list anymore, unless it was replaced since
+    pub async fn delete_prepared_file(&self, path: &str, hash: &str) -> anyhow::Result<bool> {
+        let (folder_path, file_name) = split_path(path);
+
+        let _path = self.lock_path(path).await;
 
-        file_info.skip_hash_check = !file_info.skip_hash_check;
+        self.update(|tree, tx| {
+            let Some(file_info) = file_info_mut(tree, folder_path, file_name) else {
+                return false;
+            };
 
-        instance.dump();
+            if file_info.deleted || file_info.hash != hash {
+                return false;
+            }
 
-        true
+            file_info.deleted = true;
+            file_info.deleted_at = Some(chrono::Utc::now().timestamp());
+            file_info.deleted_by = Some(0);
+            file_info.updated_by = 0;
+
+            stage_file(tree, folder_path, file_name, tx);
+
+            true
+        })
+        .await
     }
 
-    pub async fn delete_folder(folder_path: &str) -> bool {
-        let mut instance = Self::instance_mut().await;
+    /// Moves or renames the file, it keeps its hash and times so clients don't download it again
+    ///
+    /// The blob is copied first, the tree entry is moved by one commit and the old blob is
+    /// deleted last. A crash in between leaves an orphan blob, which is collected by GC
+    pub async fn move_file(
+        &self,
+        from: &str,
+        to: &str,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        check_move_path(from)?;
+        check_move_path(to)?;
+
+        if from == to {
+            return Ok(None);
+        }
+
+        let _paths = self.lock_paths(&[from, to]).await;
+
+        let (from_folder, from_name) = split_path(from);
+        let (to_folder, to_name) = split_path(to);
+
+        {
+            let tree = self.snapshot();
+
+            if file_info(&tree, from_folder, from_name).is_none() {
+                return Err(anyhow::anyhow!("No file {from}"));
+            }
+
+            if entry_exists(&tree, to) {
+                return Err(anyhow::anyhow!("{to} already exists"));
+            }
+        }
+
+        copy_blob(self.storage, from, to).await?;
+
+        let moved = self
+            .update_tree(|tree, tx| {
+                if entry_exists(tree, to) {
+                    return false;
+                }
+
+                let Some(mut file) =
+                    folder_info_mut(tree, from_folder).and_then(|v| v.files.remove(from_name))
+                else {
+                    return false;
+                };
+
+                file.updated_by = user_id;
+
+                *get_or_create_file_info(tree, to_folder, to_name).0 = file;
 
-        let Some(folder_info) = instance.folder_info_mut(folder_path) else {
-            return false;
+                tree.calc_size();
+
+                tx.delete(Table::Files, from);
+                stage_folders(tree, from_folder, tx);
+                stage_file(tree, to_folder, to_name, tx);
+
+                true
+            })
+            .await;
+
+        let change = match moved {
+            Ok(Some(v)) => v,
+            moved => {
+                let _ = self.storage.delete(to).await;
+
+                moved?;
+
+                return Err(anyhow::anyhow!("{from} was changed during the move"));
+            }
+        };
+
+        if let Err(e) = self.storage.delete(from).await {
+            error!("Can't delete moved blob {from}: {e}");
+        }
+
+        Ok(Some(change))
+    }
+
+    /// Moves or renames the folder with everything in it, like [`FileHolder::move_file`]
+    pub async fn move_folder(
+        &self,
+        from: &str,
+        to: &str,
+        user_id: u32,
+    ) -> anyhow::Result<Option<TreeChange>> {
+        check_move_path(from)?;
+        check_move_path(to)?;
+
+        if from == to {
+            return Ok(None);
+        }
+
+        if to.starts_with(&format!("{from}/")) {
+            return Err(anyhow::anyhow!("Can't move {from} into itself"));
+        }
+
+        let files = {
+            let tree = self.snapshot();
+
+            let Some(folder) = folder_info(&tree, from) else {
+                return Err(anyhow::anyhow!("No folder {from}"));
+            };
+
+            if entry_exists(&tree, to) {
+                return Err(anyhow::anyhow!("{to} already exists"));
+            }
+
+            tree_rows(folder).1
         };
 
-        folder_info.deleted = !folder_info.deleted;
+        // GC deletes blobs which are not in the tree, the copies are not there until the commit
+        let keys: Vec<_> = files.iter().map(|(path, _)| blob_key(to, path)).collect();
+        let mut paths: Vec<_> = keys.iter().map(String::as_str).collect();
+        paths.extend([from, to]);
+
+        let guards = self.lock_paths(&paths).await;
+
+        let mut copied = vec![];
+
+        for (path, _) in &files {
+            let key = blob_key(to, path);
+
+            match copy_blob(self.storage, &blob_key(from, path), &key).await {
+                Ok(()) => copied.push(key),
+                Err(e) => {
+                    for key in copied {
+                        let _ = self.storage.delete(&key).await;
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
 
-        instance.dump();
+        let (from_parent, from_name) = split_path(from);
+        let (to_parent, to_name) = split_path(to);
 
-        true
+        let moved = self
+            .update_tree(|tree, tx| {
+                if entry_exists(tree, to) {
+                    return false;
+                }
+
+                let Some(mut folder) =
+                    folder_info_mut(tree, from_parent).and_then(|v| v.folders.remove(from_name))
+                else {
+                    return false;
+                };
+
+                let (old_folders, old_files) = tree_rows(&folder);
+
+                // Files uploaded or replaced inside after their blobs were copied
+                if hashes(&old_files) != hashes(&files) {
+                    return false;
+                }
+
+                folder.updated_by = user_id;
+
+                get_or_create_folder_info(tree, to_parent)
+                    .folders
+                    .insert(to_name.to_string(), folder);
+
+                tree.calc_size();
+
+                tx.delete(Table::Folders, from);
+                for (path, _) in &old_folders {
+                    tx.delete(Table::Folders, blob_key(from, path));
+                }
+                for (path, _) in &old_files {
+                    tx.delete(Table::Files, blob_key(from, path));
+                }
+
+                if let Some(folder) = folder_info(tree, to) {
+                    let (folders, files) = tree_rows(folder);
+
+                    for (path, row) in folders {
+                        tx.put(Table::Folders, blob_key(to, &path), &row);
+                    }
+                    for (path, row) in files {
+                        tx.put(Table::Files, blob_key(to, &path), &row);
+                    }
+                }
+
+                stage_folders(tree, from_parent, tx);
+                stage_folders(tree, to, tx);
+
+                true
+            })
+            .await;
+
+        let change = match moved {
+            Ok(Some(v)) => v,
+            moved => {
+                for key in copied {
+                    let _ = self.storage.delete(&key).await;
+                }
+
+                moved?;
+
+                return Err(anyhow::anyhow!("{from} was changed during the move"));
+            }
+        };
+
+        drop(guards);
+
+        // Uploads into the old path lock only their files, so the tree is checked for each blob
+        for (path, _) in files {
+            let key = blob_key(from, &path);
+
+            if let Err(e) = self.delete_orphan_blob(&key).await {
+                error!("Can't delete moved blob {key}: {e}");
+            }
+        }
+
+        Ok(Some(change))
     }
 
-    pub async fn send_file<W: AsyncWrite + Unpin>(
+    /// Removes an [`expired`] file from the tree and then its blob, returns the file size
+    ///
+    /// A crash in between leaves an orphan blob, which is collected by the next run
+    pub async fn purge_file(
+        &self,
         folder_path: &str,
         file_name: &str,
-        out_stream: &mut W,
-    ) -> bool {
-        let instance = Self::instance().await;
+        cutoff: i64,
+    ) -> anyhow::Result<Option<u64>> {
+        let key = blob_key(folder_path, file_name);
+        let _path = self.lock_path(&key).await;
+
+        let mut size = None;
 
-        if let Some(info) = instance.file_info(folder_path, file_name) {
-            if info.deleted {
+        self.update(|tree, tx| {
+            if !file_expired(tree, folder_path, file_name, cutoff) {
                 return false;
             }
 
-            let Ok(mut file) =
-                tokio::fs::File::open(Path::new("./compressed").join(folder_path).join(file_name))
-                    .await
+            let Some(file) =
+                folder_info_mut(tree, folder_path).and_then(|v| v.files.remove(file_name))
             else {
-                app_log(
-                    Level::Error,
-                    &format!("File {folder_path} is presented in filelist, but not exists!"),
-                )
-                .await;
+                return false;
+            };
+
+            size = Some(file.size);
+
+            tree.calc_size();
+
+            tx.delete(Table::Files, &key);
+            stage_folders(tree, folder_path, tx);
+
+            true
+        })
+        .await?;
+
+        if size.is_some() {
+            self.storage.delete(&key).await?;
+        }
+
+        Ok(size)
+    }
+
+    /// Removes an [`expired`] folder which has no files left, with its empty subfolders
+    pub async fn purge_folder(&self, folder_path: &str, cutoff: i64) -> anyhow::Result<bool> {
+        let _path = self.lock_path(folder_path).await;
 
+        self.update(|tree, tx| {
+            let (parent, name) = split_path(folder_path);
+
+            let Some(parent_folder) = folder_info_mut(tree, parent) else {
                 return false;
             };
 
-            let Ok(_) = out_stream.write_file_prefix().await else {
+            let Some(folder) = parent_folder.folders.get(name) else {
                 return false;
             };
 
-            let Ok(_) = tokio::io::copy(&mut file, out_stream).await else {
+            if folder_path.is_empty()
+                || !expired(folder.deleted, folder.deleted_at, cutoff)
+                || has_files(folder)
+            {
+                return false;
+            }
+
+            let Some(folder) = parent_folder.folders.remove(name) else {
                 return false;
             };
 
-            return true;
+            let (folders, _) = tree_rows(&folder);
+
+            tx.delete(Table::Folders, folder_path);
+            for (path, _) in folders {
+                tx.delete(Table::Folders, blob_key(folder_path, &path));
+            }
+
+            tree.calc_size();
+            stage_folders(tree, parent, tx);
+
+            true
+        })
+        .await
+    }
+
+    /// Deletes the blob unless a file of the tree refers to it, returns whether it was deleted
+    pub async fn delete_orphan_blob(&self, key: &str) -> std::io::Result<bool> {
+        // Uploads hold the path lock until their file is in the tree
+        let _path = self.lock_path(key).await;
+
+        let (folder_path, file_name) = split_path(key);
+
+        if file_info(&self.snapshot(), folder_path, file_name).is_some() {
+            return Ok(false);
+        }
+
+        self.storage.delete(key).await?;
+
+        Ok(true)
+    }
+
+    pub(crate) fn storage(&self) -> &'static dyn BlobStorage {
+        self.storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::storage::MemoryStorage;
+    use std::time::Duration;
+
+    fn test_holder() -> FileHolder {
+        let dir = std::env::temp_dir().join(format!("file_holder_{}", uuid::Uuid::new_v4()));
+
+        FileHolder::load(
+            Arc::new(Store::open(&dir).unwrap()),
+            Box::leak(Box::new(MemoryStorage::default())),
+        )
+        .unwrap()
+    }
+
+    /// A failed commit is reported and leaves the tree as it was
+    #[tokio::test]
+    async fn failed_commit_keeps_tree() {
+        let holder = test_holder();
+
+        holder
+            .add_file("data", "a.bin", vec![1; 10], 1)
+            .await
+            .unwrap();
+        holder.store.break_journal();
+
+        assert!(holder.delete_file("data", "a.bin", 1).await.is_err());
+        assert!(holder
+            .add_file("data", "b.bin", vec![2; 10], 1)
+            .await
+            .is_err());
+
+        let tree = holder.snapshot();
+        let data = &tree.folders["data"];
+
+        assert!(!data.files["a.bin"].deleted);
+        assert!(!data.files.contains_key("b.bin"));
+    }
+
+    /// Concurrent uploads of one path leave the tree hash matching the stored blob
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn uploads_of_one_path_are_ordered() {
+        let holder: &'static FileHolder = Box::leak(Box::new(test_holder()));
+
+        let mut compressed = HashMap::new();
+        let mut uploads = vec![];
+
+        for i in 0..16u8 {
+            let data = vec![i; 4096 + i as usize];
+
+            let mut out = vec![];
+            compress_in_mem(&data, &mut out).unwrap();
+            compressed.insert(hash_of(&data), out);
+
+            uploads.push(tokio::spawn(async move {
+                holder.add_file("", "same.bin", data, 1).await
+            }));
+        }
+
+        for upload in uploads {
+            upload.await.unwrap().unwrap();
+        }
+
+        let tree = holder.snapshot();
+        let hash = &file_info(&tree, "", "same.bin").unwrap().hash;
+
+        assert_eq!(
+            holder.storage.get_bytes("same.bin").await.unwrap(),
+            compressed[hash]
+        );
+
+        let rows: Vec<(String, ServerFileInfo)> = holder.store.load(Table::Files).unwrap();
+        assert_eq!(&rows[0].1.hash, hash);
+    }
+
+    #[tokio::test]
+    async fn moves_keep_files_and_rows() {
+        let dir = std::env::temp_dir().join(format!("file_holder_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let storage: &'static dyn BlobStorage = Box::leak(Box::new(MemoryStorage::default()));
+        let holder = FileHolder::load(store.clone(), storage).unwrap();
+
+        holder
+            .add_file("data/sub", "a.bin", vec![1; 10], 1)
+            .await
+            .unwrap();
+        holder
+            .add_file("data", "b.bin", vec![2; 10], 1)
+            .await
+            .unwrap();
+        holder
+            .add_file("other", "c.bin", vec![3; 10], 1)
+            .await
+            .unwrap();
+
+        let before = holder.snapshot().folders["data"].files["b.bin"].clone();
+
+        holder
+            .move_file("data/b.bin", "data/renamed.bin", 2)
+            .await
+            .unwrap();
+        assert!(holder
+            .move_file("data/renamed.bin", "other/c.bin", 2)
+            .await
+            .is_err());
+        assert!(holder
+            .move_folder("data", "data/sub/deeper", 2)
+            .await
+            .is_err());
+        holder.move_folder("data", "new/place", 2).await.unwrap();
+
+        for holder in [holder, FileHolder::load(store, storage).unwrap()] {
+            let tree = holder.snapshot();
+            let moved = &tree.folders["new"].folders["place"];
+
+            assert!(!tree.folders.contains_key("data"));
+            assert_eq!(moved.files["renamed.bin"].hash, before.hash);
+            assert_eq!(moved.files["renamed.bin"].created_at, before.created_at);
+            assert_eq!(moved.folders["sub"].files["a.bin"].size, 10);
+            assert_eq!(tree.files_count, 3);
         }
 
-        drop(instance);
+        assert_eq!(
+            storage.list("").await.unwrap(),
+            vec![
+                "new/place/renamed.bin",
+                "new/place/sub/a.bin",
+                "other/c.bin"
+            ]
+        );
+        assert_eq!(storage.get_bytes("new/place/renamed.bin").await.unwrap(), {
+            let mut out = vec![];
+            compress_in_mem(&[2; 10], &mut out).unwrap();
+            out
+        });
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Audit entries are built from the trees around the change, not from later snapshots
+    #[tokio::test]
+    async fn changes_return_their_trees() {
+        let holder = test_holder();
+
+        let change = holder.create_folder("", "data", 1).await.unwrap().unwrap();
+        assert!(folder_info(&change.before, "data").is_none());
+        assert_eq!(folder_info(&change.after, "data").unwrap().updated_by, 1);
+        assert!(holder.create_folder("", "data", 2).await.unwrap().is_none());
+
+        let change = holder
+            .add_file("data", "a.bin", vec![1; 10], 1)
+            .await
+            .unwrap();
+        assert!(file_info(&change.before, "data", "a.bin").is_none());
+
+        holder
+            .add_file("data", "a.bin", vec![2; 10], 2)
+            .await
+            .unwrap();
+
+        let change = holder
+            .delete_file("data", "a.bin", 3)
+            .await
+            .unwrap()
+            .unwrap();
+        let before = file_info(&change.before, "data", "a.bin").unwrap();
+        let after = file_info(&change.after, "data", "a.bin").unwrap();
+
+        assert_eq!((before.updated_by, before.deleted), (2, false));
+        assert_eq!((after.updated_by, after.deleted), (3, true));
+
+        assert!(holder
+            .move_file("data/a.bin", "data/a.bin", 3)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(holder
+            .delete_file("data", "b.bin", 3)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn invalid_names_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("file_holder_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let storage: &'static dyn BlobStorage = Box::leak(Box::new(MemoryStorage::default()));
+        let holder = FileHolder::load(store.clone(), storage).unwrap();
+
+        holder.create_folder("", "data", 1).await.unwrap();
+
+        for (folder, name) in [
+            ("data", "a/b"),
+            ("data", "a\\b"),
+            ("data", "."),
+            ("data", ".."),
+            ("data", ""),
+            ("data/../x", "a"),
+            ("/data", "a"),
+        ] {
+            assert!(holder.create_folder(folder, name, 1).await.is_err());
+            assert!(holder.add_file(folder, name, vec![1; 10], 1).await.is_err());
+        }
+
+        assert!(holder
+            .merge_prepared_file("data/../a.bin", &ServerFileInfo::default())
+            .await
+            .is_err());
+
+        holder.create_folder("data", "sub", 1).await.unwrap();
+        holder
+            .add_file("data/sub", "a.bin", vec![1; 10], 1)
+            .await
+            .unwrap();
+
+        let tree = FileHolder::load(store, storage).unwrap().snapshot();
+
+        assert_eq!(tree.folders.len(), 1);
+        assert_eq!(tree.folders["data"].folders.len(), 1);
+        assert_eq!(tree.folders["data"].folders["sub"].files.len(), 1);
+        assert_eq!(storage.list("data").await.unwrap(), vec!["data/sub/a.bin"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// GC used to delete the copied blobs as orphans before the moved folder was committed
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn gc_during_folder_move_keeps_copies() {
+        let holder: &'static FileHolder = Box::leak(Box::new(test_holder()));
+
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            holder.add_file("data", name, vec![1; 10], 1).await.unwrap();
+        }
+
+        // The move stops before its commit while the writer is held
+        let writer = holder.writer.lock().await;
+
+        let moving = tokio::spawn(holder.move_folder("data", "moved", 2));
+
+        while holder.storage.list("moved/").await.unwrap().len() < 3 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let gc = tokio::spawn(crate::gc::collect_garbage(holder, 0, 0, false));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        drop(writer);
+
+        moving.await.unwrap().unwrap();
+        let report = gc.await.unwrap();
 
-        false
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(
+            holder.storage.list("").await.unwrap(),
+            vec!["moved/a.bin", "moved/b.bin", "moved/c.bin"]
+        );
+        assert_eq!(holder.snapshot().folders["moved"].files.len(), 3);
     }
 }
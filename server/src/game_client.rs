@@ -0,0 +1,310 @@
+use crate::file_updater::{deleted_ancestor, file_info, FileHolder};
+use crate::log::app_log;
+use crate::statistics::Statistics;
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use shared::file::ServerFolderInfo;
+use std::ops::Range;
+use tracing::log::Level;
+use uuid::Uuid;
+
+/// Installation id, generated by the game client on the first launch
+///
+/// Used only to count unique installations
+static INSTALL_ID_HEADER: &str = "x-install-id";
+
+fn install_id(headers: &HeaderMap) -> Option<Uuid> {
+    headers.get(INSTALL_ID_HEADER)?.to_str().ok()?.parse().ok()
+}
+
+/// `bytes=start-end` and `bytes=start-`, used by clients to resume downloads
+fn requested_range(headers: &HeaderMap) -> Option<Range<u64>> {
+    let (start, end) = headers
+        .get(header::RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes=")?
+        .split_once('-')?;
+
+    let start = start.parse().ok()?;
+    let end = if end.is_empty() {
+        u64::MAX
+    } else {
+        end.parse::<u64>().ok()?.checked_add(1)?
+    };
+
+    (start < end).then_some(start..end)
+}
+
+/// Server files tree, used by game clients to find outdated files
+pub async fn manifest_handler(headers: HeaderMap) -> Json<ServerFolderInfo> {
+    Statistics::instance()
+        .record_manifest_fetch(install_id(&headers))
+        .await;
+
+    Json(ServerFolderInfo::clone(&FileHolder::instance().snapshot()))
+}
+
+/// Compressed game file, supports a single byte range
+pub async fn file_handler(Path(path): Path<String>, headers: HeaderMap) -> Response {
+    serve_file(FileHolder::instance(), path, &headers).await
+}
+
+async fn serve_file(holder: &FileHolder, path: String, headers: &HeaderMap) -> Response {
+    if path
+        .split('/')
+        .any(|v| v.is_empty() || v == "." || v == "..")
+    {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let (folder_path, file_name) = path.rsplit_once('/').unwrap_or(("", &path));
+
+    let tree = holder.snapshot();
+
+    // Files of a deleted folder keep their own flag, any folder on the path hides them
+    let file = file_info(&tree, folder_path, file_name)
+        .filter(|v| !v.deleted && deleted_ancestor(&tree, folder_path).is_none());
+
+    let Some(file) = file else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // Clients keep their copy until a good one is uploaded
+    if file.quarantined {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+
+    let range = requested_range(headers);
+
+    let blob = match holder.storage().get(&path, range.clone()).await {
+        Ok(v) => v,
+        Err(e) => {
+            app_log(
+                Level::Error,
+                &format!("File {path} is presented in filelist, but can't be read: {e}"),
+            )
+            .await;
+
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    };
+
+    let (size, total) = (blob.size, blob.total);
+
+    if let Some(range) = &range {
+        if range.start >= total {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{total}"))],
+            )
+                .into_response();
+        }
+    }
+
+    Statistics::instance()
+        .record_download(
+            &path,
+            size,
+            range.as_ref().is_none_or(|v| v.start == 0),
+            install_id(headers),
+        )
+        .await;
+
+    let mut response = Body::from_stream(blob.stream).into_response();
+    let response_headers = response.headers_mut();
+
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    if let Some(range) = range {
+        let end = range.start + size - 1;
+
+        if let Ok(v) = HeaderValue::from_str(&format!("bytes {}-{end}/{total}", range.start)) {
+            response_headers.insert(header::CONTENT_RANGE, v);
+        }
+
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+    use axum::body::to_bytes;
+    use futures::StreamExt;
+    use shared::storage::{BlobStorage, FsStorage, MemoryStorage};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn test_holder_with(storage: &'static dyn BlobStorage, data: Vec<u8>) -> FileHolder {
+        let dir = std::env::temp_dir().join(format!("game_client_{}", Uuid::new_v4()));
+
+        let holder = FileHolder::load(Arc::new(Store::open(&dir).unwrap()), storage).unwrap();
+
+        holder.add_file("data", "a.bin", data, 1).await.unwrap();
+
+        holder
+    }
+
+    async fn test_holder(data: Vec<u8>) -> FileHolder {
+        test_holder_with(Box::leak(Box::new(MemoryStorage::default())), data).await
+    }
+
+    async fn get(holder: &FileHolder, range: Option<&str>) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Some(v) = range {
+            headers.insert(header::RANGE, HeaderValue::from_str(v).unwrap());
+        }
+
+        serve_file(holder, "data/a.bin".to_string(), &headers).await
+    }
+
+    fn content_range(response: &Response) -> &str {
+        response.headers()[header::CONTENT_RANGE].to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn ranges_report_the_blob_size() {
+        let holder = test_holder(vec![7; 1000]).await;
+        let blob = holder.storage().get_bytes("data/a.bin").await.unwrap();
+        let total = blob.len();
+
+        let response = get(&holder, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+            blob
+        );
+
+        let response = get(&holder, Some("bytes=2-")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            content_range(&response),
+            format!("bytes 2-{}/{total}", total - 1)
+        );
+        assert_eq!(
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+            blob[2..]
+        );
+
+        let response = get(&holder, Some("bytes=0-0")).await;
+        assert_eq!(content_range(&response), format!("bytes 0-0/{total}"));
+
+        for range in [format!("bytes={total}-"), format!("bytes={}-", total + 5)] {
+            let response = get(&holder, Some(&range)).await;
+
+            assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(content_range(&response), format!("bytes */{total}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn files_of_deleted_folders_are_not_served() {
+        let holder = test_holder(vec![7; 10]).await;
+
+        for folder in ["data/sub", "data/sub/deeper"] {
+            holder
+                .add_file(folder, "a.bin", vec![7; 10], 1)
+                .await
+                .unwrap();
+        }
+
+        let status = |path: &str| {
+            let path = path.to_string();
+            let holder = &holder;
+
+            async move { serve_file(holder, path, &HeaderMap::new()).await.status() }
+        };
+
+        assert_eq!(status("data/sub/deeper/a.bin").await, StatusCode::OK);
+
+        holder.delete_folder("data", 1).await.unwrap();
+
+        for path in ["data/a.bin", "data/sub/a.bin", "data/sub/deeper/a.bin"] {
+            assert_eq!(status(path).await, StatusCode::NOT_FOUND, "{path}");
+        }
+    }
+
+    /// Stalled downloads used to hold the tree lock and block every upload
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn stalled_downloads_do_not_block_uploads() {
+        let dir = std::env::temp_dir().join(format!("game_client_{}", Uuid::new_v4()));
+
+        // Incompressible, so the blob is read in several chunks
+        let mut seed = 1u32;
+        let data: Vec<u8> = (0..1024 * 1024)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect();
+
+        let holder: &'static FileHolder = Box::leak(Box::new(
+            test_holder_with(Box::leak(Box::new(FsStorage::new(dir.join("blobs")))), data).await,
+        ));
+        let blob = holder.storage().get_bytes("data/a.bin").await.unwrap();
+
+        // Clients which stop reading after the first chunk
+        let mut downloads = vec![];
+
+        for _ in 0..64 {
+            let response = serve_file(holder, "data/a.bin".to_string(), &HeaderMap::new()).await;
+            let mut stream = response.into_body().into_data_stream();
+
+            let first = stream.next().await.unwrap().unwrap();
+            assert!(first.len() < blob.len());
+
+            downloads.push((first, stream));
+        }
+
+        let uploads: Vec<_> = (0..32u8)
+            .map(|i| {
+                tokio::spawn(async move {
+                    holder
+                        .add_file(
+                            &format!("data/{}", i % 4),
+                            &format!("{i}.bin"),
+                            vec![i; 1024],
+                            1,
+                        )
+                        .await
+                })
+            })
+            .collect();
+
+        for upload in uploads {
+            tokio::time::timeout(Duration::from_secs(10), upload)
+                .await
+                .expect("Upload is blocked by downloads")
+                .unwrap()
+                .unwrap();
+        }
+
+        assert_eq!(holder.snapshot().files_count, 33);
+        assert!(holder.snapshot().folders["data"].folders["3"]
+            .files
+            .contains_key("31.bin"));
+
+        // Downloads still finish with the whole file
+        for (first, mut stream) in downloads {
+            let mut received = first.to_vec();
+
+            while let Some(chunk) = stream.next().await {
+                received.extend_from_slice(&chunk.unwrap());
+            }
+
+            assert_eq!(received, blob);
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
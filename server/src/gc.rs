@@ -0,0 +1,259 @@
+//! Garbage collection of game files
+//!
+//! Deleted files and folders stay restorable for [`GcConfig::retention_days`], after that their
+//! entries and blobs are purged. Blobs no file of the tree refers to are removed as well.
+//!
+//! [`GcConfig::retention_days`]: crate::config::GcConfig::retention_days
+use crate::config::Config;
+use crate::file_updater::{expired, FileHolder};
+use shared::admin_panel::GcReport;
+use shared::file::{ServerFolderInfo, PREPARED_PREFIX};
+use shared::storage::blob_key;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{error, info};
+
+pub(crate) const SECS_IN_DAY: i64 = 24 * 60 * 60;
+
+/// Entries of the tree to purge
+#[derive(Default)]
+struct Plan {
+    /// (folder path, file name, size)
+    files: Vec<(String, String, u64)>,
+    /// Children go before their parents
+    folders: Vec<String>,
+    /// Keys of every file, purged ones included
+    referenced: HashSet<String>,
+}
+
+fn plan(root: &ServerFolderInfo, cutoff: i64) -> Plan {
+    fn walk(
+        path: &str,
+        folder: &ServerFolderInfo,
+        parent_expired: bool,
+        cutoff: i64,
+        plan: &mut Plan,
+    ) {
+        let folder_expired = parent_expired || expired(folder.deleted, folder.deleted_at, cutoff);
+
+        for (name, file) in &folder.files {
+            plan.referenced.insert(blob_key(path, name));
+
+            if folder_expired || expired(file.deleted, file.deleted_at, cutoff) {
+                plan.files.push((path.to_string(), name.clone(), file.size));
+            }
+        }
+
+        for (name, child) in &folder.folders {
+            let child_path = blob_key(path, name);
+
+            walk(&child_path, child, folder_expired, cutoff, plan);
+
+            if expired(child.deleted, child.deleted_at, cutoff) {
+                plan.folders.push(child_path);
+            }
+        }
+    }
+
+    let mut plan = Plan::default();
+
+    walk("", root, false, cutoff, &mut plan);
+
+    plan
+}
+
+/// Purges entries deleted before `now - retention_days` and orphan blobs, `dry_run` changes nothing
+pub async fn collect_garbage(
+    holder: &FileHolder,
+    retention_days: u32,
+    now: i64,
+    dry_run: bool,
+) -> GcReport {
+    let cutoff = now - retention_days as i64 * SECS_IN_DAY;
+    let plan = plan(&holder.snapshot(), cutoff);
+
+    let mut report = GcReport {
+        dry_run,
+        time: now,
+        ..Default::default()
+    };
+
+    for (folder_path, file_name, size) in plan.files {
+        let path = blob_key(&folder_path, &file_name);
+
+        if dry_run {
+            report.purged_files.push(path);
+            report.purged_size += size;
+
+            continue;
+        }
+
+        match holder.purge_file(&folder_path, &file_name, cutoff).await {
+            Ok(Some(size)) => {
+                report.purged_files.push(path);
+                report.purged_size += size;
+            }
+            // Restored or replaced since the plan was made
+            Ok(None) => {}
+            Err(e) => report.errors.push(format!("Can't delete blob {path}: {e}")),
+        }
+    }
+
+    for path in plan.folders {
+        if dry_run {
+            report.purged_folders.push(path);
+
+            continue;
+        }
+
+        match holder.purge_folder(&path, cutoff).await {
+            Ok(true) => report.purged_folders.push(path),
+            Ok(false) => {}
+            Err(e) => report
+                .errors
+                .push(format!("Can't purge folder {path}: {e}")),
+        }
+    }
+
+    let blobs = match holder.storage().list("").await {
+        Ok(v) => v,
+        Err(e) => {
+            report.errors.push(format!("Can't list blobs: {e}"));
+
+            return report;
+        }
+    };
+
+    for key in blobs {
+        // Staged blobs are not in the tree until the next merge
+        if plan.referenced.contains(&key) || key.starts_with(PREPARED_PREFIX) {
+            continue;
+        }
+
+        if dry_run {
+            report.orphan_blobs.push(key);
+
+            continue;
+        }
+
+        match holder.delete_orphan_blob(&key).await {
+            Ok(true) => report.orphan_blobs.push(key),
+            Ok(false) => {}
+            Err(e) => report.errors.push(format!("Can't delete blob {key}: {e}")),
+        }
+    }
+
+    report
+}
+
+pub fn report_summary(report: &GcReport) -> String {
+    format!(
+        "{}{} files ({} bytes), {} folders and {} orphan blobs, {} errors",
+        if report.dry_run {
+            "Would purge "
+        } else {
+            "Purged "
+        },
+        report.purged_files.len(),
+        report.purged_size,
+        report.purged_folders.len(),
+        report.orphan_blobs.len(),
+        report.errors.len()
+    )
+}
+
+/// Collects garbage every [`GcConfig::interval_hours`], never returns
+///
+/// [`GcConfig::interval_hours`]: crate::config::GcConfig::interval_hours
+pub async fn run_gc_loop() {
+    let config = &Config::instance().gc;
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(config.interval_hours as u64 * 60 * 60));
+
+    loop {
+        interval.tick().await;
+
+        let report = collect_garbage(
+            FileHolder::instance(),
+            config.retention_days,
+            chrono::Utc::now().timestamp(),
+            false,
+        )
+        .await;
+
+        info!("Garbage collection: {}", report_summary(&report));
+
+        for e in &report.errors {
+            error!("Garbage collection: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{Store, Table};
+    use shared::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn deleted_entries_and_orphans_are_purged_after_retention() {
+        let dir = std::env::temp_dir().join(format!("gc_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let holder =
+            FileHolder::load(store.clone(), Box::leak(Box::new(MemoryStorage::default()))).unwrap();
+
+        for (folder, name) in [("data", "a.bin"), ("data", "b.bin"), ("old/sub", "c.bin")] {
+            holder.add_file(folder, name, vec![1; 10], 1).await.unwrap();
+        }
+
+        holder.delete_file("data", "a.bin", 1).await.unwrap();
+        holder.delete_folder("old", 1).await.unwrap();
+        holder
+            .storage()
+            .put_bytes("stray.bin", vec![1])
+            .await
+            .unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+
+        // Still restorable
+        let report = collect_garbage(&holder, 30, now + 29 * SECS_IN_DAY, false).await;
+        assert!(report.purged_files.is_empty() && report.purged_folders.is_empty());
+        assert_eq!(report.orphan_blobs, vec!["stray.bin"]);
+
+        holder
+            .storage()
+            .put_bytes("stray.bin", vec![1])
+            .await
+            .unwrap();
+
+        let later = now + 31 * SECS_IN_DAY;
+
+        let mut dry_run = collect_garbage(&holder, 30, later, true).await;
+        assert_eq!(holder.snapshot().files_count, 3);
+        assert!(holder.storage().exists("stray.bin").await.unwrap());
+
+        let mut report = collect_garbage(&holder, 30, later, false).await;
+
+        for report in [&mut dry_run, &mut report] {
+            report.purged_files.sort();
+        }
+
+        assert_eq!(report.purged_files, vec!["data/a.bin", "old/sub/c.bin"]);
+        assert_eq!(report.purged_files, dry_run.purged_files);
+        assert_eq!(report.purged_folders, vec!["old"]);
+        assert_eq!(report.purged_folders, dry_run.purged_folders);
+        assert_eq!(report.purged_size, 20);
+        assert_eq!(report.orphan_blobs, vec!["stray.bin"]);
+        assert!(report.errors.is_empty());
+
+        assert_eq!(holder.storage().list("").await.unwrap(), vec!["data/b.bin"]);
+        assert_eq!(holder.snapshot().files_count, 1);
+        assert_eq!(store.keys(Table::Files), vec!["data/b.bin"]);
+        assert_eq!(store.keys(Table::Folders), vec!["data"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
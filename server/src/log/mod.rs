@@ -0,0 +1,115 @@
+use crate::telegram::Telegram;
+use shared::admin_panel::{Log, LogLevel};
+use std::fmt::Debug;
+use tracing::field::{Field, Visit};
+use tracing::log::{log, Level};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+mod storage;
+
+pub use storage::LogStorage;
+
+pub async fn app_log(level: Level, log: &str) {
+    log!(level, "{log}");
+
+    if level == Level::Error {
+        Telegram::notify(format!("Error: {log}"));
+    }
+}
+
+/// Records tracing events into [`LogStorage`]
+///
+/// Producer of the log is the innermost span name, or the event target if there is no span
+pub struct LogLayer {
+    storage: &'static LogStorage,
+}
+
+impl LogLayer {
+    pub fn new(storage: &'static LogStorage) -> Self {
+        Self { storage }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for LogLayer {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        let level = match *metadata.level() {
+            tracing::Level::TRACE | tracing::Level::DEBUG => LogLevel::Debug,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::WARN => LogLevel::Warning,
+            tracing::Level::ERROR => LogLevel::Error,
+        };
+
+        let producer = ctx
+            .event_span(event)
+            .map(|v| v.name().to_string())
+            .unwrap_or_else(|| metadata.target().to_string());
+
+        let mut visitor = LogVisitor::default();
+        event.record(&mut visitor);
+
+        self.storage.push(Log {
+            level,
+            producer,
+            log: visitor.log,
+            time: chrono::Local::now().timestamp(),
+        });
+    }
+}
+
+#[derive(Default)]
+struct LogVisitor {
+    log: String,
+}
+
+impl Visit for LogVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.log.insert_str(0, value);
+        } else if !field.name().starts_with("log.") {
+            self.log += &format!(" {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.log.insert_str(0, &format!("{value:?}"));
+        } else if !field.name().starts_with("log.") {
+            self.log += &format!(" {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn layer_records_span_as_producer() {
+        let dir = std::env::temp_dir().join(format!("log_layer_{}", uuid::Uuid::new_v4()));
+        let storage = Box::leak(Box::new(LogStorage::new(dir.clone(), 1)));
+
+        let subscriber = tracing_subscriber::registry().with(LogLayer::new(storage));
+        let mut new_logs = storage.subscribe();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("Monitor").entered();
+
+            tracing::warn!(files = 3, "Scrub finished");
+        });
+
+        let log = std::iter::from_fn(|| new_logs.try_recv().ok())
+            .find(|v| v.producer == "Monitor")
+            .unwrap();
+
+        assert_eq!(log.level, LogLevel::Warning);
+        assert_eq!(log.log, "Scrub finished files=3");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,350 @@
+use crate::config::Config;
+use shared::admin_panel::{Log, LogQuery};
+use std::collections::{HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+static INSTANCE: OnceLock<LogStorage> = OnceLock::new();
+
+const NEW_LOGS_CHANNEL_CAPACITY: usize = 1_024;
+const LOG_FILE_EXTENSION: &str = "log";
+/// `skip` and `take` come from the client, the page buffer grows past this only if it has logs
+const MAX_PREALLOCATED_LOGS: usize = 1_024;
+
+/// Server logs, stored as one RON line per log in `{database}/logs/{YYYY-MM-DD}.log`
+///
+/// Log files are rotated daily, only last `log.retention_days` files are kept
+///
+/// Logs are written by a dedicated thread, so recording never blocks the caller
+pub struct LogStorage {
+    dir: PathBuf,
+    to_writer: Sender<Log>,
+    new_logs: broadcast::Sender<Log>,
+}
+
+impl LogStorage {
+    pub fn instance<'a>() -> &'a LogStorage {
+        INSTANCE.get_or_init(|| {
+            let config = Config::instance();
+
+            Self::new(config.dirs.database.join("logs"), config.log.retention_days)
+        })
+    }
+
+    pub(super) fn new(dir: PathBuf, retention_days: usize) -> Self {
+        let (to_writer, from_storage) = std::sync::mpsc::channel();
+
+        let writer = LogWriter {
+            dir: dir.clone(),
+            retention_days,
+            file: None,
+        };
+
+        std::thread::Builder::new()
+            .name("log_writer".to_string())
+            .spawn(move || writer.run(from_storage))
+            .expect("Can't spawn log writer thread");
+
+        Self {
+            dir,
+            to_writer,
+            new_logs: broadcast::channel(NEW_LOGS_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn push(&self, log: Log) {
+        let _ = self.to_writer.send(log.clone());
+
+        // error only means that nobody is subscribed
+        let _ = self.new_logs.send(log);
+    }
+
+    /// Receiver of every log recorded after subscription
+    pub fn subscribe(&self) -> broadcast::Receiver<Log> {
+        self.new_logs.subscribe()
+    }
+
+    /// Blocking, returns (total, producers, logs)
+    pub fn query(&self, query: &LogQuery) -> (u32, Vec<String>, Vec<Log>) {
+        query_dir(&self.dir, query)
+    }
+}
+
+struct LogWriter {
+    dir: PathBuf,
+    retention_days: usize,
+    /// (day, file)
+    file: Option<(String, BufWriter<File>)>,
+}
+
+impl LogWriter {
+    fn run(mut self, from_storage: Receiver<Log>) {
+        while let Ok(log) = from_storage.recv() {
+            self.write(&log);
+
+            while let Ok(log) = from_storage.try_recv() {
+                self.write(&log);
+            }
+
+            self.flush();
+        }
+    }
+
+    // tracing is not used for errors here, because every error would produce a new log
+    fn write(&mut self, log: &Log) {
+        let day = day_of(log.time);
+
+        if self.file.as_ref().is_none_or(|v| v.0 != day) {
+            self.rotate(day);
+        }
+
+        let Some((_, file)) = &mut self.file else {
+            return;
+        };
+
+        let Ok(line) = ron::ser::to_string(log) else {
+            return;
+        };
+
+        if let Err(e) = writeln!(file, "{line}") {
+            eprintln!("Can't write log: {e}");
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some((_, file)) = &mut self.file {
+            let _ = file.flush();
+        }
+    }
+
+    fn rotate(&mut self, day: String) {
+        self.flush();
+        self.file = None;
+
+        if let Err(e) = std::fs::create_dir_all(&self.dir) {
+            eprintln!("Can't create logs dir: {e}");
+
+            return;
+        }
+
+        match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(format!("{day}.{LOG_FILE_EXTENSION}")))
+        {
+            Ok(file) => self.file = Some((day, BufWriter::new(file))),
+            Err(e) => eprintln!("Can't open log file: {e}"),
+        }
+
+        let files = log_files(&self.dir);
+
+        if files.len() > self.retention_days {
+            for (_, path) in &files[..files.len() - self.retention_days] {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+fn day_of(time: i64) -> String {
+    chrono::DateTime::from_timestamp(time, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// Returns (day, path) sorted by day
+fn log_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut res: Vec<_> = entries
+        .filter_map(|v| v.ok())
+        .map(|v| v.path())
+        .filter(|v| v.extension().is_some_and(|e| e == LOG_FILE_EXTENSION))
+        .filter_map(|v| Some((v.file_stem()?.to_str()?.to_string(), v)))
+        .collect();
+
+    res.sort();
+
+    res
+}
+
+/// Logs are returned in chronological order, `skip` and `take` are counted from the newest one
+fn query_dir(dir: &Path, query: &LogQuery) -> (u32, Vec<String>, Vec<Log>) {
+    let from_day = query.from.map(day_of);
+    let to_day = query.to.map(day_of);
+
+    // Only the newest page is kept, older matches are just counted
+    let keep = query.skip as usize + query.take as usize;
+
+    let mut producers = HashSet::new();
+    let mut logs = VecDeque::with_capacity(keep.min(MAX_PREALLOCATED_LOGS));
+    let mut total: u32 = 0;
+
+    for (day, path) in log_files(dir) {
+        if from_day.as_ref().is_some_and(|v| &day < v) || to_day.as_ref().is_some_and(|v| &day > v)
+        {
+            continue;
+        }
+
+        let Ok(file) = File::open(path) else {
+            continue;
+        };
+
+        for line in BufReader::new(file).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            // last line can be partially written
+            let Ok(log) = ron::de::from_str::<Log>(&line) else {
+                continue;
+            };
+
+            if query.from.is_some_and(|v| log.time < v) || query.to.is_some_and(|v| log.time >= v) {
+                continue;
+            }
+
+            producers.insert(log.producer.clone());
+
+            if query.matches(&log) {
+                total += 1;
+
+                if logs.len() == keep {
+                    logs.pop_front();
+                }
+
+                if keep > 0 {
+                    logs.push_back(log);
+                }
+            }
+        }
+    }
+
+    let end = logs.len().saturating_sub(query.skip as usize);
+    let start = end.saturating_sub(query.take as usize);
+
+    let mut producers: Vec<_> = producers.into_iter().collect();
+    producers.sort();
+
+    (total, producers, logs.drain(start..end).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::admin_panel::LogLevel;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn log(time: i64, level: LogLevel, producer: &str, text: &str) -> Log {
+        Log {
+            level,
+            producer: producer.to_string(),
+            log: text.to_string(),
+            time,
+        }
+    }
+
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("log_storage_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn query_filters_and_pages() {
+        let dir = test_dir();
+
+        let mut writer = LogWriter {
+            dir: dir.clone(),
+            retention_days: 30,
+            file: None,
+        };
+
+        for i in 0..10 {
+            writer.write(&log(
+                i * 100,
+                LogLevel::Info,
+                "Files",
+                &format!("Upload {i}"),
+            ));
+        }
+        writer.write(&log(DAY, LogLevel::Error, "Monitor", "Disk is FULL"));
+        writer.write(&log(2 * DAY, LogLevel::Debug, "Monitor", "tick"));
+        writer.flush();
+
+        let (total, producers, logs) = query_dir(&dir, &LogQuery::default());
+        assert_eq!(total, 11);
+        assert_eq!(producers, vec!["Files", "Monitor"]);
+        assert_eq!(logs.last().unwrap().log, "Disk is FULL");
+
+        let (total, _, logs) = query_dir(
+            &dir,
+            &LogQuery {
+                take: 3,
+                skip: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(total, 11);
+        assert_eq!(
+            logs.iter().map(|v| v.log.as_str()).collect::<Vec<_>>(),
+            vec!["Upload 6", "Upload 7", "Upload 8"]
+        );
+
+        let (total, _, logs) = query_dir(
+            &dir,
+            &LogQuery {
+                take: 5,
+                skip: 9,
+                ..Default::default()
+            },
+        );
+        assert_eq!(total, 11);
+        assert_eq!(
+            logs.iter().map(|v| v.log.as_str()).collect::<Vec<_>>(),
+            vec!["Upload 0", "Upload 1"]
+        );
+
+        let (total, _, logs) = query_dir(
+            &dir,
+            &LogQuery {
+                from: Some(DAY),
+                min_level: LogLevel::Debug,
+                producer: Some("Monitor".to_string()),
+                search: "full".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(total, 1);
+        assert_eq!(logs[0].level, LogLevel::Error);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn old_files_are_removed() {
+        let dir = test_dir();
+
+        let mut writer = LogWriter {
+            dir: dir.clone(),
+            retention_days: 2,
+            file: None,
+        };
+
+        for day in 0..4 {
+            writer.write(&log(day * DAY, LogLevel::Info, "Files", "Upload"));
+        }
+        writer.flush();
+
+        let days: Vec<_> = log_files(&dir).into_iter().map(|v| v.0).collect();
+        assert_eq!(days, vec!["1970-01-03", "1970-01-04"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
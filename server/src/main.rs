@@ -1,52 +1,190 @@
 mod admin_panel;
+mod config;
 mod db;
 mod file_updater;
+mod game_client;
+mod gc;
 mod log;
+mod media;
+mod migrations;
+mod patch_notes;
+mod prepared;
+mod scrub;
 mod statistics;
+mod store;
+mod telegram;
+mod trash;
 
 use crate::admin_panel::admin_socket_handler;
+use crate::config::{Cli, Config};
 use crate::db::Database;
 use crate::file_updater::FileHolder;
+use crate::game_client::{file_handler, manifest_handler};
+use crate::gc::run_gc_loop;
+use crate::log::{LogLayer, LogStorage};
+use crate::media::media_handler;
+use crate::patch_notes::{
+    atom_feed_handler, patch_note_handler, patch_notes_handler, raw_patch_note_handler,
+    rss_feed_handler, run_publish_scheduler,
+};
+use crate::prepared::merge_prepared;
+use crate::scrub::{recompute_legacy_sizes, run_startup_scrub};
+use crate::statistics::Statistics;
+use crate::store::Store;
+use crate::telegram::Telegram;
 use axum::routing::get;
 use axum::Router;
+use clap::Parser;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::filter::EnvFilter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load(&cli)?;
+
+    config.validate(!cli.check)?;
+
+    if cli.check {
+        println!(
+            "{}",
+            ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default())?
+        );
+
+        return Ok(());
+    }
+
+    let console_filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log.level));
+    let stored_level = config.stored_log_level();
+
+    Config::init(config);
+    let config = Config::instance();
+
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer().with_filter(console_filter))
+        .with(LogLayer::new(LogStorage::instance()).with_filter(stored_level))
         .init();
 
+    Store::init(&config.dirs.database)?;
+    merge_prepared(
+        FileHolder::instance(),
+        Store::instance(),
+        &config.dirs.database,
+    )
+    .await?;
+
     // build our application with some routes
-    let app = Router::new()
-        .route("/ws", get(admin_socket_handler))
-        // logging so we can see whats going on
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        );
+    let mut app = Router::new().route("/ws", get(admin_socket_handler));
+
+    if config.features.game_client {
+        app = app
+            .route("/manifest", get(manifest_handler))
+            .route("/files/*path", get(file_handler));
+    }
+
+    if config.features.public_patch_notes {
+        app = app
+            .route("/patch_notes", get(patch_notes_handler))
+            .route("/patch_notes/:id", get(patch_note_handler))
+            .route("/patch_notes/:id/raw", get(raw_patch_note_handler))
+            .route("/feed/atom.xml", get(atom_feed_handler))
+            .route("/feed/rss.xml", get(rss_feed_handler))
+            .route("/media/:name", get(media_handler));
+    }
+
+    // logging so we can see whats going on
+    let app = app.layer(
+        TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::default().include_headers(true)),
+    );
+
+    let mut listeners = vec![];
+    for addr in &config.listen {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Can't listen on {addr}: {e}"))?;
 
-    // run it with hyper
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
-        .await
-        .unwrap();
+        listeners.push(listener);
+    }
 
+    info!("{}", config.info());
+    info!("{}", Store::instance().info());
     info!("{}", Database::instance().info().await);
-    info!("{}", FileHolder::instance().await.info());
+    info!("{}", FileHolder::instance().info());
+    info!("{}", Statistics::instance().info().await);
+    if let Some(telegram) = Telegram::instance() {
+        info!("{}", telegram.info());
+    }
 
-    info!("listening on {}", listener.local_addr()?);
+    tokio::spawn(Statistics::instance().run_dump_loop());
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await?;
+    if config.features.publish_scheduler {
+        tokio::spawn(run_publish_scheduler());
+    }
+
+    if config.features.gc {
+        tokio::spawn(run_gc_loop());
+    }
+
+    let on_startup = config.scrub.on_startup;
+    tokio::spawn(async move {
+        // Sizes are recomputed first, the scrub would report them
+        if let Err(e) = recompute_legacy_sizes(FileHolder::instance(), Store::instance()).await {
+            error!("{e}");
+        }
+
+        if on_startup {
+            run_startup_scrub().await;
+        }
+    });
+
+    let mut servers = vec![];
+    for listener in listeners {
+        info!("listening on {}", listener.local_addr()?);
+
+        servers.push(
+            axum::serve(
+                listener,
+                app.clone()
+                    .into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .into_future(),
+        );
+    }
+
+    tokio::select! {
+        result = futures::future::try_join_all(servers) => {
+            result?;
+        }
+        _ = shutdown_signal() => info!("Shutting down"),
+    }
+
+    // Counters since the last periodic dump
+    Statistics::instance().flush().await;
 
     Ok(())
 }
+
+/// Ctrl+C, or SIGTERM from a service manager
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("Can't listen for SIGTERM");
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,72 @@
+use crate::config::Config;
+use axum::extract::Path;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use uuid::Uuid;
+
+/// Extension, content type
+const MEDIA_TYPES: [(&str, &str); 5] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+];
+
+fn media_type(name: &str) -> Option<(&'static str, &'static str)> {
+    let (_, extension) = name.rsplit_once('.')?;
+    let extension = extension.to_ascii_lowercase();
+
+    MEDIA_TYPES.into_iter().find(|v| v.0 == extension)
+}
+
+/// Saves an uploaded image under a new unique name in `dirs.media` and returns its url path
+pub async fn save_media(name: &str, data: &[u8]) -> Result<String, String> {
+    let config = Config::instance();
+
+    let Some((extension, _)) = media_type(name) else {
+        return Err(format!("{name} is not a png, jpeg, gif or webp image"));
+    };
+
+    if data.len() > config.limits.max_media_size {
+        return Err(format!(
+            "{name} is larger than {} KB",
+            config.limits.max_media_size / 1024
+        ));
+    }
+
+    let file_name = format!("{}.{extension}", Uuid::new_v4());
+
+    // The media dir is created by config validation
+    tokio::fs::write(config.dirs.media.join(&file_name), data)
+        .await
+        .map_err(|e| format!("Can't save {name}: {e}"))?;
+
+    Ok(format!("/media/{file_name}"))
+}
+
+/// `GET /media/{name}`
+pub async fn media_handler(Path(name): Path<String>) -> Response {
+    // Names are made by `save_media`, anything else can point outside of the media dir
+    let Some((_, content_type)) = media_type(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if name.starts_with('.') || name.contains(['/', '\\']) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    match tokio::fs::read(Config::instance().dirs.media.join(&name)).await {
+        Ok(data) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+                // The admin panel can be served from another origin
+                (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
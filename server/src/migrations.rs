@@ -0,0 +1,382 @@
+//! Versions of persisted documents and the migrations between them
+//!
+//! Every document is saved in a [`Versioned`] envelope. A document of an older version is backed
+//! up and upgraded by the migrations of its [`Schema`] when the server starts, a newer one is an
+//! error. Each migration needs golden fixtures in `fixtures/migrations`, see the tests.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use shared::file::ROOT_FOLDER_INFO_VERSION;
+use shared::versioned::Versioned;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Upgrades a document from version `from` to `from + 1`
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    /// Gets the upgrade time for values which were not tracked before
+    pub apply: fn(&mut Value, i64) -> anyhow::Result<()>,
+}
+
+pub struct Schema {
+    /// Name of backups and fixtures
+    pub name: &'static str,
+    pub version: u32,
+    /// Sorted by `from`, the last one upgrades to `version`
+    pub migrations: &'static [Migration],
+}
+
+/// Tables of [`crate::store::Store`], the snapshot and every journal record
+pub const STORE: Schema = Schema {
+    name: "store",
+    version: 5,
+    migrations: &[
+        Migration {
+            from: 1,
+            description: "Set publish time of patch notes published before statuses",
+            apply: set_legacy_published_at,
+        },
+        Migration {
+            from: 2,
+            description: "Set deletion time of deleted files and folders",
+            apply: set_deleted_at,
+        },
+        Migration {
+            from: 3,
+            description: "Set who deleted deleted files and folders",
+            apply: set_deleted_by,
+        },
+        Migration {
+            from: 4,
+            description:
+                "Mark file sizes for recomputing, older preparators recorded compressed sizes",
+            apply: mark_legacy_sizes,
+        },
+    ],
+};
+
+/// [`crate::store::Table::Meta`] key set while file sizes may be compressed sizes of blobs, see
+/// [`crate::scrub::recompute_legacy_sizes`]
+pub const LEGACY_SIZES_KEY: &str = "files.legacy_sizes";
+
+/// File list written by the preparator
+pub const ROOT_FOLDER: Schema = Schema {
+    name: "root_folder",
+    version: ROOT_FOLDER_INFO_VERSION,
+    migrations: &[Migration {
+        from: 1,
+        description: "Keep compressed sizes, the server recomputes them after the merge",
+        apply: keep_legacy_sizes,
+    }],
+};
+
+pub const STATISTICS: Schema = Schema {
+    name: "statistics",
+    version: 1,
+    migrations: &[],
+};
+
+#[cfg(test)]
+const SCHEMAS: &[&Schema] = &[&STORE, &ROOT_FOLDER, &STATISTICS];
+
+/// Version and data of a document, documents without the envelope are version 1
+pub fn split_envelope(value: Value) -> (u32, Value) {
+    match value {
+        Value::Object(mut map)
+            if map.len() == 2 && map.contains_key("data") && map["version"].is_u64() =>
+        {
+            let version = map["version"].as_u64().unwrap_or_default() as u32;
+
+            (version, map.remove("data").unwrap_or_default())
+        }
+        v => (1, v),
+    }
+}
+
+impl Schema {
+    pub fn envelope<T>(&self, data: T) -> Versioned<T> {
+        Versioned {
+            version: self.version,
+            data,
+        }
+    }
+
+    /// Upgrades `data` of `version` to [`Schema::version`]
+    pub fn migrate(&self, data: &mut Value, version: u32) -> anyhow::Result<()> {
+        if version > self.version {
+            return Err(anyhow::anyhow!(
+                "{} version {version} is newer than supported {}, update the server",
+                self.name,
+                self.version
+            ));
+        }
+
+        for migration in self.migrations.iter().filter(|v| v.from >= version) {
+            (migration.apply)(data, chrono::Utc::now().timestamp()).map_err(|e| {
+                anyhow::anyhow!(
+                    "{} migration from version {} failed: {e}",
+                    self.name,
+                    migration.from
+                )
+            })?;
+
+            info!(
+                "Migrated {} to version {}: {}",
+                self.name,
+                migration.from + 1,
+                migration.description
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Reads a RON document of any supported version, returns it with the version it was saved in
+    pub fn load_ron<T: DeserializeOwned + Serialize>(
+        &self,
+        text: &str,
+    ) -> anyhow::Result<(T, u32)> {
+        // Current documents are read as is, maps with non string keys have no JSON form
+        if let Ok(v) = ron::de::from_str::<Versioned<T>>(text) {
+            if v.version == self.version {
+                return Ok((v.data, v.version));
+            }
+        }
+
+        if self.version == 1 {
+            return Ok((ron::de::from_str(text)?, 1));
+        }
+
+        let (version, mut data) = match ron::de::from_str(text) {
+            Ok(v) => split_envelope(v),
+            // Structs without the envelope can't be read as values, they are read as the current
+            // type when their shape didn't change
+            Err(e) => match ron::de::from_str::<Versioned<T>>(text) {
+                Ok(v) => (v.version, serde_json::to_value(v.data)?),
+                Err(_) => (
+                    1,
+                    serde_json::to_value(ron::de::from_str::<T>(text).map_err(|_| e)?)?,
+                ),
+            },
+        };
+
+        self.migrate(&mut data, version)?;
+
+        Ok((serde_json::from_value(data)?, version))
+    }
+
+    /// Like [`Schema::load_ron`], the file is backed up when it is older than the current version
+    pub fn load_ron_file<T: DeserializeOwned + Serialize>(
+        &self,
+        path: &Path,
+    ) -> anyhow::Result<Option<T>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let (data, version) = self.load_ron(&text)?;
+
+        if version < self.version {
+            let dir = path.parent().unwrap_or(Path::new("."));
+
+            backup(dir, self.name, version, &[path.to_path_buf()])?;
+        }
+
+        Ok(Some(data))
+    }
+
+    pub fn to_ron<T: Serialize>(&self, data: &T) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string(&self.envelope(data))?)
+    }
+}
+
+/// Copies existing `files` to `{dir}/backups/{name}-v{version}-{time}/` before they are migrated
+pub fn backup(dir: &Path, name: &str, version: u32, files: &[PathBuf]) -> anyhow::Result<PathBuf> {
+    let backup_dir = dir.join("backups").join(format!(
+        "{name}-v{version}-{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f")
+    ));
+
+    std::fs::create_dir_all(&backup_dir)?;
+
+    for file in files.iter().filter(|v| v.exists()) {
+        let Some(file_name) = file.file_name() else {
+            continue;
+        };
+
+        std::fs::copy(file, backup_dir.join(file_name))
+            .map_err(|e| anyhow::anyhow!("Can't back up {file:?}: {e}"))?;
+    }
+
+    info!("Backed up {name} version {version} to {backup_dir:?}");
+
+    Ok(backup_dir)
+}
+
+/// Patch notes without a status were live, public endpoints used `created_at` as their publish time
+fn set_legacy_published_at(tables: &mut Value, _: i64) -> anyhow::Result<()> {
+    let Some(patch_notes) = tables.get_mut("PatchNotes").and_then(Value::as_object_mut) else {
+        return Ok(());
+    };
+
+    for patch_note in patch_notes.values_mut() {
+        let Some(patch_note) = patch_note.as_object_mut() else {
+            return Err(anyhow::anyhow!("Patch note is not an object"));
+        };
+
+        let published = patch_note.get("status").and_then(Value::as_str) == Some("Published");
+
+        if published && patch_note.get("published_at").is_none_or(Value::is_null) {
+            let created_at = patch_note.get("created_at").cloned().unwrap_or(0.into());
+
+            patch_note.insert("published_at".to_string(), created_at);
+        }
+    }
+
+    Ok(())
+}
+
+/// Entries deleted before the deletion time was tracked are kept a full GC retention period
+fn set_deleted_at(tables: &mut Value, now: i64) -> anyhow::Result<()> {
+    for table in ["Files", "Folders"] {
+        let Some(rows) = tables.get_mut(table).and_then(Value::as_object_mut) else {
+            continue;
+        };
+
+        for row in rows.values_mut() {
+            let Some(row) = row.as_object_mut() else {
+                return Err(anyhow::anyhow!("{table} row is not an object"));
+            };
+
+            let deleted = row.get("deleted").and_then(Value::as_bool) == Some(true);
+
+            if deleted && row.get("deleted_at").is_none_or(Value::is_null) {
+                row.insert("deleted_at".to_string(), now.into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Deleting an entry set its `updated_by`, so it is the admin who deleted it
+fn set_deleted_by(tables: &mut Value, _: i64) -> anyhow::Result<()> {
+    for table in ["Files", "Folders"] {
+        let Some(rows) = tables.get_mut(table).and_then(Value::as_object_mut) else {
+            continue;
+        };
+
+        for row in rows.values_mut() {
+            let Some(row) = row.as_object_mut() else {
+                return Err(anyhow::anyhow!("{table} row is not an object"));
+            };
+
+            let deleted = row.get("deleted").and_then(Value::as_bool) == Some(true);
+
+            if deleted && row.get("deleted_by").is_none_or(Value::is_null) {
+                let updated_by = row.get("updated_by").cloned().unwrap_or(0.into());
+
+                row.insert("deleted_by".to_string(), updated_by);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sizes of the files can't be read from the tables, they are recomputed from blobs on start
+fn mark_legacy_sizes(tables: &mut Value, _: i64) -> anyhow::Result<()> {
+    let has_files = tables
+        .get("Files")
+        .and_then(Value::as_object)
+        .is_some_and(|v| !v.is_empty());
+
+    if !has_files {
+        return Ok(());
+    }
+
+    let Some(meta) = tables.as_object_mut().map(|v| {
+        v.entry("Meta")
+            .or_insert_with(|| Value::Object(Default::default()))
+    }) else {
+        return Err(anyhow::anyhow!("Tables are not an object"));
+    };
+
+    let Some(meta) = meta.as_object_mut() else {
+        return Err(anyhow::anyhow!("Meta is not an object"));
+    };
+
+    meta.insert(LEGACY_SIZES_KEY.to_string(), true.into());
+
+    Ok(())
+}
+
+/// The list is unchanged, [`crate::prepared::merge_prepared`] marks sizes of version 1 lists
+fn keep_legacy_sizes(_: &mut Value, _: i64) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::file::ServerFolderInfo;
+
+    /// Upgrade time of the fixtures
+    const FIXTURE_TIME: i64 = 1_750_000_000;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/migrations")
+            .join(name);
+
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("No fixture {path:?}: {e}"))
+    }
+
+    /// `{name}_v{from}.json` upgraded by the migration has to equal `{name}_v{from + 1}.json`
+    #[test]
+    fn migrations_match_golden_fixtures() {
+        for schema in SCHEMAS {
+            let first = schema.version - schema.migrations.len() as u32;
+
+            for (i, migration) in schema.migrations.iter().enumerate() {
+                assert_eq!(migration.from, first + i as u32, "{} gap", schema.name);
+
+                let mut data: Value = serde_json::from_str(&fixture(&format!(
+                    "{}_v{}.json",
+                    schema.name, migration.from
+                )))
+                .unwrap();
+                let expected: Value = serde_json::from_str(&fixture(&format!(
+                    "{}_v{}.json",
+                    schema.name,
+                    migration.from + 1
+                )))
+                .unwrap();
+
+                (migration.apply)(&mut data, FIXTURE_TIME).unwrap();
+
+                assert_eq!(data, expected, "{} from {}", schema.name, migration.from);
+            }
+        }
+    }
+
+    #[test]
+    fn documents_with_and_without_envelope_are_loaded() {
+        let legacy = fixture("root_folder_v1.ron");
+
+        let (root, version) = ROOT_FOLDER.load_ron::<ServerFolderInfo>(&legacy).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(root.folders["data"].files["a.bin"].hash, "0123");
+
+        let text = ROOT_FOLDER.to_ron(&root).unwrap();
+        let (root, version) = ROOT_FOLDER.load_ron::<ServerFolderInfo>(&text).unwrap();
+        assert_eq!(version, ROOT_FOLDER.version);
+        assert_eq!(root.files_count, 1);
+
+        let newer = format!("(version: {}, data: {legacy})", STORE.version + 1);
+        assert!(STORE.load_ron::<Value>(&newer).is_err());
+    }
+}
@@ -0,0 +1,385 @@
+use crate::config::Config;
+use crate::db::Database;
+use crate::telegram::Telegram;
+use axum::extract::{Path, Query};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use shared::admin_panel::{AuditAction, Locale, PatchNote, PatchNoteStatus};
+use shared::easy_mark::to_html_with_base;
+use shared::feed::{PatchNotesPage, PublishedPatchNote};
+use std::time::Duration;
+use tracing::{error, info};
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+
+const DEFAULT_PAGE_SIZE: u32 = 10;
+const MAX_PAGE_SIZE: u32 = 100;
+/// Patch notes count in Atom and RSS feeds
+const FEED_SIZE: u32 = 20;
+
+/// Address of audit records made by the server itself
+pub static SERVER_ADDR: &str = "server";
+
+pub fn published_notification(id: u32, title: &str) -> String {
+    if title.is_empty() {
+        format!("Patch note #{id} published")
+    } else {
+        format!("Patch note #{id} published: {title}")
+    }
+}
+
+/// Publishes scheduled patch notes every [`SCHEDULER_INTERVAL`], never returns
+pub async fn run_publish_scheduler() {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let published = match Database::instance()
+            .publish_scheduled_patch_notes(chrono::Utc::now().timestamp())
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Can't publish scheduled patch notes: {e}");
+
+                continue;
+            }
+        };
+
+        for patch_note in published {
+            info!("Scheduled patch note {} published", patch_note.id);
+
+            // Failures are logged by the commit
+            let _ = Database::instance()
+                .add_audit_record(
+                    0,
+                    SERVER_ADDR.to_string(),
+                    AuditAction::ChangePatchNoteStatus,
+                    patch_note.id.to_string(),
+                    None,
+                    ron::ser::to_string(&patch_note).ok(),
+                )
+                .await;
+
+            Telegram::notify(published_notification(patch_note.id, &patch_note.title));
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    take: Option<u32>,
+    skip: Option<u32>,
+    lang: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LocaleQuery {
+    lang: Option<String>,
+}
+
+/// Locale from `lang` query parameter, `Accept-Language` header or the default one
+fn requested_locale(lang: Option<&str>, headers: &HeaderMap) -> Locale {
+    if let Some(locale) = lang.and_then(Locale::from_code) {
+        return locale;
+    }
+
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| {
+            v.split(',')
+                .filter_map(|v| v.split(';').next())
+                .find_map(Locale::from_code)
+        })
+        .unwrap_or_default()
+}
+
+fn published(patch_note: PatchNote, locale: Locale) -> PublishedPatchNote {
+    let (locale, data) = patch_note.localized(locale);
+
+    PublishedPatchNote {
+        id: patch_note.id,
+        title: patch_note.title.clone(),
+        published_at: patch_note.published_at.unwrap_or(patch_note.created_at),
+        updated_at: patch_note.updated_at,
+        locale,
+        data: data.to_string(),
+    }
+}
+
+async fn published_patch_note(id: u32, locale: Locale) -> Option<PublishedPatchNote> {
+    Database::instance()
+        .patch_note(id)
+        .await
+        .filter(|v| v.status == PatchNoteStatus::Published && !v.deleted)
+        .map(|v| published(v, locale))
+}
+
+async fn feed_patch_notes(locale: Locale) -> Vec<PublishedPatchNote> {
+    let (_, patch_notes) = Database::instance()
+        .published_patch_notes(FEED_SIZE, 0)
+        .await;
+
+    patch_notes
+        .into_iter()
+        .map(|v| published(v, locale))
+        .collect()
+}
+
+/// `GET /patch_notes?take=10&skip=0&lang=en`
+pub async fn patch_notes_handler(
+    Query(query): Query<PageQuery>,
+    headers: HeaderMap,
+) -> Json<PatchNotesPage> {
+    let take = query.take.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let skip = query.skip.unwrap_or(0);
+    let locale = requested_locale(query.lang.as_deref(), &headers);
+
+    let (total, patch_notes) = Database::instance().published_patch_notes(take, skip).await;
+
+    Json(PatchNotesPage {
+        total,
+        take,
+        skip,
+        patch_notes: patch_notes
+            .into_iter()
+            .map(|v| published(v, locale))
+            .collect(),
+    })
+}
+
+/// `GET /patch_notes/{id}?lang=en`
+pub async fn patch_note_handler(
+    Path(id): Path<u32>,
+    Query(query): Query<LocaleQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let locale = requested_locale(query.lang.as_deref(), &headers);
+
+    match published_patch_note(id, locale).await {
+        Some(v) => Json(v).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// `GET /patch_notes/{id}/raw?lang=en`, EasyMark source
+pub async fn raw_patch_note_handler(
+    Path(id): Path<u32>,
+    Query(query): Query<LocaleQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let locale = requested_locale(query.lang.as_deref(), &headers);
+
+    match published_patch_note(id, locale).await {
+        Some(v) => (
+            [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+                (header::CONTENT_LANGUAGE, v.locale.code()),
+            ],
+            v.data,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// `GET /feed/atom.xml?lang=en`
+pub async fn atom_feed_handler(Query(query): Query<LocaleQuery>, headers: HeaderMap) -> Response {
+    let patch_notes = feed_patch_notes(requested_locale(query.lang.as_deref(), &headers)).await;
+
+    (
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        atom_feed(&public_url(), &patch_notes),
+    )
+        .into_response()
+}
+
+/// `GET /feed/rss.xml?lang=en`
+pub async fn rss_feed_handler(Query(query): Query<LocaleQuery>, headers: HeaderMap) -> Response {
+    let patch_notes = feed_patch_notes(requested_locale(query.lang.as_deref(), &headers)).await;
+
+    (
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        rss_feed(&public_url(), &patch_notes),
+    )
+        .into_response()
+}
+
+/// Base url for feed links, links are relative to the server root without `public_url`
+///
+/// The Host header is not used, it is set by the client and would end up in cached feeds
+fn public_url() -> String {
+    Config::instance()
+        .public_url
+        .as_deref()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn note_title(patch_note: &PublishedPatchNote) -> String {
+    if patch_note.title.is_empty() {
+        format!("Patch note #{}", patch_note.id)
+    } else {
+        patch_note.title.clone()
+    }
+}
+
+fn rfc3339(time: i64) -> String {
+    chrono::DateTime::from_timestamp(time, 0)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            _ => res.push(c),
+        }
+    }
+
+    res
+}
+
+fn atom_feed(base_url: &str, patch_notes: &[PublishedPatchNote]) -> String {
+    let updated = patch_notes
+        .iter()
+        .map(|v| v.updated_at.max(v.published_at))
+        .max()
+        .unwrap_or_default();
+
+    let mut res = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <title>Patch notes</title>\n\
+         <id>{base_url}/feed/atom.xml</id>\n\
+         <link rel=\"self\" href=\"{base_url}/feed/atom.xml\"/>\n\
+         <updated>{}</updated>\n",
+        rfc3339(updated)
+    );
+
+    for patch_note in patch_notes {
+        let url = format!("{base_url}/patch_notes/{}", patch_note.id);
+
+        res += &format!(
+            "<entry xml:lang=\"{}\">\n\
+             <title>{}</title>\n\
+             <id>{url}</id>\n\
+             <link href=\"{url}/raw\"/>\n\
+             <published>{}</published>\n\
+             <updated>{}</updated>\n\
+             <content type=\"html\">{}</content>\n\
+             </entry>\n",
+            patch_note.locale.code(),
+            escape_xml(&note_title(patch_note)),
+            rfc3339(patch_note.published_at),
+            rfc3339(patch_note.updated_at.max(patch_note.published_at)),
+            escape_xml(&to_html_with_base(&patch_note.data, base_url)),
+        );
+    }
+
+    res + "</feed>\n"
+}
+
+fn rss_feed(base_url: &str, patch_notes: &[PublishedPatchNote]) -> String {
+    let mut res = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n\
+         <title>Patch notes</title>\n\
+         <link>{base_url}/patch_notes</link>\n\
+         <description>Patch notes</description>\n"
+    );
+
+    for patch_note in patch_notes {
+        let url = format!("{base_url}/patch_notes/{}", patch_note.id);
+
+        res += &format!(
+            "<item>\n\
+             <title>{}</title>\n\
+             <link>{url}/raw</link>\n\
+             <guid isPermaLink=\"false\">{url}</guid>\n\
+             <pubDate>{}</pubDate>\n\
+             <description>{}</description>\n\
+             </item>\n",
+            escape_xml(&note_title(patch_note)),
+            chrono::DateTime::from_timestamp(patch_note.published_at, 0)
+                .unwrap_or_default()
+                .to_rfc2822(),
+            escape_xml(&to_html_with_base(&patch_note.data, base_url)),
+        );
+    }
+
+    res + "</channel>\n</rss>\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feeds_are_escaped() {
+        let patch_notes = vec![PublishedPatchNote {
+            id: 3,
+            title: "Fixes & <balance>".to_string(),
+            published_at: 1_700_000_000,
+            updated_at: 1_700_000_100,
+            locale: Locale::En,
+            data: "# Header\n- `a < b`\n![map](/media/map.png)".to_string(),
+        }];
+
+        let atom = atom_feed("https://example.com", &patch_notes);
+
+        assert!(atom.contains("<entry xml:lang=\"en\">"));
+        assert!(atom.contains("<title>Fixes &amp; &lt;balance&gt;</title>"));
+        assert!(atom.contains("<id>https://example.com/patch_notes/3</id>"));
+        assert!(atom.contains("<updated>2023-11-14T22:15:00+00:00</updated>"));
+        assert!(atom
+            .contains("&lt;li&gt;&lt;code&gt;a &amp;lt; b&lt;/code&gt;&lt;/li&gt;\n&lt;/ul&gt;\n"));
+        assert!(atom.contains("&lt;img src=&quot;https://example.com/media/map.png&quot;"));
+
+        let rss = rss_feed("https://example.com", &patch_notes);
+
+        assert!(rss.contains("<link>https://example.com/patch_notes/3/raw</link>"));
+        assert!(rss.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+        assert!(rss.contains("&lt;img src=&quot;https://example.com/media/map.png&quot;"));
+    }
+
+    #[test]
+    fn locale_falls_back() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_LANGUAGE,
+            "fr-FR, de-AT;q=0.8".parse().unwrap(),
+        );
+
+        assert_eq!(requested_locale(None, &headers), Locale::De);
+        assert_eq!(requested_locale(Some("en"), &headers), Locale::En);
+        assert_eq!(requested_locale(None, &HeaderMap::new()), Locale::Ru);
+
+        let patch_note = PatchNote {
+            data: "Russian".to_string(),
+            translations: [
+                (Locale::En, "English".to_string()),
+                (Locale::De, " ".to_string()),
+            ]
+            .into(),
+            ..Default::default()
+        };
+
+        let de = published(patch_note.clone(), Locale::De);
+        assert_eq!((de.locale, de.data.as_str()), (Locale::En, "English"));
+        assert_eq!(patch_note.missing_locales(), vec![Locale::De]);
+    }
+}
@@ -0,0 +1,337 @@
+//! Merge of the file list written by the preparator
+//!
+//! The list describes the source folder, while admins change the tree in the store. Only files
+//! which changed between the last merged list and the new one are applied, so uploads, moves,
+//! deletions and quarantine done by admins are kept for untouched files. Blobs of changed files
+//! are staged under [`PREPARED_PREFIX`] and moved to their keys here.
+use crate::file_updater::FileHolder;
+use crate::migrations::{LEGACY_SIZES_KEY, ROOT_FOLDER};
+use crate::store::{import_mark_key, Store, Table, Transaction};
+use shared::file::{ServerFileInfo, ServerFolderInfo, PREPARED_PREFIX, ROOT_FOLDER_INFO_FILE_NAME};
+use shared::storage::blob_key;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::task::spawn_blocking;
+use tracing::{error, info};
+
+/// [`Table::Meta`] key of path -> hash of the files in the last merged list
+const MERGED_FILES_KEY: &str = "prepared.files";
+
+/// Files of the list which are not deleted, files of a vanished folder stay marked as they were
+fn listed_files(root: &ServerFolderInfo) -> BTreeMap<String, ServerFileInfo> {
+    fn walk(path: &str, folder: &ServerFolderInfo, out: &mut BTreeMap<String, ServerFileInfo>) {
+        for (name, file) in folder.files.iter().filter(|(_, v)| !v.deleted) {
+            out.insert(blob_key(path, name), file.clone());
+        }
+
+        for (name, child) in folder.folders.iter().filter(|(_, v)| !v.deleted) {
+            walk(&blob_key(path, name), child, out);
+        }
+    }
+
+    let mut files = BTreeMap::new();
+
+    walk("", root, &mut files);
+
+    files
+}
+
+async fn commit(store: &Arc<Store>, tx: Transaction) -> anyhow::Result<()> {
+    let store = store.clone();
+
+    spawn_blocking(move || store.commit(tx)).await?
+}
+
+/// Merges [`ROOT_FOLDER_INFO_FILE_NAME`] from `dir` when it changed since the last merge
+///
+/// A failed file leaves the merge unfinished, it is repeated on the next start
+pub async fn merge_prepared(
+    holder: &FileHolder,
+    store: Arc<Store>,
+    dir: &Path,
+) -> anyhow::Result<()> {
+    let path = dir.join(ROOT_FOLDER_INFO_FILE_NAME);
+
+    let text = match std::fs::read_to_string(&path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mark_key = import_mark_key(ROOT_FOLDER_INFO_FILE_NAME);
+    let hash = format!("{:016x}", seahash::hash(text.as_bytes()));
+
+    let (root, version) = ROOT_FOLDER
+        .load_ron::<ServerFolderInfo>(&text)
+        .map_err(|e| anyhow::anyhow!("Can't read {path:?}: {e}"))?;
+
+    let listed = listed_files(&root);
+    let hashes: BTreeMap<&str, &str> = listed
+        .iter()
+        .map(|(path, file)| (path.as_str(), file.hash.as_str()))
+        .collect();
+
+    let merged: Option<BTreeMap<String, String>> = store.get(Table::Meta, MERGED_FILES_KEY)?;
+
+    if store.get::<String>(Table::Meta, &mark_key)?.as_ref() == Some(&hash) {
+        // Imported whole by an older server, the list is the base of the next merge
+        if merged.is_none() {
+            let mut tx = Transaction::default();
+            tx.put(Table::Meta, MERGED_FILES_KEY, &hashes);
+
+            commit(&store, tx).await?;
+        }
+
+        return Ok(());
+    }
+
+    let merged = merged.unwrap_or_default();
+
+    let mut errors = vec![];
+    let mut changed = 0;
+    let mut deleted = 0;
+
+    for (path, file) in &listed {
+        if merged.get(path) == Some(&file.hash) {
+            continue;
+        }
+
+        match holder.merge_prepared_file(path, file).await {
+            Ok(true) => changed += 1,
+            Ok(false) => errors.push(format!("Can't merge {path}")),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    for (path, hash) in &merged {
+        if listed.contains_key(path) {
+            continue;
+        }
+
+        match holder.delete_prepared_file(path, hash).await {
+            Ok(true) => deleted += 1,
+            Ok(false) => {}
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    if !errors.is_empty() {
+        for e in &errors {
+            error!("{e}");
+        }
+
+        return Err(anyhow::anyhow!(
+            "Can't merge {path:?}, {} files failed",
+            errors.len()
+        ));
+    }
+
+    let mut tx = Transaction::default();
+    tx.put(Table::Meta, MERGED_FILES_KEY, &hashes);
+    tx.put(Table::Meta, mark_key, &hash);
+
+    // Version 1 lists recorded compressed sizes
+    if version == 1 && changed > 0 {
+        tx.put(Table::Meta, LEGACY_SIZES_KEY, &true);
+    }
+
+    commit(&store, tx).await?;
+
+    // Staged blobs of unchanged files are not needed either
+    let storage = holder.storage();
+
+    for key in storage.list(PREPARED_PREFIX).await? {
+        if let Err(e) = storage.delete(&key).await {
+            error!("Can't delete staged blob {key}: {e}");
+        }
+    }
+
+    info!("Merged {path:?}: {changed} files changed, {deleted} deleted");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::file::{compress_in_mem, ROOT_FOLDER_INFO_VERSION};
+    use shared::storage::{BlobStorage, MemoryStorage};
+    use shared::versioned::Versioned;
+
+    /// Writes the list like the preparator does, `vanished` folders are marked deleted as a whole
+    async fn prepare(
+        storage: &dyn BlobStorage,
+        dir: &Path,
+        files: &[(&str, &[u8])],
+        vanished: &[&str],
+    ) {
+        let mut root = ServerFolderInfo::default();
+
+        for (path, data) in files {
+            let (folder_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+            let mut folder = &mut root;
+            for name in folder_path.split('/').filter(|v| !v.is_empty()) {
+                folder = folder.folders.entry(name.to_string()).or_default();
+            }
+
+            folder.files.insert(
+                name.to_string(),
+                ServerFileInfo {
+                    hash: shared::file::hash_of(data),
+                    size: data.len() as u64,
+                    created_at: 1,
+                    ..Default::default()
+                },
+            );
+
+            let mut compressed = vec![];
+            compress_in_mem(data, &mut compressed).unwrap();
+            storage
+                .put_bytes(&format!("{PREPARED_PREFIX}{path}"), compressed)
+                .await
+                .unwrap();
+        }
+
+        for path in vanished {
+            let mut folder = &mut root;
+            for name in path.split('/') {
+                folder = folder.folders.get_mut(name).unwrap();
+            }
+
+            folder.deleted = true;
+        }
+
+        root.calc_size();
+
+        let document = Versioned {
+            version: ROOT_FOLDER_INFO_VERSION,
+            data: root,
+        };
+
+        std::fs::write(
+            dir.join(ROOT_FOLDER_INFO_FILE_NAME),
+            ron::ser::to_string(&document).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn legacy_lists_mark_sizes() {
+        let dir = std::env::temp_dir().join(format!("prepared_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let storage: &'static dyn BlobStorage = Box::leak(Box::new(MemoryStorage::default()));
+        let holder = FileHolder::load(store.clone(), storage).unwrap();
+
+        prepare(storage, &dir, &[("data/a.bin", b"a")], &[]).await;
+        merge_prepared(&holder, store.clone(), &dir).await.unwrap();
+
+        assert_eq!(
+            store.get::<bool>(Table::Meta, LEGACY_SIZES_KEY).unwrap(),
+            None
+        );
+
+        // Version 1 lists were written without the envelope
+        prepare(storage, &dir, &[("data/a.bin", b"b")], &[]).await;
+        let path = dir.join(ROOT_FOLDER_INFO_FILE_NAME);
+        let document: Versioned<ServerFolderInfo> =
+            ron::de::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::write(&path, ron::ser::to_string(&document.data).unwrap()).unwrap();
+
+        merge_prepared(&holder, store.clone(), &dir).await.unwrap();
+
+        assert_eq!(
+            store.get::<bool>(Table::Meta, LEGACY_SIZES_KEY).unwrap(),
+            Some(true)
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn admin_changes_survive_new_lists() {
+        let dir = std::env::temp_dir().join(format!("prepared_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let storage: &'static dyn BlobStorage = Box::leak(Box::new(MemoryStorage::default()));
+        let holder = FileHolder::load(store.clone(), storage).unwrap();
+
+        prepare(
+            storage,
+            &dir,
+            &[("data/a.bin", b"a"), ("data/b.bin", b"b")],
+            &[],
+        )
+        .await;
+        merge_prepared(&holder, store.clone(), &dir).await.unwrap();
+
+        // Admin work which lives only in the store
+        holder
+            .add_file("uploads", "u.bin", vec![1; 10], 2)
+            .await
+            .unwrap();
+        holder
+            .move_file("data/b.bin", "data/moved.bin", 2)
+            .await
+            .unwrap();
+
+        prepare(
+            storage,
+            &dir,
+            &[("data/b.bin", b"b"), ("data/c.bin", b"c"), ("x.bin", b"x")],
+            &[],
+        )
+        .await;
+        merge_prepared(&holder, store.clone(), &dir).await.unwrap();
+
+        let tree = holder.snapshot();
+        let data = &tree.folders["data"];
+
+        assert!(data.files["a.bin"].deleted);
+        assert_eq!(data.files["a.bin"].deleted_by, Some(0));
+        assert!(!data.files.contains_key("b.bin"));
+        assert!(data.files.contains_key("moved.bin"));
+        assert_eq!(data.files["c.bin"].created_at, 1);
+        assert!(tree.folders["uploads"].files.contains_key("u.bin"));
+        assert!(tree.files.contains_key("x.bin"));
+
+        assert_eq!(
+            storage.list("").await.unwrap(),
+            vec![
+                "data/a.bin",
+                "data/c.bin",
+                "data/moved.bin",
+                "uploads/u.bin",
+                "x.bin"
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn files_of_vanished_folders_are_deleted() {
+        let dir = std::env::temp_dir().join(format!("prepared_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let storage: &'static dyn BlobStorage = Box::leak(Box::new(MemoryStorage::default()));
+        let holder = FileHolder::load(store.clone(), storage).unwrap();
+
+        let files: &[(&str, &[u8])] = &[("a.bin", b"a"), ("old/sub/c.bin", b"c")];
+
+        prepare(storage, &dir, files, &[]).await;
+        merge_prepared(&holder, store.clone(), &dir).await.unwrap();
+
+        // The preparator marks only the folder, its files keep their flags
+        prepare(storage, &dir, files, &["old"]).await;
+        merge_prepared(&holder, store.clone(), &dir).await.unwrap();
+
+        let tree = holder.snapshot();
+        let c = &tree.folders["old"].folders["sub"].files["c.bin"];
+
+        assert!(c.deleted);
+        assert_eq!(c.deleted_by, Some(0));
+        assert!(!tree.files["a.bin"].deleted);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
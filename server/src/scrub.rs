@@ -0,0 +1,383 @@
+//! Integrity scrub of game files
+//!
+//! Every served file must have a blob which decompresses to its recorded size and hash. Broken
+//! files can be quarantined, the game client endpoint stops serving them until a good copy is
+//! uploaded or a later scrub finds them intact.
+use crate::config::Config;
+use crate::file_updater::FileHolder;
+use crate::migrations::LEGACY_SIZES_KEY;
+use crate::store::{Store, Table, Transaction};
+use futures::StreamExt;
+use shared::admin_panel::{BrokenFile, ScrubReport};
+use shared::file::{DecompressedHasher, ServerFileInfo, ServerFolderInfo};
+use shared::storage::{blob_key, BlobStorage};
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use tokio::task::spawn_blocking;
+use tracing::{error, info};
+
+/// Blobs read and decompressed at once
+const PARALLEL_CHECKS: usize = 4;
+
+static LAST_REPORT: Mutex<Option<ScrubReport>> = Mutex::new(None);
+
+/// Report of the last finished scrub
+pub fn last_report() -> Option<ScrubReport> {
+    LAST_REPORT.lock().unwrap().clone()
+}
+
+/// (folder path, file name, file) of files, deleted ones and files of deleted folders only with
+/// `with_deleted`
+fn files_of(root: &ServerFolderInfo, with_deleted: bool) -> Vec<(String, String, ServerFileInfo)> {
+    fn walk(
+        path: &str,
+        folder: &ServerFolderInfo,
+        with_deleted: bool,
+        out: &mut Vec<(String, String, ServerFileInfo)>,
+    ) {
+        for (name, file) in folder.files.iter() {
+            if with_deleted || !file.deleted {
+                out.push((path.to_string(), name.clone(), file.clone()));
+            }
+        }
+
+        for (name, child) in folder.folders.iter() {
+            if with_deleted || !child.deleted {
+                walk(&blob_key(path, name), child, with_deleted, out);
+            }
+        }
+    }
+
+    let mut out = vec![];
+
+    walk("", root, with_deleted, &mut out);
+
+    out
+}
+
+/// Decompressed size and hash of the blob at `key`
+async fn read_blob(storage: &dyn BlobStorage, key: &str) -> Result<(u64, String), String> {
+    let mut blob = storage.get(key, None).await.map_err(|e| match e.kind() {
+        ErrorKind::NotFound => "Blob is missing".to_string(),
+        _ => format!("Can't read blob: {e}"),
+    })?;
+
+    let mut hasher = DecompressedHasher::default();
+
+    while let Some(chunk) = blob.stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Can't read blob: {e}"))?;
+
+        hasher = spawn_blocking(move || hasher.update(&chunk).map(|_| hasher))
+            .await
+            .map_err(|e| format!("Can't decompress blob: {e}"))?
+            .map_err(|e| format!("Can't decompress blob: {e}"))?;
+    }
+
+    hasher
+        .finish()
+        .map_err(|e| format!("Can't decompress blob: {e}"))
+}
+
+/// Describes what is wrong with the blob of `file`
+async fn check_file(
+    storage: &dyn BlobStorage,
+    key: &str,
+    file: &ServerFileInfo,
+) -> Result<(), String> {
+    let (size, hash) = read_blob(storage, key).await?;
+
+    if file.size != size {
+        return Err(format!(
+            "Size is {size} bytes instead of {} bytes",
+            file.size
+        ));
+    }
+
+    if hash != file.hash {
+        return Err(format!("Hash is {hash} instead of {}", file.hash));
+    }
+
+    Ok(())
+}
+
+/// Checks every served file, `quarantine` stops serving broken ones
+///
+/// Quarantined files which are intact now are released either way
+pub async fn scrub(holder: &FileHolder, quarantine: bool) -> ScrubReport {
+    let files = files_of(&holder.snapshot(), false);
+
+    let mut report = ScrubReport {
+        time: chrono::Utc::now().timestamp(),
+        quarantine,
+        ..Default::default()
+    };
+
+    let mut results = futures::stream::iter(files)
+        .map(|(folder_path, file_name, file)| async move {
+            let key = blob_key(&folder_path, &file_name);
+            let result = check_file(holder.storage(), &key, &file).await;
+
+            (folder_path, file_name, file, result)
+        })
+        .buffer_unordered(PARALLEL_CHECKS);
+
+    while let Some((folder_path, file_name, file, result)) = results.next().await {
+        let path = blob_key(&folder_path, &file_name);
+
+        report.checked += 1;
+        report.checked_size += file.size;
+
+        match result {
+            Ok(()) => {
+                if file.quarantined {
+                    match holder
+                        .set_quarantined(&folder_path, &file_name, &file.hash, false)
+                        .await
+                    {
+                        Ok(true) => report.released.push(path),
+                        Ok(false) => {}
+                        Err(e) => error!("Can't release {path}: {e}"),
+                    }
+                }
+            }
+            Err(problem) => {
+                if quarantine {
+                    if let Err(e) = holder
+                        .set_quarantined(&folder_path, &file_name, &file.hash, true)
+                        .await
+                    {
+                        error!("Can't quarantine {path}: {e}");
+                    }
+                }
+
+                report.broken.push(BrokenFile { path, problem });
+            }
+        }
+    }
+
+    report.broken.sort_by(|a, b| a.path.cmp(&b.path));
+    report.released.sort();
+
+    *LAST_REPORT.lock().unwrap() = Some(report.clone());
+
+    report
+}
+
+pub fn scrub_summary(report: &ScrubReport) -> String {
+    format!(
+        "checked {} files ({} bytes), {} broken{}, {} released from quarantine",
+        report.checked,
+        report.checked_size,
+        report.broken.len(),
+        if report.quarantine && !report.broken.is_empty() {
+            " and quarantined"
+        } else {
+            ""
+        },
+        report.released.len()
+    )
+}
+
+/// Scrubs files once with [`ScrubConfig::quarantine`] and logs broken ones
+///
+/// [`ScrubConfig::quarantine`]: crate::config::ScrubConfig::quarantine
+/// Sets sizes of files to their decompressed sizes while [`LEGACY_SIZES_KEY`] is set
+///
+/// Older preparators recorded compressed sizes. Files whose blob is missing or doesn't match
+/// their hash are left to the scrub
+pub async fn recompute_legacy_sizes(holder: &FileHolder, store: Arc<Store>) -> anyhow::Result<()> {
+    if store.get::<bool>(Table::Meta, LEGACY_SIZES_KEY)?.is_none() {
+        return Ok(());
+    }
+
+    // Deleted files can be restored
+    let files = files_of(&holder.snapshot(), true);
+
+    let mut results = futures::stream::iter(files)
+        .map(|(folder_path, file_name, file)| async move {
+            let key = blob_key(&folder_path, &file_name);
+            let result = read_blob(holder.storage(), &key).await;
+
+            (folder_path, file_name, file, result)
+        })
+        .buffer_unordered(PARALLEL_CHECKS);
+
+    let mut fixed = 0;
+    let mut errors = 0;
+
+    while let Some((folder_path, file_name, file, result)) = results.next().await {
+        let Ok((size, hash)) = result else {
+            continue;
+        };
+
+        if hash != file.hash || size == file.size {
+            continue;
+        }
+
+        match holder
+            .set_size(&folder_path, &file_name, &file.hash, size)
+            .await
+        {
+            Ok(true) => fixed += 1,
+            Ok(false) => {}
+            Err(e) => {
+                error!(
+                    "Can't set size of {}: {e}",
+                    blob_key(&folder_path, &file_name)
+                );
+                errors += 1;
+            }
+        }
+    }
+
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "Can't recompute sizes, {errors} files failed"
+        ));
+    }
+
+    let mut tx = Transaction::default();
+    tx.delete(Table::Meta, LEGACY_SIZES_KEY);
+
+    spawn_blocking(move || store.commit(tx)).await??;
+
+    info!("Recomputed file sizes: {fixed} files changed");
+
+    Ok(())
+}
+
+pub async fn run_startup_scrub() {
+    let report = scrub(FileHolder::instance(), Config::instance().scrub.quarantine).await;
+
+    info!("Integrity scrub: {}", scrub_summary(&report));
+
+    for v in &report.broken {
+        error!("Integrity scrub: {} - {}", v.path, v.problem);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn broken_files_are_quarantined_until_intact() {
+        let dir = std::env::temp_dir().join(format!("scrub_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let holder =
+            FileHolder::load(store, Box::leak(Box::new(MemoryStorage::default()))).unwrap();
+
+        for name in ["a.bin", "b.bin", "c.bin", "d.bin"] {
+            holder.add_file("data", name, vec![1; 10], 1).await.unwrap();
+        }
+
+        holder.delete_file("data", "d.bin", 1).await.unwrap();
+        holder.storage().delete("data/d.bin").await.unwrap();
+        holder.storage().delete("data/b.bin").await.unwrap();
+        holder
+            .storage()
+            .put_bytes("data/c.bin", vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        let report = scrub(&holder, false).await;
+
+        assert_eq!(report.checked, 3);
+        assert_eq!(
+            report.broken.iter().map(|v| &v.path).collect::<Vec<_>>(),
+            vec!["data/b.bin", "data/c.bin"]
+        );
+        assert!(!holder.snapshot().folders["data"].files["b.bin"].quarantined);
+
+        scrub(&holder, true).await;
+
+        let tree = holder.snapshot();
+        let files = &tree.folders["data"].files;
+        assert!(!files["a.bin"].quarantined);
+        assert!(files["b.bin"].quarantined && files["c.bin"].quarantined);
+
+        let mut compressed = vec![];
+        shared::file::compress_in_mem(&[1; 10], &mut compressed).unwrap();
+        holder
+            .storage()
+            .put_bytes("data/b.bin", compressed)
+            .await
+            .unwrap();
+
+        let report = scrub(&holder, true).await;
+
+        assert_eq!(report.released, vec!["data/b.bin"]);
+        assert_eq!(report.broken.len(), 1);
+        assert!(!holder.snapshot().folders["data"].files["b.bin"].quarantined);
+        assert!(last_report().is_some());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn legacy_sizes_are_recomputed() {
+        let dir = std::env::temp_dir().join(format!("scrub_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let holder =
+            FileHolder::load(store.clone(), Box::leak(Box::new(MemoryStorage::default()))).unwrap();
+
+        holder
+            .add_file("data", "a.bin", vec![1; 100], 1)
+            .await
+            .unwrap();
+        holder
+            .add_file("data", "b.bin", vec![2; 10], 1)
+            .await
+            .unwrap();
+
+        // Recorded by an older preparator
+        let compressed_size = holder
+            .storage()
+            .get_bytes("data/a.bin")
+            .await
+            .unwrap()
+            .len() as u64;
+        let hash = holder.snapshot().folders["data"].files["a.bin"]
+            .hash
+            .clone();
+        holder
+            .set_size("data", "a.bin", &hash, compressed_size)
+            .await
+            .unwrap();
+
+        let report = scrub(&holder, false).await;
+        assert_eq!(
+            report.broken.iter().map(|v| &v.path).collect::<Vec<_>>(),
+            vec!["data/a.bin"]
+        );
+
+        // Nothing is recomputed without the mark
+        recompute_legacy_sizes(&holder, store.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            holder.snapshot().folders["data"].files["a.bin"].size,
+            compressed_size
+        );
+
+        let mut tx = Transaction::default();
+        tx.put(Table::Meta, LEGACY_SIZES_KEY, &true);
+        store.commit(tx).unwrap();
+
+        recompute_legacy_sizes(&holder, store.clone())
+            .await
+            .unwrap();
+
+        let tree = holder.snapshot();
+        assert_eq!(tree.folders["data"].files["a.bin"].size, 100);
+        assert_eq!(tree.size, 110);
+        assert!(scrub(&holder, false).await.broken.is_empty());
+        assert_eq!(
+            store.get::<bool>(Table::Meta, LEGACY_SIZES_KEY).unwrap(),
+            None
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
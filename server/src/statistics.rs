@@ -1 +1,367 @@
+use crate::config::Config;
+use crate::migrations::STATISTICS;
+use serde::{Deserialize, Serialize};
+use shared::admin_panel::{DayStatistics, FileDownloads, StatisticsSeries};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::spawn_blocking;
+use tracing::error;
+use uuid::Uuid;
 
+static INSTANCE: OnceLock<Statistics> = OnceLock::new();
+
+static STATISTICS_FILE_NAME: &str = "statistics.ron";
+
+const SECS_IN_DAY: i64 = 24 * 60 * 60;
+/// Counters are changed on every download, so they are dumped periodically, not on every change
+const DUMP_INTERVAL: Duration = Duration::from_secs(60);
+const TOP_FILES_COUNT: usize = 20;
+/// Longest series an admin can request, a year with a leap day. Older days are dropped
+const MAX_SERIES_DAYS: u32 = 366;
+/// Install ids come from clients, ids past this are not stored, so unique installs of a busier
+/// day are a lower bound
+const MAX_INSTALLS_PER_DAY: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Default)]
+struct DayCounters {
+    manifest_fetches: u64,
+    bytes_served: u64,
+    /// file path -> (downloads, bytes served), resumed downloads add only their bytes
+    files: HashMap<String, (u64, u64)>,
+    installs: HashSet<Uuid>,
+}
+
+/// Game clients activity counters, aggregated per day (UTC)
+///
+/// Stored in `{database}/statistics.ron`
+pub struct Statistics {
+    path: PathBuf,
+    /// day start timestamp -> counters
+    days: RwLock<BTreeMap<i64, DayCounters>>,
+    changed: AtomicBool,
+}
+
+impl Statistics {
+    pub fn instance<'a>() -> &'a Statistics {
+        INSTANCE
+            .get_or_init(|| Self::load(Config::instance().dirs.database.join(STATISTICS_FILE_NAME)))
+    }
+
+    fn load(path: PathBuf) -> Self {
+        let days = STATISTICS
+            .load_ron_file(&path)
+            .unwrap_or_else(|e| {
+                error!("Corrupted statistics file {path:?}: {e}");
+
+                None
+            })
+            .unwrap_or_default();
+
+        Self {
+            path,
+            days: RwLock::new(days),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    pub async fn info(&self) -> String {
+        let days = self.days.read().await;
+
+        format!(
+            "Statistics:\n\tDays recorded: {}\n\tTotal downloads: {}\n",
+            days.len(),
+            days.values()
+                .flat_map(|v| v.files.values())
+                .map(|v| v.0)
+                .sum::<u64>()
+        )
+    }
+
+    pub async fn record_manifest_fetch(&self, install_id: Option<Uuid>) {
+        self.record(install_id, |day| day.manifest_fetches += 1)
+            .await;
+    }
+
+    /// A download is counted once by its request from the first byte, resumed parts add bytes
+    pub async fn record_download(
+        &self,
+        path: &str,
+        bytes: u64,
+        from_start: bool,
+        install_id: Option<Uuid>,
+    ) {
+        self.record(install_id, |day| {
+            day.bytes_served += bytes;
+
+            let file = day.files.entry(path.to_string()).or_default();
+            file.0 += u64::from(from_start);
+            file.1 += bytes;
+        })
+        .await;
+    }
+
+    async fn record(&self, install_id: Option<Uuid>, f: impl FnOnce(&mut DayCounters)) {
+        let mut days = self.days.write().await;
+
+        let today = day_start(chrono::Utc::now().timestamp());
+
+        if !days.contains_key(&today) {
+            prune(&mut days, today);
+        }
+
+        let day = days.entry(today).or_default();
+
+        if let Some(id) = install_id {
+            if day.installs.len() < MAX_INSTALLS_PER_DAY {
+                day.installs.insert(id);
+            }
+        }
+
+        f(day);
+
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Statistics for the last `days_count` days, including today, at most [`MAX_SERIES_DAYS`]
+    pub async fn series(&self, days_count: u32) -> StatisticsSeries {
+        let days_count = days_count.clamp(1, MAX_SERIES_DAYS);
+
+        let today = day_start(chrono::Utc::now().timestamp());
+        let first_day = today - SECS_IN_DAY * (days_count as i64 - 1);
+
+        let days = self.days.read().await;
+
+        let mut installs: HashSet<&Uuid> = HashSet::new();
+        let mut files: HashMap<&str, (u64, u64)> = HashMap::new();
+
+        let series = (0..days_count as i64)
+            .map(|i| {
+                let day = first_day + i * SECS_IN_DAY;
+
+                let Some(counters) = days.get(&day) else {
+                    return DayStatistics {
+                        day,
+                        ..Default::default()
+                    };
+                };
+
+                installs.extend(counters.installs.iter());
+
+                for (path, (downloads, bytes)) in &counters.files {
+                    let file = files.entry(path).or_default();
+                    file.0 += downloads;
+                    file.1 += bytes;
+                }
+
+                DayStatistics {
+                    day,
+                    manifest_fetches: counters.manifest_fetches,
+                    downloads: counters.files.values().map(|v| v.0).sum(),
+                    bytes_served: counters.bytes_served,
+                    unique_installs: counters.installs.len() as u32,
+                }
+            })
+            .collect();
+
+        let mut top_files: Vec<_> = files
+            .into_iter()
+            .map(|(path, (downloads, bytes_served))| FileDownloads {
+                path: path.to_string(),
+                downloads,
+                bytes_served,
+            })
+            .collect();
+
+        top_files.sort_by(|a, b| b.downloads.cmp(&a.downloads).then(a.path.cmp(&b.path)));
+        top_files.truncate(TOP_FILES_COUNT);
+
+        StatisticsSeries {
+            days: series,
+            unique_installs: installs.len() as u32,
+            top_files,
+        }
+    }
+
+    /// Dumps counters every [`DUMP_INTERVAL`] if they were changed, never returns
+    pub async fn run_dump_loop(&self) {
+        let mut interval = tokio::time::interval(DUMP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            self.flush().await;
+        }
+    }
+
+    /// Dumps counters if they were changed since the last dump, called on shutdown as well
+    pub async fn flush(&self) {
+        if self.changed.swap(false, Ordering::Relaxed) {
+            self.dump().await;
+        }
+    }
+
+    async fn dump(&self) {
+        let data = {
+            let days = self.days.read().await;
+
+            match STATISTICS.to_ron(&*days) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Can't serialize statistics: {e}");
+
+                    return;
+                }
+            }
+        };
+
+        let path = self.path.clone();
+
+        let written = spawn_blocking(move || write_file(&path, data.as_bytes()))
+            .await
+            .map_err(io::Error::other)
+            .and_then(|v| v);
+
+        if let Err(e) = written {
+            error!("Can't write statistics to {:?}: {e}", self.path);
+
+            // Retried on the next tick
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The file is replaced by rename, a crash during the dump leaves the previous one intact
+fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("ron.tmp");
+
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Drops days which are too old to be a part of any series
+fn prune(days: &mut BTreeMap<i64, DayCounters>, today: i64) {
+    let first_day = today - SECS_IN_DAY * (MAX_SERIES_DAYS as i64 - 1);
+
+    *days = days.split_off(&first_day);
+}
+
+fn day_start(time: i64) -> i64 {
+    time - time.rem_euclid(SECS_IN_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn series_are_aggregated_per_day() {
+        let path = std::env::temp_dir().join(format!("statistics_{}.ron", Uuid::new_v4()));
+
+        let statistics = Statistics::load(path.clone());
+
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+
+        statistics.record_manifest_fetch(Some(first)).await;
+        statistics.record_manifest_fetch(Some(second)).await;
+        statistics.record_manifest_fetch(None).await;
+        statistics
+            .record_download("system/l2.ini", 100, true, Some(first))
+            .await;
+        statistics
+            .record_download("system/l2.ini", 100, true, Some(second))
+            .await;
+        statistics
+            .record_download("maps/1.unr", 600, true, Some(first))
+            .await;
+        // Resumed by the same client
+        statistics
+            .record_download("maps/1.unr", 400, false, Some(first))
+            .await;
+
+        let yesterday = day_start(chrono::Utc::now().timestamp()) - SECS_IN_DAY;
+        statistics.days.write().await.insert(
+            yesterday,
+            DayCounters {
+                manifest_fetches: 5,
+                installs: HashSet::from([first]),
+                ..Default::default()
+            },
+        );
+
+        let StatisticsSeries {
+            days,
+            unique_installs,
+            top_files,
+        } = statistics.series(3).await;
+
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].manifest_fetches, 0);
+        assert_eq!(days[1].day, yesterday);
+        assert_eq!(days[1].manifest_fetches, 5);
+        assert_eq!(days[2].manifest_fetches, 3);
+        assert_eq!(days[2].downloads, 3);
+        assert_eq!(days[2].bytes_served, 1200);
+        assert_eq!(days[2].unique_installs, 2);
+        assert_eq!(unique_installs, 2);
+        assert_eq!(top_files[0].path, "system/l2.ini");
+        assert_eq!(top_files[0].downloads, 2);
+
+        assert_eq!(statistics.series(0).await.days.len(), 1);
+        assert_eq!(
+            statistics.series(u32::MAX).await.days.len(),
+            MAX_SERIES_DAYS as usize
+        );
+
+        statistics.dump().await;
+
+        assert!(!path.with_extension("ron.tmp").exists());
+
+        let loaded = Statistics::load(path.clone());
+        assert_eq!(loaded.series(3).await.days[2].bytes_served, 1200);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn old_days_and_extra_installs_are_dropped() {
+        let path = std::env::temp_dir().join(format!("statistics_{}.ron", Uuid::new_v4()));
+
+        let statistics = Statistics::load(path);
+
+        let today = day_start(chrono::Utc::now().timestamp());
+        let oldest = today - SECS_IN_DAY * (MAX_SERIES_DAYS as i64 - 1);
+
+        for day in [oldest - SECS_IN_DAY, oldest] {
+            statistics
+                .days
+                .write()
+                .await
+                .insert(day, Default::default());
+        }
+
+        for _ in 0..MAX_INSTALLS_PER_DAY + 5 {
+            statistics.record_manifest_fetch(Some(Uuid::new_v4())).await;
+        }
+
+        let days = statistics.days.read().await;
+
+        assert_eq!(
+            days.keys().copied().collect::<Vec<_>>(),
+            vec![oldest, today]
+        );
+        assert_eq!(days[&today].installs.len(), MAX_INSTALLS_PER_DAY);
+        assert_eq!(
+            days[&today].manifest_fetches,
+            MAX_INSTALLS_PER_DAY as u64 + 5
+        );
+    }
+}
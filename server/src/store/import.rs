@@ -0,0 +1,141 @@
+use crate::store::{next_id_key, Store, Table, Transaction};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use shared::admin_panel::{AdminUser, AuditRecord, PatchNote, PatchNoteRevision};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::info;
+
+/// Layout of the RON files written before the store
+#[derive(Deserialize)]
+struct LegacyItems<V> {
+    next_id: u32,
+    items: HashMap<u32, V>,
+}
+
+/// [`Table::Meta`] key of the hash of the imported `file_name`
+pub fn import_mark_key(file_name: &str) -> String {
+    format!("import.{file_name}")
+}
+
+/// Imports RON files of older versions
+///
+/// Each file replaces its tables in one commit and is imported only once, later changes live only
+/// in the store. The file list of the preparator is merged by [`crate::prepared`] instead.
+pub fn import_legacy_files(store: &Store, dir: &Path) -> anyhow::Result<()> {
+    import_items::<PatchNote>(store, dir, "patchnotes.ron", Table::PatchNotes)?;
+    import_items::<PatchNoteRevision>(
+        store,
+        dir,
+        "patch_note_revisions.ron",
+        Table::PatchNoteRevisions,
+    )?;
+    import_items::<AuditRecord>(store, dir, "audit.ron", Table::Audit)?;
+    import_items::<AdminUser>(store, dir, "admin_users.ron", Table::AdminUsers)
+}
+
+fn import_items<V: DeserializeOwned + Serialize>(
+    store: &Store,
+    dir: &Path,
+    file_name: &str,
+    table: Table,
+) -> anyhow::Result<()> {
+    import_file(store, dir, file_name, |text, tx| {
+        let legacy: LegacyItems<V> = ron::de::from_str(text)?;
+
+        clear(store, tx, table);
+
+        for (id, item) in &legacy.items {
+            tx.put(table, id, item);
+        }
+        tx.put(Table::Meta, next_id_key(table), &legacy.next_id);
+
+        Ok(())
+    })
+}
+
+fn import_file(
+    store: &Store,
+    dir: &Path,
+    file_name: &str,
+    import: impl FnOnce(&str, &mut Transaction) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let path = dir.join(file_name);
+
+    let text = match std::fs::read_to_string(&path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mark_key = import_mark_key(file_name);
+    let hash = format!("{:016x}", seahash::hash(text.as_bytes()));
+
+    // Admins changed the tables since, the file is stale even if it was edited by hand
+    if store.get::<String>(Table::Meta, &mark_key)?.is_some() {
+        return Ok(());
+    }
+
+    let mut tx = Transaction::default();
+
+    import(&text, &mut tx).map_err(|e| anyhow::anyhow!("Can't import {path:?}: {e}"))?;
+    tx.put(Table::Meta, mark_key, &hash);
+
+    store.commit(tx)?;
+
+    info!("Imported {path:?}");
+
+    Ok(())
+}
+
+fn clear(store: &Store, tx: &mut Transaction, table: Table) {
+    for key in store.keys(table) {
+        tx.delete(table, key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_files_are_imported_once() {
+        let dir = std::env::temp_dir().join(format!("import_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(
+            dir.join("admin_users.ron"),
+            r#"(next_id: 3, items: {1: (id: 1, name: "Admin", tg_user: None), 2: (id: 2, name: "Editor", tg_user: Some((id: 42, user_name: "editor")))})"#,
+        )
+        .unwrap();
+        let store = Store::open(&dir).unwrap();
+
+        import_legacy_files(&store, &dir).unwrap();
+
+        let users: Vec<(String, AdminUser)> = store.load(Table::AdminUsers).unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[1].1.tg_user.as_ref().unwrap().id, 42);
+        assert_eq!(
+            store
+                .get::<u32>(Table::Meta, &next_id_key(Table::AdminUsers))
+                .unwrap(),
+            Some(3)
+        );
+
+        // Changes made after the import are kept even if the file changes
+        let mut tx = Transaction::default();
+        tx.delete(Table::AdminUsers, 2);
+        store.commit(tx).unwrap();
+
+        std::fs::write(
+            dir.join("admin_users.ron"),
+            r#"(next_id: 4, items: {3: (id: 3, name: "Other", tg_user: None)})"#,
+        )
+        .unwrap();
+
+        import_legacy_files(&store, &dir).unwrap();
+        assert_eq!(store.keys(Table::AdminUsers), vec!["1"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
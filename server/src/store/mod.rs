@@ -0,0 +1,615 @@
+//! Embedded transactional store of patch notes, admins, audit and the game files tree
+//!
+//! A commit is appended to the journal and synced before it is applied in memory, so it is either
+//! fully on disk or absent after a crash: a torn record at the journal end is dropped on open.
+//! The journal is compacted into the snapshot, which is replaced atomically.
+//!
+//! The snapshot and journal records are [`Versioned`](shared::versioned::Versioned), an older
+//! store is backed up and migrated by [`migrations::STORE`] on open.
+mod import;
+
+pub use import::{import_legacy_files, import_mark_key};
+
+use crate::migrations::{self, split_envelope};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{error, warn};
+
+static INSTANCE: OnceLock<Arc<Store>> = OnceLock::new();
+
+const SNAPSHOT_FILE: &str = "store.snapshot";
+const JOURNAL_FILE: &str = "store.journal";
+/// Journal size in bytes after which it is compacted into the snapshot
+const COMPACT_AFTER: u64 = 16 * 1024 * 1024;
+/// Record header: payload length (u32) and payload hash (u64), little endian
+const HEADER_SIZE: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Table {
+    /// Id counters and import marks
+    Meta,
+    PatchNotes,
+    PatchNoteRevisions,
+    Audit,
+    AdminUsers,
+    /// Folder path -> folder without its children
+    Folders,
+    /// File path -> file info
+    Files,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Op {
+    Put {
+        table: Table,
+        key: String,
+        value: Value,
+    },
+    Delete {
+        table: Table,
+        key: String,
+    },
+}
+
+type Tables = BTreeMap<Table, BTreeMap<String, Value>>;
+
+/// [`Table::Meta`] key of the next id of items in `table`
+pub fn next_id_key(table: Table) -> String {
+    format!("{table:?}.next_id")
+}
+
+/// Changes applied by [`Store::commit`] all together or not at all
+#[derive(Default)]
+pub struct Transaction {
+    ops: Vec<Op>,
+    /// First serialization error, reported by commit
+    error: Option<serde_json::Error>,
+}
+
+impl Transaction {
+    pub fn put<T: Serialize>(&mut self, table: Table, key: impl ToString, value: &T) {
+        match serde_json::to_value(value) {
+            Ok(value) => self.ops.push(Op::Put {
+                table,
+                key: key.to_string(),
+                value,
+            }),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+    }
+
+    pub fn delete(&mut self, table: Table, key: impl ToString) {
+        self.ops.push(Op::Delete {
+            table,
+            key: key.to_string(),
+        });
+    }
+}
+
+pub struct Store {
+    dir: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    tables: Tables,
+    journal: File,
+    journal_size: u64,
+}
+
+impl Store {
+    /// Opened by [`Store::init`] on startup
+    pub fn instance() -> Arc<Store> {
+        INSTANCE.get().expect("Store is opened on startup").clone()
+    }
+
+    /// Opens the store in `dir` and imports legacy RON files found there
+    pub fn init(dir: &Path) -> anyhow::Result<()> {
+        let store = Store::open(dir)?;
+
+        import_legacy_files(&store, dir)?;
+
+        if INSTANCE.set(Arc::new(store)).is_err() {
+            panic!("Store is already opened");
+        }
+
+        Ok(())
+    }
+
+    /// A corrupted snapshot or journal is an error, the data is never silently dropped
+    pub fn open(dir: &Path) -> anyhow::Result<Store> {
+        std::fs::create_dir_all(dir)?;
+
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+
+        let snapshot = match std::fs::read(&snapshot_path) {
+            Ok(data) => Some(split_envelope(serde_json::from_slice(&data).map_err(
+                |e| anyhow::anyhow!("Corrupted snapshot {snapshot_path:?}: {e}"),
+            )?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut version = snapshot.as_ref().map(|(v, _)| *v);
+        let mut tables: Tables = match snapshot {
+            Some((_, data)) => serde_json::from_value(data)
+                .map_err(|e| anyhow::anyhow!("Corrupted snapshot {snapshot_path:?}: {e}"))?,
+            None => Tables::new(),
+        };
+
+        let journal_path = dir.join(JOURNAL_FILE);
+
+        let data = match std::fs::read(&journal_path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut offset = 0;
+
+        while let Some((payload, next)) = read_record(&data, offset)
+            .map_err(|e| anyhow::anyhow!("Corrupted journal {journal_path:?}: {e}"))?
+        {
+            let corrupted =
+                |e| anyhow::anyhow!("Corrupted journal {journal_path:?} at {offset}: {e}");
+
+            let (record_version, ops) =
+                split_envelope(serde_json::from_slice(payload).map_err(corrupted)?);
+
+            // Migration compacts the journal, so records always match the snapshot
+            if *version.get_or_insert(record_version) != record_version {
+                return Err(anyhow::anyhow!(
+                    "Journal {journal_path:?} record at {offset} is version {record_version}, \
+                     store is version {version:?}"
+                ));
+            }
+
+            apply(&mut tables, serde_json::from_value(ops).map_err(corrupted)?);
+            offset = next;
+        }
+
+        let version = version.unwrap_or(migrations::STORE.version);
+
+        if version > migrations::STORE.version {
+            return Err(anyhow::anyhow!(
+                "Store version {version} is newer than supported {}, update the server",
+                migrations::STORE.version
+            ));
+        }
+
+        if version < migrations::STORE.version {
+            migrations::backup(
+                dir,
+                migrations::STORE.name,
+                version,
+                &[snapshot_path, journal_path.clone()],
+            )?;
+
+            let mut data = serde_json::to_value(&tables)?;
+            migrations::STORE.migrate(&mut data, version)?;
+            tables = serde_json::from_value(data)?;
+        }
+
+        if offset < data.len() {
+            warn!(
+                "Dropping {} bytes of an unfinished commit from {journal_path:?}",
+                data.len() - offset
+            );
+        }
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)?;
+
+        journal.set_len(offset as u64)?;
+        journal.sync_all()?;
+
+        let store = Store {
+            dir: dir.to_path_buf(),
+            inner: Mutex::new(Inner {
+                tables,
+                journal,
+                journal_size: offset as u64,
+            }),
+        };
+
+        if version < migrations::STORE.version {
+            store.compact(&mut store.inner.lock().unwrap())?;
+        }
+
+        Ok(store)
+    }
+
+    pub fn commit(&self, tx: Transaction) -> anyhow::Result<()> {
+        if let Some(e) = tx.error {
+            return Err(anyhow::anyhow!("Can't serialize a record: {e}"));
+        }
+
+        if tx.ops.is_empty() {
+            return Ok(());
+        }
+
+        let payload = serde_json::to_vec(&migrations::STORE.envelope(&tx.ops))?;
+
+        let mut record = Vec::with_capacity(HEADER_SIZE + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&seahash::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+
+        let mut inner = self.inner.lock().unwrap();
+
+        let written = inner
+            .journal
+            .write_all(&record)
+            .and_then(|_| inner.journal.sync_data());
+
+        if let Err(e) = written {
+            // Later commits must not follow a torn record
+            let size = inner.journal_size;
+            let _ = inner.journal.set_len(size);
+
+            return Err(anyhow::anyhow!("Can't write the journal: {e}"));
+        }
+
+        inner.journal_size += record.len() as u64;
+
+        apply(&mut inner.tables, tx.ops);
+
+        if inner.journal_size > COMPACT_AFTER {
+            if let Err(e) = self.compact(&mut inner) {
+                error!("Can't compact the store journal: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, table: Table, key: &str) -> anyhow::Result<Option<T>> {
+        let inner = self.inner.lock().unwrap();
+
+        let Some(value) = inner.tables.get(&table).and_then(|v| v.get(key)) else {
+            return Ok(None);
+        };
+
+        serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Invalid {table:?} record {key}: {e}"))
+    }
+
+    /// All records of the table, sorted by key
+    pub fn load<T: DeserializeOwned>(&self, table: Table) -> anyhow::Result<Vec<(String, T)>> {
+        let inner = self.inner.lock().unwrap();
+
+        let Some(records) = inner.tables.get(&table) else {
+            return Ok(vec![]);
+        };
+
+        records
+            .iter()
+            .map(|(key, value)| {
+                serde_json::from_value(value.clone())
+                    .map(|v| (key.clone(), v))
+                    .map_err(|e| anyhow::anyhow!("Invalid {table:?} record {key}: {e}"))
+            })
+            .collect()
+    }
+
+    pub fn keys(&self, table: Table) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+
+        inner
+            .tables
+            .get(&table)
+            .map(|v| v.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn info(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+
+        format!(
+            "Store:\n\tDir: {:?}\n\tRecords: {}\n\tJournal: {} bytes\n",
+            self.dir,
+            inner.tables.values().map(|v| v.len()).sum::<usize>(),
+            inner.journal_size
+        )
+    }
+
+    /// Makes the following commits fail like on a full disk
+    #[cfg(test)]
+    pub(crate) fn break_journal(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.journal = File::open(self.dir.join(JOURNAL_FILE)).unwrap();
+    }
+
+    /// Snapshot is replaced by rename, the journal is truncated after it is durable
+    fn compact(&self, inner: &mut Inner) -> anyhow::Result<()> {
+        let tmp_path = self.dir.join(format!("{SNAPSHOT_FILE}.tmp"));
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(
+            &migrations::STORE.envelope(&inner.tables),
+        )?)?;
+        file.sync_all()?;
+
+        std::fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+        File::open(&self.dir)?.sync_all()?;
+
+        // A crash before this point replays the journal over the snapshot, which is harmless
+        inner.journal.set_len(0)?;
+        inner.journal.sync_all()?;
+        inner.journal_size = 0;
+
+        Ok(())
+    }
+}
+
+/// Returns the payload and the next record offset, `None` for the end or a torn last record
+///
+/// Only the last record can be torn, a broken record followed by more data is an error
+fn read_record(data: &[u8], offset: usize) -> anyhow::Result<Option<(&[u8], usize)>> {
+    let Some(header) = offset
+        .checked_add(HEADER_SIZE)
+        .and_then(|end| data.get(offset..end))
+    else {
+        return Ok(None);
+    };
+
+    let len = u32::from_le_bytes(header[0..4].try_into()?) as usize;
+    let hash = u64::from_le_bytes(header[4..12].try_into()?);
+
+    let start = offset + HEADER_SIZE;
+
+    let Some(payload) = start.checked_add(len).and_then(|end| data.get(start..end)) else {
+        // A torn record is the last one, a broken length would hide the commits after it
+        if let Some(next) = (start..data.len()).find(|v| is_record(data, *v)) {
+            return Err(anyhow::anyhow!(
+                "record at {offset} is longer than the journal, a record follows at {next}"
+            ));
+        }
+
+        return Ok(None);
+    };
+
+    let end = start + len;
+
+    if seahash::hash(payload) == hash {
+        return Ok(Some((payload, end)));
+    }
+
+    if end == data.len() {
+        return Ok(None);
+    }
+
+    Err(anyhow::anyhow!(
+        "record at {offset} doesn't match its hash and is followed by {} bytes",
+        data.len() - end
+    ))
+}
+
+/// A whole record matching its hash starts at `offset`
+fn is_record(data: &[u8], offset: usize) -> bool {
+    let Some(header) = data.get(offset..).and_then(|v| v.get(..HEADER_SIZE)) else {
+        return false;
+    };
+
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let start = offset + HEADER_SIZE;
+
+    match start.checked_add(len).and_then(|end| data.get(start..end)) {
+        Some(payload) => {
+            !payload.is_empty() && seahash::hash(payload).to_le_bytes() == header[4..12]
+        }
+        None => false,
+    }
+}
+
+fn apply(tables: &mut Tables, ops: Vec<Op>) {
+    for op in ops {
+        match op {
+            Op::Put { table, key, value } => {
+                tables.entry(table).or_default().insert(key, value);
+            }
+            Op::Delete { table, key } => {
+                if let Some(v) = tables.get_mut(&table) {
+                    v.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("store_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn commits_survive_reopen_and_torn_writes() {
+        let dir = test_dir();
+        let store = Store::open(&dir).unwrap();
+
+        let mut tx = Transaction::default();
+        tx.put(Table::Meta, "a", &1);
+        tx.put(Table::Meta, "b", &2);
+        store.commit(tx).unwrap();
+
+        let mut tx = Transaction::default();
+        tx.delete(Table::Meta, "a");
+        tx.put(Table::Meta, "c", &3);
+        store.commit(tx).unwrap();
+
+        drop(store);
+
+        // Crash in the middle of the next commit
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap();
+        journal.write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+        drop(journal);
+
+        let store = Store::open(&dir).unwrap();
+
+        assert_eq!(
+            store.load::<u32>(Table::Meta).unwrap(),
+            vec![("b".to_string(), 2), ("c".to_string(), 3)]
+        );
+
+        let mut tx = Transaction::default();
+        tx.put(Table::Meta, "d", &4);
+        store.commit(tx).unwrap();
+
+        store.compact(&mut store.inner.lock().unwrap()).unwrap();
+
+        let mut tx = Transaction::default();
+        tx.put(Table::Meta, "e", &5);
+        store.commit(tx).unwrap();
+
+        drop(store);
+
+        let store = Store::open(&dir).unwrap();
+
+        assert_eq!(store.keys(Table::Meta), vec!["b", "c", "d", "e"]);
+        assert_eq!(store.get::<u32>(Table::Meta, "d").unwrap(), Some(4));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn broken_record_before_others_is_an_error() {
+        let dir = test_dir();
+        let store = Store::open(&dir).unwrap();
+
+        for i in 0..3 {
+            let mut tx = Transaction::default();
+            tx.put(Table::Meta, i, &i);
+            store.commit(tx).unwrap();
+        }
+
+        drop(store);
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        let mut data = std::fs::read(&journal_path).unwrap();
+        let valid = data.clone();
+
+        // Payload of the first record
+        data[HEADER_SIZE + 2] ^= 0xff;
+        std::fs::write(&journal_path, &data).unwrap();
+
+        let error = Store::open(&dir).err().unwrap().to_string();
+        assert!(error.contains("Corrupted journal"), "{error}");
+
+        // Later commits are not dropped by the failed open
+        assert_eq!(std::fs::read(&journal_path).unwrap(), data);
+
+        std::fs::write(&journal_path, &valid).unwrap();
+        assert_eq!(
+            Store::open(&dir).unwrap().keys(Table::Meta),
+            vec!["0", "1", "2"]
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn broken_length_before_others_is_an_error() {
+        let dir = test_dir();
+        let store = Store::open(&dir).unwrap();
+
+        for i in 0..3 {
+            let mut tx = Transaction::default();
+            tx.put(Table::Meta, i, &i);
+            store.commit(tx).unwrap();
+        }
+
+        drop(store);
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        let mut data = std::fs::read(&journal_path).unwrap();
+        let valid = data.clone();
+
+        // Length of the first record points past the end of the journal
+        data[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&journal_path, &data).unwrap();
+
+        let error = Store::open(&dir).err().unwrap().to_string();
+        assert!(error.contains("Corrupted journal"), "{error}");
+        assert_eq!(std::fs::read(&journal_path).unwrap(), data);
+
+        // The same header as the last record is a torn write
+        let mut torn = valid.clone();
+        torn.extend_from_slice(&[200, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        std::fs::write(&journal_path, &torn).unwrap();
+
+        assert_eq!(
+            Store::open(&dir).unwrap().keys(Table::Meta),
+            vec!["0", "1", "2"]
+        );
+        assert_eq!(std::fs::read(&journal_path).unwrap(), valid);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn old_store_is_backed_up_and_migrated() {
+        let dir = test_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Snapshots had no envelope in version 1
+        let legacy = include_str!("../../fixtures/migrations/store_v1.json");
+        std::fs::write(dir.join(SNAPSHOT_FILE), legacy).unwrap();
+
+        let store = Store::open(&dir).unwrap();
+
+        let patch_note: Value = store.get(Table::PatchNotes, "0").unwrap().unwrap();
+        assert_eq!(patch_note["published_at"], 1_700_000_000);
+
+        let backups: Vec<_> = std::fs::read_dir(dir.join("backups"))
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(backups[0].join(SNAPSHOT_FILE)).unwrap(),
+            legacy
+        );
+
+        drop(store);
+
+        let snapshot: Value =
+            serde_json::from_slice(&std::fs::read(dir.join(SNAPSHOT_FILE)).unwrap()).unwrap();
+        assert_eq!(snapshot["version"], migrations::STORE.version);
+
+        // Reopening a current store takes no backup
+        Store::open(&dir).unwrap();
+        assert_eq!(std::fs::read_dir(dir.join("backups")).unwrap().count(), 1);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn corrupted_snapshot_is_an_error() {
+        let dir = test_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(SNAPSHOT_FILE), "{ not json").unwrap();
+
+        assert!(Store::open(&dir).is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::config::Config;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use shared::admin_panel::TgLoginData;
+use std::sync::OnceLock;
+use tokio::spawn;
+use tracing::{error, info};
+
+static INSTANCE: OnceLock<Option<Telegram>> = OnceLock::new();
+
+static DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+/// Login widget payloads older than this are rejected
+const MAX_AUTH_AGE_SECS: i64 = 24 * 60 * 60;
+
+/// Login widget payloads dated later than now by more than this are rejected
+const MAX_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// Telegram bot, used for admins login and server events notifications
+///
+/// Configured with env variables:
+///  - `TELEGRAM_BOT_TOKEN` - integration is disabled if not set
+///  - `TELEGRAM_API_URL` - Bot API base url, `https://api.telegram.org` by default
+///  - `TELEGRAM_CHAT_ID` - chat for notifications, notifications are disabled if not set
+pub struct Telegram {
+    api_url: String,
+    bot_token: String,
+    chat_id: Option<i64>,
+    client: reqwest::Client,
+}
+
+impl Telegram {
+    pub fn instance<'a>() -> Option<&'a Telegram> {
+        INSTANCE.get_or_init(Self::from_env).as_ref()
+    }
+
+    pub fn new(api_url: &str, bot_token: &str, chat_id: Option<i64>) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            bot_token: bot_token.to_string(),
+            chat_id,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn from_env() -> Option<Self> {
+        if !Config::instance().features.telegram {
+            info!("Telegram integration is disabled in config");
+
+            return None;
+        }
+
+        let Ok(bot_token) = std::env::var("TELEGRAM_BOT_TOKEN") else {
+            info!("Telegram integration is disabled");
+
+            return None;
+        };
+
+        let api_url =
+            std::env::var("TELEGRAM_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+
+        let chat_id = std::env::var("TELEGRAM_CHAT_ID")
+            .ok()
+            .and_then(|v| v.parse().ok());
+
+        Some(Self::new(&api_url, &bot_token, chat_id))
+    }
+
+    pub fn info(&self) -> String {
+        format!(
+            "Telegram:\n\tBot id: {}\n\tNotifications: {}\n",
+            self.bot_id(),
+            if self.chat_id.is_some() { "on" } else { "off" }
+        )
+    }
+
+    /// Bot id is the part of the token before `:`
+    pub fn bot_id(&self) -> u64 {
+        self.bot_token
+            .split(':')
+            .next()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn verify_login(&self, data: &TgLoginData) -> anyhow::Result<()> {
+        if !self.check_hash(data) {
+            anyhow::bail!("Telegram login hash mismatch");
+        }
+
+        check_auth_date(data.auth_date, chrono::Utc::now().timestamp())
+    }
+
+    fn check_hash(&self, data: &TgLoginData) -> bool {
+        let mut fields = vec![
+            ("auth_date", data.auth_date.to_string()),
+            ("first_name", data.first_name.clone()),
+            ("id", data.id.to_string()),
+        ];
+
+        if let Some(v) = &data.last_name {
+            fields.push(("last_name", v.clone()));
+        }
+        if let Some(v) = &data.photo_url {
+            fields.push(("photo_url", v.clone()));
+        }
+        if let Some(v) = &data.username {
+            fields.push(("username", v.clone()));
+        }
+
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+
+        let data_check_string = fields
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let Ok(hash) = hex::decode(&data.hash) else {
+            return false;
+        };
+
+        let Ok(mut mac) =
+            Hmac::<Sha256>::new_from_slice(&Sha256::digest(self.bot_token.as_bytes()))
+        else {
+            return false;
+        };
+
+        mac.update(data_check_string.as_bytes());
+
+        mac.verify_slice(&hash).is_ok()
+    }
+
+    pub async fn send_message(&self, chat_id: i64, text: &str) -> anyhow::Result<()> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/bot{}/sendMessage",
+                self.api_url, self.bot_token
+            ))
+            .json(&serde_json::json!({
+                "chat_id": chat_id,
+                "text": text,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Telegram responded with {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    /// Sends message to the notifications chat in background
+    pub fn notify(text: impl Into<String>) {
+        let Some(telegram) = Self::instance() else {
+            return;
+        };
+
+        let Some(chat_id) = telegram.chat_id else {
+            return;
+        };
+
+        let text = text.into();
+
+        spawn(async move {
+            if let Err(e) = telegram.send_message(chat_id, &text).await {
+                // not app_log, otherwise failed notification about error produces new one
+                error!("Can't send Telegram notification: {e}");
+            }
+        });
+    }
+}
+
+fn check_auth_date(auth_date: i64, now: i64) -> anyhow::Result<()> {
+    // Dates too far away to subtract are rejected as well
+    if auth_date
+        .checked_sub(now)
+        .is_none_or(|v| v > MAX_CLOCK_SKEW_SECS)
+    {
+        anyhow::bail!("Telegram login data is dated in the future");
+    }
+
+    if now
+        .checked_sub(auth_date)
+        .is_none_or(|v| v > MAX_AUTH_AGE_SECS)
+    {
+        anyhow::bail!("Telegram login data is outdated");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use tokio::sync::mpsc;
+
+    const TOKEN: &str = "123456:TEST-token";
+
+    #[test]
+    fn verify_login() {
+        let telegram = Telegram::new(DEFAULT_API_URL, TOKEN, None);
+
+        let mut data = TgLoginData {
+            id: 42,
+            first_name: "Ivan".to_string(),
+            last_name: None,
+            username: Some("ivan".to_string()),
+            photo_url: None,
+            auth_date: 1700000000,
+            hash: "e44cd32a7373654958e6d136c8a2320cd1952852cd531c4d4c267308bf4146bd".to_string(),
+        };
+
+        assert!(telegram.check_hash(&data));
+        // valid, but outdated
+        assert!(telegram.verify_login(&data).is_err());
+
+        data.username = Some("admin".to_string());
+        assert!(!telegram.check_hash(&data));
+
+        data.hash = "not hex".to_string();
+        assert!(!telegram.check_hash(&data));
+    }
+
+    #[test]
+    fn auth_date_is_checked_both_ways() {
+        let now = 1700000000;
+
+        assert!(check_auth_date(now, now).is_ok());
+        assert!(check_auth_date(now - MAX_AUTH_AGE_SECS, now).is_ok());
+        assert!(check_auth_date(now - MAX_AUTH_AGE_SECS - 1, now).is_err());
+        assert!(check_auth_date(now + MAX_CLOCK_SKEW_SECS, now).is_ok());
+        assert!(check_auth_date(now + MAX_CLOCK_SKEW_SECS + 1, now).is_err());
+        assert!(check_auth_date(i64::MAX, now).is_err());
+        assert!(check_auth_date(i64::MIN, now).is_err());
+        assert!(check_auth_date(i64::MAX, -1).is_err());
+    }
+
+    #[tokio::test]
+    async fn send_message_uses_configured_api_url() {
+        let (tx, mut rx) = mpsc::channel(1);
+
+        let app = Router::new().route(
+            "/bot123456:TEST-token/sendMessage",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                tx.send(body).await.unwrap();
+
+                Json(serde_json::json!({ "ok": true }))
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        spawn(async move { axum::serve(listener, app).await });
+
+        let telegram = Telegram::new(&format!("http://{addr}/"), TOKEN, Some(7));
+
+        telegram.send_message(7, "Release!").await.unwrap();
+
+        let body = rx.recv().await.unwrap();
+
+        assert_eq!(body["chat_id"], 7);
+        assert_eq!(body["text"], "Release!");
+    }
+}
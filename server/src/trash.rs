@@ -0,0 +1,240 @@
+//! Trash of game files
+//!
+//! Deleted files and folders stay in the tree until an admin restores or purges them, or the GC
+//! collects them after [`GcConfig::retention_days`].
+//!
+//! [`GcConfig::retention_days`]: crate::config::GcConfig::retention_days
+use crate::config::Config;
+use crate::file_updater::{folder_info, tree_rows, FileHolder};
+use crate::gc::SECS_IN_DAY;
+use shared::admin_panel::{AdminUser, TrashEntry, TrashItem};
+use shared::file::ServerFolderInfo;
+use shared::storage::blob_key;
+use std::cmp::Reverse;
+
+/// Every deleted entry of the tree, oldest deletions first
+pub fn trash_entries(root: &ServerFolderInfo, users: &[AdminUser]) -> Vec<TrashEntry> {
+    fn walk(
+        path: &str,
+        folder: &ServerFolderInfo,
+        items: &mut Vec<(TrashItem, u64, Option<i64>, Option<u32>)>,
+    ) {
+        for (name, file) in folder.files.iter().filter(|(_, v)| v.deleted) {
+            let item = TrashItem {
+                path: blob_key(path, name),
+                folder: false,
+            };
+
+            items.push((item, file.size, file.deleted_at, file.deleted_by));
+        }
+
+        for (name, child) in &folder.folders {
+            let child_path = blob_key(path, name);
+
+            if child.deleted {
+                let item = TrashItem {
+                    path: child_path.clone(),
+                    folder: true,
+                };
+
+                items.push((item, child.size, child.deleted_at, child.deleted_by));
+            }
+
+            walk(&child_path, child, items);
+        }
+    }
+
+    let config = Config::instance();
+    let retention = config
+        .features
+        .gc
+        .then_some(config.gc.retention_days as i64 * SECS_IN_DAY);
+
+    let mut items = vec![];
+
+    walk("", root, &mut items);
+
+    let mut entries: Vec<_> = items
+        .into_iter()
+        .map(|(item, size, deleted_at, deleted_by)| TrashEntry {
+            item,
+            size,
+            deleted_at,
+            deleted_by,
+            deleted_by_name: users
+                .iter()
+                .find(|v| Some(v.id) == deleted_by)
+                .map(|v| v.name.clone()),
+            purge_at: retention.zip(deleted_at).map(|(a, b)| a + b),
+        })
+        .collect();
+
+    entries.sort_by(|a, b| (a.deleted_at, &a.item).cmp(&(b.deleted_at, &b.item)));
+
+    entries
+}
+
+/// Returns problems of items which could not be restored
+pub async fn restore(holder: &FileHolder, items: &[TrashItem], user_id: u32) -> Vec<String> {
+    let mut items = items.to_vec();
+
+    // Parents first, their entries can be restored in the same batch
+    items.sort_by_key(|v| v.path.matches('/').count());
+
+    let mut errors = vec![];
+
+    for item in items {
+        let restored = if item.folder {
+            holder.restore_folder(&item.path, user_id).await
+        } else {
+            let (folder_path, file_name) = item.path.rsplit_once('/').unwrap_or(("", &item.path));
+
+            holder.restore_file(folder_path, file_name, user_id).await
+        };
+
+        match restored {
+            Ok(true) => {}
+            Ok(false) => errors.push(format!("{} is not in the trash", item.path)),
+            Err(e) => errors.push(format!("Can't restore {}: {e}", item.path)),
+        }
+    }
+
+    errors
+}
+
+/// Purges the entries with their blobs right away, returns problems of items which were not purged
+pub async fn purge(holder: &FileHolder, items: &[TrashItem]) -> Vec<String> {
+    let mut items = items.to_vec();
+
+    // Files and nested folders first, their parents can be purged in the same batch
+    items.sort_by_key(|v| (v.folder, Reverse(v.path.matches('/').count())));
+
+    let mut errors = vec![];
+
+    for item in items {
+        let files = if item.folder {
+            let tree = holder.snapshot();
+
+            match folder_info(&tree, &item.path).filter(|v| v.deleted) {
+                Some(folder) => tree_rows(folder)
+                    .1
+                    .into_iter()
+                    .map(|(path, _)| blob_key(&item.path, &path))
+                    .collect(),
+                None => {
+                    errors.push(format!("{} is not in the trash", item.path));
+
+                    continue;
+                }
+            }
+        } else {
+            vec![item.path.clone()]
+        };
+
+        // Everything in the trash is expired for an admin
+        for path in files {
+            let (folder_path, file_name) = path.rsplit_once('/').unwrap_or(("", &path));
+
+            match holder.purge_file(folder_path, file_name, i64::MAX).await {
+                Ok(Some(_)) => {}
+                Ok(None) => errors.push(format!("{path} is not in the trash")),
+                Err(e) => errors.push(format!("Can't delete blob {path}: {e}")),
+            }
+        }
+
+        if item.folder {
+            match holder.purge_folder(&item.path, i64::MAX).await {
+                Ok(true) => {}
+                Ok(false) => errors.push(format!("Can't purge folder {}", item.path)),
+                Err(e) => errors.push(format!("Can't purge folder {}: {e}", item.path)),
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_updater::file_info;
+    use crate::store::Store;
+    use shared::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn trash_is_restored_and_purged() {
+        let dir = std::env::temp_dir().join(format!("trash_{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let holder =
+            FileHolder::load(store, Box::leak(Box::new(MemoryStorage::default()))).unwrap();
+
+        for (folder, name) in [("data", "a.bin"), ("data", "b.bin"), ("old/sub", "c.bin")] {
+            holder.add_file(folder, name, vec![1; 10], 1).await.unwrap();
+        }
+
+        holder.delete_file("data", "a.bin", 1).await.unwrap();
+        holder.delete_file("data", "b.bin", 2).await.unwrap();
+        holder.delete_file("old/sub", "c.bin", 2).await.unwrap();
+        holder.delete_folder("old", 1).await.unwrap();
+
+        let users = [AdminUser {
+            id: 2,
+            name: "admin".to_string(),
+            tg_user: None,
+        }];
+
+        let mut entries = trash_entries(&holder.snapshot(), &users);
+        entries.sort_by(|a, b| a.item.cmp(&b.item));
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|v| v.item.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["data/a.bin", "data/b.bin", "old", "old/sub/c.bin"]
+        );
+        assert!(entries[2].item.folder && entries[2].size == 10);
+        assert_eq!(entries[1].deleted_by_name.as_deref(), Some("admin"));
+        assert_eq!(entries[0].deleted_by, Some(1));
+        assert!(entries[0].deleted_by_name.is_none());
+
+        let item = |path: &str, folder| TrashItem {
+            path: path.to_string(),
+            folder,
+        };
+
+        assert!(restore(&holder, &[item("data/a.bin", false)], 1)
+            .await
+            .is_empty());
+        assert_eq!(
+            restore(&holder, &[item("data/a.bin", false)], 1).await,
+            vec!["data/a.bin is not in the trash"]
+        );
+        assert_eq!(
+            restore(&holder, &[item("old/sub/c.bin", false)], 1).await,
+            vec!["Can't restore old/sub/c.bin: old is deleted, restore it first"]
+        );
+        assert!(
+            file_info(&holder.snapshot(), "old/sub", "c.bin")
+                .unwrap()
+                .deleted
+        );
+
+        let errors = purge(
+            &holder,
+            &[
+                item("old", true),
+                item("old/sub/c.bin", false),
+                item("data/b.bin", false),
+            ],
+        )
+        .await;
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert!(trash_entries(&holder.snapshot(), &users).is_empty());
+        assert_eq!(holder.storage().list("").await.unwrap(), vec!["data/a.bin"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -2,32 +2,222 @@ use bincode::config;
 use bincode::error::{DecodeError, EncodeError};
 use byteorder::{LittleEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::io::{BufReader, Cursor, Read};
-use strum::{Display, EnumIter};
+use strum::{Display, EnumIter, IntoEnumIterator};
 use uuid::Uuid;
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Display, EnumIter, Serialize, Deserialize)]
+pub enum PatchNoteStatus {
+    #[default]
+    Draft,
+    Published,
+    Archived,
+}
+
+/// Language of patch note bodies, [`Locale::default`] body is [`PatchNote::data`]
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Default,
+    Display,
+    EnumIter,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    #[strum(to_string = "Russian")]
+    Ru,
+    #[strum(to_string = "English")]
+    En,
+    #[strum(to_string = "German")]
+    De,
+}
+
+impl Locale {
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::Ru => "ru",
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+
+    /// Accepts language tags like `de` or `de-AT`
+    pub fn from_code(code: &str) -> Option<Self> {
+        let language = code.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+
+        Self::iter().find(|v| v.code() == language)
+    }
+
+    /// Locales tried in order when the patch note has no body in `self`
+    pub fn fallbacks(self) -> Vec<Locale> {
+        let mut res = vec![self];
+
+        for v in [Locale::En, Locale::default()] {
+            if !res.contains(&v) {
+                res.push(v);
+            }
+        }
+
+        res
+    }
+}
+
+/// Patch notes saved before statuses were introduced were live
+fn legacy_patch_note_status() -> PatchNoteStatus {
+    PatchNoteStatus::Published
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct PatchNote {
     pub id: u32,
+    #[serde(default)]
+    pub title: String,
+    /// EasyMark body in [`Locale::default`]
     pub data: String,
+    /// Bodies in other locales
+    #[serde(default)]
+    pub translations: BTreeMap<Locale, String>,
+    #[serde(default = "legacy_patch_note_status")]
+    pub status: PatchNoteStatus,
+    /// Admin id
+    #[serde(default)]
+    pub author: u32,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+    #[serde(default)]
+    pub published_at: Option<i64>,
+    /// Draft is published by the server at this time
+    #[serde(default)]
+    pub publish_at: Option<i64>,
+    /// Deleted patch notes are kept and can be restored
+    #[serde(default)]
+    pub deleted: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TgUser {
-    id: u64,
-    user_name: String,
+impl PatchNote {
+    /// Body in `locale`, `None` if it is not translated
+    pub fn body(&self, locale: Locale) -> Option<&str> {
+        let body = if locale == Locale::default() {
+            &self.data
+        } else {
+            self.translations.get(&locale)?
+        };
+
+        (!body.trim().is_empty()).then_some(body.as_str())
+    }
+
+    /// Body in `locale` or in the first of its [`Locale::fallbacks`] which is translated
+    pub fn localized(&self, locale: Locale) -> (Locale, &str) {
+        locale
+            .fallbacks()
+            .into_iter()
+            .find_map(|v| self.body(v).map(|body| (v, body)))
+            .unwrap_or((Locale::default(), &self.data))
+    }
+
+    pub fn missing_locales(&self) -> Vec<Locale> {
+        Locale::iter().filter(|v| self.body(*v).is_none()).collect()
+    }
 }
 
-impl TgUser {
-    pub fn test() -> Self {
+/// Patch note as it was saved by [`ClientPacket::SavePatchNote`] or [`ClientPacket::AddPatchNote`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchNoteRevision {
+    pub id: u32,
+    /// Admin id
+    pub author: u32,
+    pub time: i64,
+    pub patch_note: PatchNote,
+}
+
+/// [`PatchNoteRevision`] without the patch note body
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchNoteRevisionInfo {
+    pub id: u32,
+    pub author: u32,
+    pub time: i64,
+    pub title: String,
+}
+
+impl From<&PatchNoteRevision> for PatchNoteRevisionInfo {
+    fn from(value: &PatchNoteRevision) -> Self {
         Self {
-            id: 1488,
-            user_name: "Test User".to_string(),
+            id: value.id,
+            author: value.author,
+            time: value.time,
+            title: value.patch_note.title.clone(),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchNoteQuery {
+    pub take: u32,
+    pub skip: u32,
+    pub with_deleted: bool,
+    pub status: Option<PatchNoteStatus>,
+    pub author: Option<u32>,
+}
+
+impl PatchNoteQuery {
+    pub fn matches(&self, patch_note: &PatchNote) -> bool {
+        (self.with_deleted || !patch_note.deleted)
+            && self.status.is_none_or(|v| v == patch_note.status)
+            && self.author.is_none_or(|v| v == patch_note.author)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TgUser {
+    pub id: u64,
+    pub user_name: String,
+}
+
+/// Payload of the Telegram login widget
+///
+/// See <https://core.telegram.org/widgets/login#checking-authorization>
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TgLoginData {
+    pub id: u64,
+    pub first_name: String,
+    pub last_name: Option<String>,
+    pub username: Option<String>,
+    pub photo_url: Option<String>,
+    pub auth_date: i64,
+    pub hash: String,
+}
+
+impl TgLoginData {
+    pub fn tg_user(&self) -> TgUser {
+        TgUser {
+            id: self.id,
+            user_name: self
+                .username
+                .clone()
+                .unwrap_or_else(|| self.first_name.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminUser {
+    pub id: u32,
+    pub name: String,
+    pub tg_user: Option<TgUser>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FileInfo {
     pub name: String,
@@ -37,6 +227,8 @@ pub struct FileInfo {
     pub updated_by: u32,
     pub skip_hash_check: bool,
     pub deleted: bool,
+    /// Not served because the stored blob is broken
+    pub quarantined: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -49,6 +241,147 @@ pub struct FolderInfo {
     pub deleted: bool,
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Serialize, Deserialize)]
+pub enum AuditAction {
+    CreateFolder,
+    RemoveFolder,
+    AddFile,
+    RemoveFile,
+    SkipFileHashCheck,
+    AddPatchNote,
+    SavePatchNote,
+    ChangePatchNoteStatus,
+    DeletePatchNote,
+    RestorePatchNote,
+    RestorePatchNoteRevision,
+    UploadMedia,
+    CreateAdminUser,
+    LinkTelegram,
+    CollectGarbage,
+    Scrub,
+    MoveFile,
+    MoveFolder,
+    RestoreFromTrash,
+    PurgeFromTrash,
+}
+
+/// Single record of the admin actions audit trail
+///
+/// `before` and `after` hold RON snapshots of the affected entity
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditRecord {
+    pub id: u32,
+    pub user_id: u32,
+    pub addr: String,
+    pub time: i64,
+    pub action: AuditAction,
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Game clients activity for one day
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DayStatistics {
+    /// Unix timestamp of the day start, UTC
+    pub day: i64,
+    pub manifest_fetches: u64,
+    pub downloads: u64,
+    pub bytes_served: u64,
+    pub unique_installs: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileDownloads {
+    pub path: String,
+    pub downloads: u64,
+    pub bytes_served: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StatisticsSeries {
+    /// One entry per day, oldest first, days without activity included
+    pub days: Vec<DayStatistics>,
+    /// Installations seen in the whole period
+    pub unique_installs: u32,
+    /// Most downloaded files in the period
+    pub top_files: Vec<FileDownloads>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectedAdmin {
+    pub addr: String,
+    /// `0` if the admin is not authenticated
+    pub user_id: u32,
+    pub connected_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DashboardSummary {
+    pub total_size: u64,
+    pub files_count: u32,
+    pub patch_notes_count: u32,
+    pub connected_admins: Vec<ConnectedAdmin>,
+    /// Newest last
+    pub recent_errors: Vec<Log>,
+    pub statistics: StatisticsSeries,
+}
+
+/// Result of a garbage collection of game files
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub time: i64,
+    /// Files deleted longer than the retention period ago, with files of such folders
+    pub purged_files: Vec<String>,
+    pub purged_folders: Vec<String>,
+    /// Original size of purged files
+    pub purged_size: u64,
+    /// Blobs no file refers to
+    pub orphan_blobs: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TrashItem {
+    pub path: String,
+    pub folder: bool,
+}
+
+/// Deleted file or folder, folders are listed with everything in them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashEntry {
+    pub item: TrashItem,
+    pub size: u64,
+    /// `None` for entries deleted before the time was tracked
+    pub deleted_at: Option<i64>,
+    pub deleted_by: Option<u32>,
+    /// Name of the admin, `None` for removed admins and the server itself
+    pub deleted_by_name: Option<String>,
+    /// When the background GC purges the entry, `None` if it never does
+    pub purge_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrokenFile {
+    pub path: String,
+    pub problem: String,
+}
+
+/// Result of an integrity scrub of game files
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ScrubReport {
+    pub time: i64,
+    /// Whether broken files were quarantined
+    pub quarantine: bool,
+    pub checked: u32,
+    /// Original size of checked files
+    pub checked_size: u64,
+    pub broken: Vec<BrokenFile>,
+    /// Quarantined files which are intact now
+    pub released: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ClientPacket {
     FileList {
@@ -72,28 +405,113 @@ pub enum ClientPacket {
         dir: String,
         name: String,
     },
-    PatchNotes {
-        take: u32,
-        skip: u32,
-    },
+    /// Newest first
+    PatchNotes(PatchNoteQuery),
     RequestEditPatchNote {
         id: u32,
     },
     SavePatchNote {
         id: u32,
+        title: String,
         data: String,
+        translations: BTreeMap<Locale, String>,
+        publish_at: Option<i64>,
+    },
+    SetPatchNoteStatus {
+        id: u32,
+        status: PatchNoteStatus,
     },
     DeletePatchNote {
         id: u32,
     },
+    RestorePatchNote {
+        id: u32,
+    },
+    /// Newest first
+    PatchNoteRevisions {
+        id: u32,
+    },
+    PatchNoteRevision {
+        revision: u32,
+    },
+    /// Saves the revision title and bodies as a new revision
+    RestorePatchNoteRevision {
+        revision: u32,
+    },
+    /// Image for patch notes, answered with [`ServerPacket::MediaUploaded`]
+    UploadMedia {
+        name: String,
+        data: Vec<u8>,
+    },
+    /// New patch note is a draft
     AddPatchNote {
+        title: String,
         data: String,
+        translations: BTreeMap<Locale, String>,
+        publish_at: Option<i64>,
+    },
+    /// `subscribe` enables or disables pushing of new logs with [`ServerPacket::NewLogs`]
+    Logs {
+        query: LogQuery,
+        subscribe: bool,
     },
-    Logs,
     SkipFileHashCheck {
         dir: String,
         name: String,
     },
+    AuditLog {
+        take: u32,
+        skip: u32,
+    },
+    TelegramLogin(TgLoginData),
+    AdminUsers,
+    CreateAdminUser {
+        name: String,
+        tg_user: Option<TgUser>,
+    },
+    /// `tg_user: None` unlinks Telegram account
+    LinkTelegram {
+        user_id: u32,
+        tg_user: Option<TgUser>,
+    },
+    /// Statistics for the last `days` days, including today
+    Statistics {
+        days: u32,
+    },
+    /// Dashboard with statistics for the last `days` days
+    Dashboard {
+        days: u32,
+    },
+    /// Answered with [`ServerPacket::GcReport`], `dry_run` only reports what would be removed
+    CollectGarbage {
+        dry_run: bool,
+    },
+    /// Answered with [`ServerPacket::ScrubReport`] of the last scrub
+    LastScrubReport,
+    /// Answered with [`ServerPacket::ScrubReport`], `quarantine` stops serving broken files
+    Scrub {
+        quarantine: bool,
+    },
+    /// Moves or renames, `from` and `to` are full paths. Answered with the file list of the
+    /// `from` folder or [`ServerPacket::MoveFailed`]
+    MoveFile {
+        from: String,
+        to: String,
+    },
+    /// Like [`ClientPacket::MoveFile`]
+    MoveFolder {
+        from: String,
+        to: String,
+    },
+    /// Answered with [`ServerPacket::Trash`]
+    Trash,
+    RestoreFromTrash {
+        items: Vec<TrashItem>,
+    },
+    /// Removes the entries and their blobs right away
+    PurgeFromTrash {
+        items: Vec<TrashItem>,
+    },
 }
 
 impl ClientPacket {
@@ -181,13 +599,24 @@ pub enum ServerPacket {
         files: Vec<FileInfo>,
         folders: Vec<FolderInfo>,
     },
-    Logs(Vec<Log>),
+    Logs {
+        total: u32,
+        /// Producers of logs in the requested time range
+        producers: Vec<String>,
+        logs: Vec<Log>,
+    },
+    NewLogs(Vec<Log>),
     FileUploaded {
         id: Uuid,
     },
     FileProceeded {
         id: Uuid,
     },
+    /// The upload was received, but not saved
+    FileFailed {
+        id: Uuid,
+        reason: String,
+    },
 
     PatchNotes {
         take: u32,
@@ -196,6 +625,54 @@ pub enum ServerPacket {
         patch_notes: Vec<PatchNote>,
     },
     OpenPatchNote(PatchNote),
+    PatchNoteRevisions {
+        id: u32,
+        revisions: Vec<PatchNoteRevisionInfo>,
+    },
+    /// `url` is a path on the server like `/media/{uuid}.png`
+    MediaUploaded {
+        name: String,
+        url: String,
+    },
+    MediaUploadFailed {
+        name: String,
+        reason: String,
+    },
+    PatchNoteRevision {
+        revision: PatchNoteRevision,
+        current: PatchNote,
+    },
+    AuditLog {
+        take: u32,
+        skip: u32,
+        total: u32,
+        records: Vec<AuditRecord>,
+    },
+    /// Sent right after connection if server requires Telegram login
+    AuthRequired {
+        bot_id: u64,
+    },
+    LoggedIn(AdminUser),
+    AuthFailed(String),
+    AdminUsers(Vec<AdminUser>),
+    Statistics(StatisticsSeries),
+    Dashboard(DashboardSummary),
+    GcReport(GcReport),
+    /// `None` until the first scrub is finished
+    ScrubReport(Option<ScrubReport>),
+    MoveFailed {
+        from: String,
+        to: String,
+        folder: bool,
+        reason: String,
+    },
+    /// A change was not saved
+    ActionFailed(String),
+    /// Every deleted entry, oldest deletions first. `errors` are of the action which was asked for
+    Trash {
+        entries: Vec<TrashEntry>,
+        errors: Vec<String>,
+    },
 }
 
 impl ServerPacket {
@@ -207,25 +684,19 @@ impl ServerPacket {
     }
 }
 
+/// Keeps last [`LogHolder::MAX_LOGS`] server and app logs
 #[derive(Debug)]
 pub struct LogHolder {
     pub producers: HashSet<String>,
-    pub server_logs: Vec<Log>,
-    pub app_logs: Vec<Log>,
+    pub server_logs: VecDeque<Log>,
+    pub app_logs: VecDeque<Log>,
+    /// Total server logs matching [`LogHolder::query`]
+    pub total: u32,
 
-    pub producer_filter: String,
+    pub query: LogQuery,
     pub(crate) max_log_level: LogLevel,
-    pub level_filter: LogLevelFilter,
-}
-
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Display, EnumIter)]
-#[repr(u8)]
-pub enum LogLevelFilter {
-    Debug,
-    Info,
-    Warning,
-    Error,
-    All = 255,
+    /// New server logs are pushed by the server
+    pub live: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -236,7 +707,9 @@ pub struct Log {
     pub time: i64,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Display, EnumIter, Serialize, Deserialize,
+)]
 #[repr(u8)]
 pub enum LogLevel {
     Debug,
@@ -245,6 +718,47 @@ pub enum LogLevel {
     Error,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogQuery {
+    /// Unix timestamp, inclusive
+    pub from: Option<i64>,
+    /// Unix timestamp, exclusive
+    pub to: Option<i64>,
+    pub min_level: LogLevel,
+    pub producer: Option<String>,
+    /// Case-insensitive substring of the log text
+    pub search: String,
+    pub take: u32,
+    /// Counted from the newest log
+    pub skip: u32,
+}
+
+impl Default for LogQuery {
+    fn default() -> Self {
+        Self {
+            from: None,
+            to: None,
+            min_level: LogLevel::Info,
+            producer: None,
+            search: String::new(),
+            take: 200,
+            skip: 0,
+        }
+    }
+}
+
+impl LogQuery {
+    /// Checks every filter but paging
+    pub fn matches(&self, log: &Log) -> bool {
+        self.from.is_none_or(|v| log.time >= v)
+            && self.to.is_none_or(|v| log.time < v)
+            && log.level >= self.min_level
+            && self.producer.as_ref().is_none_or(|v| v == &log.producer)
+            && (self.search.is_empty()
+                || log.log.to_lowercase().contains(&self.search.to_lowercase()))
+    }
+}
+
 impl Default for LogHolder {
     fn default() -> Self {
         Self::new()
@@ -252,23 +766,44 @@ impl Default for LogHolder {
 }
 
 impl LogHolder {
-    pub const ALL: &'static str = "All";
+    pub const MAX_LOGS: usize = 5_000;
 
     pub fn new() -> Self {
-        let mut producers = HashSet::new();
-        producers.insert(Self::ALL.to_string());
-
         Self {
-            producers,
-            server_logs: vec![],
-            app_logs: vec![],
-            producer_filter: LogHolder::ALL.to_string(),
+            producers: HashSet::new(),
+            server_logs: VecDeque::new(),
+            app_logs: VecDeque::new(),
+            total: 0,
+            query: LogQuery::default(),
             max_log_level: LogLevel::Info,
-            level_filter: LogLevelFilter::Info,
+            live: true,
         }
     }
 
-    pub fn add_server(&mut self, logs: Vec<Log>) {
+    pub fn set_server(&mut self, total: u32, producers: Vec<String>, logs: Vec<Log>) {
+        self.server_logs.clear();
+        self.total = total;
+        self.producers.extend(producers);
+
+        self.add_server(logs);
+    }
+
+    /// Adds pushed logs, if they match current query and the newest page is shown
+    pub fn add_new_server(&mut self, logs: Vec<Log>) {
+        for log in logs {
+            if !self.producers.contains(&log.producer) {
+                self.producers.insert(log.producer.clone());
+            }
+
+            if self.query.skip == 0 && self.query.matches(&log) {
+                self.total += 1;
+
+                self.add_server(vec![log]);
+            }
+        }
+    }
+
+    fn add_server(&mut self, logs: Vec<Log>) {
         for log in logs {
             self.max_log_level = self.max_log_level.max(log.level);
 
@@ -276,11 +811,19 @@ impl LogHolder {
                 self.producers.insert(log.producer.clone());
             }
 
-            self.server_logs.push(log);
+            if self.server_logs.len() == Self::MAX_LOGS {
+                self.server_logs.pop_front();
+            }
+
+            self.server_logs.push_back(log);
         }
     }
 
     pub fn add_app(&mut self, log: Log) {
-        self.app_logs.push(log);
+        if self.app_logs.len() == Self::MAX_LOGS {
+            self.app_logs.pop_front();
+        }
+
+        self.app_logs.push_back(log);
     }
 }
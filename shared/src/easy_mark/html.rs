@@ -0,0 +1,297 @@
+//! Renders `EasyMark` to HTML for the public patch note feeds and launchers
+
+use crate::easy_mark::parser::{Item, Parser, Style};
+use std::fmt::Write;
+
+/// Renders `EasyMark` source to an HTML fragment.
+///
+/// All text is escaped, links are limited to `http(s)`, `mailto` and relative urls.
+pub fn to_html(source: &str) -> String {
+    to_html_with_base(source, "")
+}
+
+/// Like [`to_html`], urls starting with `/` are prefixed with `base_url`.
+///
+/// Feed readers show the HTML outside the site, so `/media/` images need the host.
+pub fn to_html_with_base(source: &str, base_url: &str) -> String {
+    let mut writer = HtmlWriter {
+        base_url: base_url.trim_end_matches('/'),
+        ..Default::default()
+    };
+    let mut line = vec![];
+
+    for item in Parser::new(source) {
+        if item == Item::Newline {
+            writer.line(&line);
+            line.clear();
+        } else {
+            line.push(item);
+        }
+    }
+
+    writer.line(&line);
+    writer.close_block();
+
+    writer.html
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Block {
+    Paragraph,
+    Quote,
+    BulletList,
+    NumberedList,
+}
+
+impl Block {
+    fn tag(self) -> &'static str {
+        match self {
+            Block::Paragraph => "p",
+            Block::Quote => "blockquote",
+            Block::BulletList => "ul",
+            Block::NumberedList => "ol",
+        }
+    }
+}
+
+#[derive(Default)]
+struct HtmlWriter<'a> {
+    html: String,
+    block: Option<Block>,
+    base_url: &'a str,
+}
+
+impl HtmlWriter<'_> {
+    fn line(&mut self, items: &[Item]) {
+        let items = match items
+            .iter()
+            .position(|v| !matches!(v, Item::Indentation(_)))
+        {
+            Some(start) => &items[start..],
+            None => {
+                self.close_block();
+                return;
+            }
+        };
+
+        match items[0] {
+            Item::Separator => {
+                self.close_block();
+                self.html.push_str("<hr>\n");
+                self.line(&items[1..]);
+            }
+            Item::CodeBlock(language, code) => {
+                self.close_block();
+
+                if language.is_empty() {
+                    self.html.push_str("<pre><code>");
+                } else {
+                    let _ = write!(
+                        self.html,
+                        "<pre><code class=\"language-{}\">",
+                        escape_html(language)
+                    );
+                }
+
+                self.html.push_str(&escape_html(code));
+                self.html.push_str("</code></pre>\n");
+                self.line(&items[1..]);
+            }
+            Item::BulletPoint => {
+                self.open_block(Block::BulletList);
+                self.html.push_str("<li>");
+                self.inline(&items[1..]);
+                self.html.push_str("</li>\n");
+            }
+            Item::NumberedPoint(number) => {
+                self.open_block(Block::NumberedList);
+                let _ = write!(self.html, "<li value=\"{}\">", escape_html(number));
+                self.inline(&items[1..]);
+                self.html.push_str("</li>\n");
+            }
+            Item::QuoteIndent => {
+                self.continue_block(Block::Quote);
+                self.inline(&items[1..]);
+            }
+            Item::Text(style, _) | Item::Hyperlink(style, _, _) if style.heading => {
+                self.close_block();
+                self.html.push_str("<h1>");
+                self.inline(items);
+                self.html.push_str("</h1>\n");
+            }
+            _ => {
+                self.continue_block(Block::Paragraph);
+                self.inline(items);
+            }
+        }
+    }
+
+    fn open_block(&mut self, block: Block) {
+        if self.block != Some(block) {
+            self.close_block();
+            let _ = writeln!(self.html, "<{}>", block.tag());
+            self.block = Some(block);
+        }
+    }
+
+    /// Opens `block` or starts a new line in it if it is already open
+    fn continue_block(&mut self, block: Block) {
+        if self.block == Some(block) {
+            self.html.push_str("<br>\n");
+        } else {
+            self.close_block();
+            let _ = write!(self.html, "<{}>", block.tag());
+            self.block = Some(block);
+        }
+    }
+
+    fn close_block(&mut self) {
+        if let Some(block) = self.block.take() {
+            let _ = writeln!(self.html, "</{}>", block.tag());
+        }
+    }
+
+    fn inline(&mut self, items: &[Item]) {
+        for item in items {
+            match *item {
+                Item::Text(style, text) => {
+                    let text = escape_html(text);
+                    self.styled(style, &text);
+                }
+                Item::Hyperlink(style, text, url) => {
+                    let link = format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape_html(&self.url(url)),
+                        escape_html(text)
+                    );
+                    self.styled(style, &link);
+                }
+                Item::Image(alt, url) => {
+                    let _ = write!(
+                        self.html,
+                        "<img src=\"{}\" alt=\"{}\">",
+                        escape_html(&self.url(url)),
+                        escape_html(alt)
+                    );
+                }
+                Item::CodeBlock(_, code) => {
+                    let _ = write!(self.html, "<code>{}</code>", escape_html(code));
+                }
+                Item::Newline
+                | Item::Indentation(_)
+                | Item::QuoteIndent
+                | Item::BulletPoint
+                | Item::NumberedPoint(_)
+                | Item::Separator => {}
+            }
+        }
+    }
+
+    fn url(&self, url: &str) -> String {
+        let url = safe_url(url);
+
+        if url.starts_with('/') && !url.starts_with("//") {
+            format!("{}{url}", self.base_url)
+        } else {
+            url.to_string()
+        }
+    }
+
+    fn styled(&mut self, style: Style, html: &str) {
+        let tags = [
+            (style.code, "code"),
+            (style.strong, "strong"),
+            (style.italics, "em"),
+            (style.underline, "u"),
+            (style.strikethrough, "del"),
+            (style.small, "small"),
+            (style.raised, "sup"),
+        ];
+
+        for (_, tag) in tags.iter().filter(|v| v.0) {
+            let _ = write!(self.html, "<{tag}>");
+        }
+
+        self.html.push_str(html);
+
+        for (_, tag) in tags.iter().rev().filter(|v| v.0) {
+            let _ = write!(self.html, "</{tag}>");
+        }
+    }
+}
+
+/// Replaces urls with a script or unknown scheme by `#`
+fn safe_url(url: &str) -> &str {
+    let url = url.trim();
+
+    match url.find(':') {
+        Some(scheme_end) if !url[..scheme_end].contains(['/', '?', '#']) => {
+            let scheme = url[..scheme_end].to_ascii_lowercase();
+
+            if ["http", "https", "mailto"].contains(&scheme.as_str()) {
+                url
+            } else {
+                "#"
+            }
+        }
+        _ => url,
+    }
+}
+
+#[test]
+fn test_easy_mark_html() {
+    let html = to_html(
+        "# Patch 1.2 & \"fixes\"\n\
+         Fixed **crash** when a < b [bug](https://a.b/?x=1&y=\"2\")\n\
+         second line [xss](javascript:alert(1)\n\
+         ![\"Map\"](/media/map.png)\n\
+         \n\
+         - one\n\
+         - `two`\n\
+         1. first\n\
+         ```rust\nfn main() {}\n```\n\
+         ---",
+    );
+
+    assert_eq!(
+        html,
+        "<h1>Patch 1.2 &amp; &quot;fixes&quot;</h1>\n\
+         <p>Fixed <strong>crash</strong> when a &lt; b <a href=\"https://a.b/?x=1&amp;y=&quot;2&quot;\">bug</a><br>\n\
+         second line <a href=\"#\">xss</a><br>\n\
+         <img src=\"/media/map.png\" alt=\"&quot;Map&quot;\"></p>\n\
+         <ul>\n<li>one</li>\n<li><code>two</code></li>\n</ul>\n\
+         <ol>\n<li value=\"1\">first</li>\n</ol>\n\
+         <pre><code class=\"language-rust\">fn main() {}</code></pre>\n\
+         <hr>\n"
+    );
+}
+
+#[test]
+fn test_easy_mark_html_base_url() {
+    let source = "![map](/media/map.png) [patch](/patch_notes/1) [site](//a.b/c) [x](x.html)";
+
+    assert_eq!(
+        to_html_with_base(source, "https://example.com/"),
+        "<p><img src=\"https://example.com/media/map.png\" alt=\"map\"> \
+         <a href=\"https://example.com/patch_notes/1\">patch</a> \
+         <a href=\"//a.b/c\">site</a> <a href=\"x.html\">x</a></p>\n"
+    );
+    assert_eq!(to_html_with_base(source, ""), to_html(source));
+}
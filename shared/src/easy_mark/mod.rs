@@ -0,0 +1,7 @@
+//! `EasyMark` markup of patch notes, shared by the admin panel preview, the server and launchers
+
+pub mod html;
+pub mod parser;
+
+pub use html::{to_html, to_html_with_base};
+pub use parser::{Item, Parser, Style};
@@ -19,6 +19,9 @@ pub enum Item<'a> {
     /// title, url
     Hyperlink(Style, &'a str, &'a str),
 
+    /// `![alt](url)`: alt, url
+    Image(&'a str, &'a str),
+
     /// leading space before e.g. a [`Self::BulletPoint`].
     Indentation(usize),
 
@@ -74,7 +77,7 @@ pub struct Style {
 ///
 /// # Example:
 /// ```
-/// # use egui_demo_lib::easy_mark::parser::Parser;
+/// # use shared::easy_mark::Parser;
 /// for item in Parser::new("Hello *world*!") {
 /// }
 ///
@@ -154,6 +157,21 @@ impl<'a> Parser<'a> {
         None
     }
 
+    /// `![alt](url)`
+    fn image(&mut self) -> Option<Item<'a>> {
+        let rest = self.s.strip_prefix("![")?;
+        let this_line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        let bracket_end = this_line.find(']')?;
+        let url_start = this_line[bracket_end + 1..].strip_prefix('(')?;
+        let parens_end = url_start.find(')')?;
+
+        let alt = &this_line[..bracket_end];
+        let url = &url_start[..parens_end];
+        self.s = &rest[bracket_end + 2 + parens_end + 1..];
+        self.start_of_line = false;
+        Some(Item::Image(alt, url))
+    }
+
     /// `<url>` or `[link](url)`
     fn url(&mut self) -> Option<Item<'a>> {
         if self.s.starts_with('<') {
@@ -311,6 +329,11 @@ impl<'a> Iterator for Parser<'a> {
             //     continue;
             // }
 
+            // `![alt](url)`
+            if let Some(item) = self.image() {
+                return Some(item);
+            }
+
             // `<url>` or `[link](url)`
             if let Some(item) = self.url() {
                 return Some(item);
@@ -319,7 +342,7 @@ impl<'a> Iterator for Parser<'a> {
             // Swallow everything up to the next special character:
             let end = self
                 .s
-                .find(&['*', '`', '~', '_', '/', '$', '^', '\\', '<', '[', '\n'][..])
+                .find(&['*', '`', '~', '_', '/', '$', '^', '\\', '<', '[', '!', '\n'][..])
                 .map_or_else(|| self.s.len(), |special| special.max(1));
 
             let item = Item::Text(self.style, &self.s[..end]);
@@ -332,7 +355,7 @@ impl<'a> Iterator for Parser<'a> {
 
 #[test]
 fn test_easy_mark_parser() {
-    let items: Vec<_> = Parser::new("~strikethrough `code`~").collect();
+    let items: Vec<_> = Parser::new("~~strikethrough `code`~~").collect();
     assert_eq!(
         items,
         vec![
@@ -354,3 +377,25 @@ fn test_easy_mark_parser() {
         ]
     );
 }
+
+#[test]
+fn test_easy_mark_image() {
+    let items: Vec<_> = Parser::new("New map!\n![Harbor](/media/harbor.png) *day*").collect();
+    assert_eq!(
+        items,
+        vec![
+            Item::Text(Style::default(), "New map"),
+            Item::Text(Style::default(), "!"),
+            Item::Newline,
+            Item::Image("Harbor", "/media/harbor.png"),
+            Item::Text(Style::default(), " "),
+            Item::Text(
+                Style {
+                    italics: true,
+                    ..Default::default()
+                },
+                "day"
+            ),
+        ]
+    );
+}
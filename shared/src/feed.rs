@@ -0,0 +1,24 @@
+use crate::admin_panel::Locale;
+use serde::{Deserialize, Serialize};
+
+/// Published patch note, as served to launchers and the website
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublishedPatchNote {
+    pub id: u32,
+    pub title: String,
+    pub published_at: i64,
+    pub updated_at: i64,
+    /// Locale of `data`, differs from the requested one when the patch note is not translated
+    pub locale: Locale,
+    /// EasyMark source
+    pub data: String,
+}
+
+/// Page of published patch notes, newest first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchNotesPage {
+    pub total: u32,
+    pub take: u32,
+    pub skip: u32,
+    pub patch_notes: Vec<PublishedPatchNote>,
+}
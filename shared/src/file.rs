@@ -1,12 +1,19 @@
 use chrono::Utc;
 use seahash::SeaHasher;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hasher;
 use std::io::Write;
 
 pub static COMPRESSED_FOLDER_NAME: &str = "compressed";
 pub static ROOT_FOLDER_INFO_FILE_NAME: &str = "root_folder_server_info.ron";
+/// [`crate::versioned::Versioned`] version of [`ROOT_FOLDER_INFO_FILE_NAME`], the server migrates older ones
+///
+/// Version 1 lists recorded compressed sizes of files, later ones record original sizes
+pub const ROOT_FOLDER_INFO_VERSION: u32 = 2;
+/// Blob key prefix of files compressed by the preparator, the server moves them to their keys when
+/// it merges [`ROOT_FOLDER_INFO_FILE_NAME`]. GC keeps blobs under it
+pub static PREPARED_PREFIX: &str = ".prepared/";
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FileList {
@@ -25,9 +32,18 @@ pub struct ServerFolderInfo {
     pub files_count: u32,
     pub created_at: i64,
     pub updated_at: i64,
+    #[serde(default)]
+    pub updated_by: u32,
     pub deleted: bool,
-    pub files: HashMap<String, ServerFileInfo>,
-    pub folders: HashMap<String, ServerFolderInfo>,
+    /// Deleted folders are purged with their files after the GC retention period
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+    /// Admin who deleted the folder, 0 is the server itself
+    #[serde(default)]
+    pub deleted_by: Option<u32>,
+    /// Sorted so the written list is the same for the same tree
+    pub files: BTreeMap<String, ServerFileInfo>,
+    pub folders: BTreeMap<String, ServerFolderInfo>,
 }
 
 impl Default for ServerFolderInfo {
@@ -38,7 +54,10 @@ impl Default for ServerFolderInfo {
             files_count: 0,
             created_at: t,
             updated_at: t,
+            updated_by: 0,
             deleted: false,
+            deleted_at: None,
+            deleted_by: None,
             files: Default::default(),
             folders: Default::default(),
         }
@@ -77,6 +96,19 @@ pub struct ServerFileInfo {
     /// хотим проверять хэш - только наличие файла
     pub skip_hash_check: bool,
     pub deleted: bool,
+    #[serde(default)]
+    pub updated_by: u32,
+    /// Deleted files are purged after the GC retention period
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+    #[serde(default)]
+    pub deleted_by: Option<u32>,
+    /// Set by the integrity scrub when the stored blob is broken, such files are not served
+    #[serde(default)]
+    pub quarantined: bool,
+    /// Modification time of the source file, the incremental preparator skips unchanged ones
+    #[serde(default)]
+    pub source_modified_at: Option<i64>,
 }
 
 pub fn hash_of(bytes_too_hash: &[u8]) -> String {
@@ -109,3 +141,221 @@ pub fn decompress_in_mem<W: Write>(bytes_to_decompress: &[u8], buff: &mut W) ->
 
     Ok(())
 }
+
+/// Counts and hashes the bytes written to it, the hash is the one of [`hash_of`]
+#[derive(Default)]
+struct HashWriter {
+    hasher: SeaHasher,
+    size: u64,
+}
+
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.hasher.write(buf);
+        self.size += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decompresses a blob fed in chunks, without keeping it in memory
+pub struct DecompressedHasher(flate2::write::ZlibDecoder<HashWriter>);
+
+impl Default for DecompressedHasher {
+    fn default() -> Self {
+        Self(flate2::write::ZlibDecoder::new(HashWriter::default()))
+    }
+}
+
+impl DecompressedHasher {
+    pub fn update(&mut self, compressed: &[u8]) -> anyhow::Result<()> {
+        self.0.write_all(compressed)?;
+
+        Ok(())
+    }
+
+    /// Size and [`hash_of`] of the decompressed data
+    pub fn finish(self) -> anyhow::Result<(u64, String)> {
+        let writer = self.0.finish()?;
+
+        Ok((writer.size, format!("{:016x}", writer.hasher.finish())))
+    }
+}
+
+/// What a client has to do to match the files of the server
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncPlan {
+    /// Server paths which have no good local copy
+    pub download: Vec<String>,
+    /// (local path, server path) of files which were moved on the server, renamed locally instead
+    /// of downloading them again
+    pub rename: Vec<(String, String)>,
+    /// Local paths of files deleted on the server
+    pub remove: Vec<String>,
+}
+
+/// Compares `local` paths and hashes of client files with the server tree
+///
+/// A served file which is missing locally is taken from a local file with the same hash which is
+/// not served anymore, moves on the server keep hashes. Local files unknown to the server are
+/// left alone
+pub fn plan_sync(local: &HashMap<String, String>, root: &ServerFolderInfo) -> SyncPlan {
+    fn walk(
+        path: &str,
+        folder: &ServerFolderInfo,
+        deleted: bool,
+        files: &mut Vec<(String, bool, ServerFileInfo)>,
+    ) {
+        let deleted = deleted || folder.deleted;
+
+        for (name, file) in &folder.files {
+            files.push((join(path, name), deleted || file.deleted, file.clone()));
+        }
+
+        for (name, child) in &folder.folders {
+            walk(&join(path, name), child, deleted, files);
+        }
+    }
+
+    fn join(path: &str, name: &str) -> String {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{path}/{name}")
+        }
+    }
+
+    let mut files = vec![];
+
+    walk("", root, false, &mut files);
+
+    // Deterministic choice between local copies with the same hash
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let served: HashMap<&str, &ServerFileInfo> = files
+        .iter()
+        .filter(|(_, deleted, _)| !*deleted)
+        .map(|(path, _, file)| (path.as_str(), file))
+        .collect();
+
+    let mut stale: Vec<(&String, &String)> = local
+        .iter()
+        .filter(|(path, _)| !served.contains_key(path.as_str()))
+        .collect();
+    stale.sort();
+
+    let mut plan = SyncPlan::default();
+
+    for (path, file) in files
+        .iter()
+        .filter(|(_, deleted, _)| !*deleted)
+        .map(|v| (&v.0, &v.2))
+    {
+        let up_to_date = local
+            .get(path)
+            .is_some_and(|hash| file.skip_hash_check || *hash == file.hash);
+
+        if up_to_date {
+            continue;
+        }
+
+        let moved = if local.contains_key(path) {
+            None
+        } else {
+            stale.iter().position(|(_, hash)| **hash == file.hash)
+        };
+
+        match moved {
+            Some(i) => {
+                let (local_path, _) = stale.remove(i);
+
+                plan.rename.push((local_path.clone(), path.clone()));
+            }
+            None => plan.download.push(path.clone()),
+        }
+    }
+
+    let deleted: HashSet<&str> = files
+        .iter()
+        .filter(|(_, deleted, _)| *deleted)
+        .map(|(path, _, _)| path.as_str())
+        .collect();
+
+    plan.remove = stale
+        .into_iter()
+        .filter(|(path, _)| deleted.contains(path.as_str()))
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompressed_hash_matches_in_chunks() {
+        let data: Vec<u8> = (0..300_000u32).map(|v| (v * 7 % 253) as u8).collect();
+
+        let mut compressed = vec![];
+        compress_in_mem(&data, &mut compressed).unwrap();
+
+        for chunk_size in [1, 7, 4096, compressed.len()] {
+            let mut hasher = DecompressedHasher::default();
+
+            for chunk in compressed.chunks(chunk_size) {
+                hasher.update(chunk).unwrap();
+            }
+
+            assert_eq!(
+                hasher.finish().unwrap(),
+                (data.len() as u64, hash_of(&data))
+            );
+        }
+    }
+
+    #[test]
+    fn moved_files_are_renamed_locally() {
+        let file = |hash: &str, deleted: bool| ServerFileInfo {
+            hash: hash.to_string(),
+            deleted,
+            ..Default::default()
+        };
+
+        let mut root = ServerFolderInfo::default();
+        let mut data = ServerFolderInfo::default();
+
+        data.files.insert("moved.bin".to_string(), file("1", false));
+        data.files
+            .insert("changed.bin".to_string(), file("2", false));
+        data.files.insert("same.bin".to_string(), file("3", false));
+        data.files
+            .insert("removed.bin".to_string(), file("4", true));
+        root.folders.insert("data".to_string(), data);
+
+        let local = HashMap::from(
+            [
+                ("old/moved.bin", "1"),
+                ("data/changed.bin", "old"),
+                ("data/same.bin", "3"),
+                ("data/removed.bin", "4"),
+                ("saves/user.sav", "5"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+
+        assert_eq!(
+            plan_sync(&local, &root),
+            SyncPlan {
+                download: vec!["data/changed.bin".to_string()],
+                rename: vec![("old/moved.bin".to_string(), "data/moved.bin".to_string())],
+                remove: vec!["data/removed.bin".to_string()],
+            }
+        );
+    }
+}
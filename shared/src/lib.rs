@@ -1,3 +1,8 @@
 pub mod admin_panel;
+pub mod easy_mark;
+pub mod feed;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod file;
+#[cfg(feature = "storage")]
+pub mod storage;
+pub mod versioned;
@@ -0,0 +1,159 @@
+use super::{check_key, clamp_range, not_found, Blob, BlobStorage, BlobStream};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Unfinished writes, skipped by [`FsStorage::list`]
+const TMP_DIR: &str = ".tmp";
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Blobs are plain files under `root`
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        check_key(key)?;
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStorage for FsStorage {
+    async fn put(&self, key: &str, mut data: BlobStream) -> io::Result<u64> {
+        let path = self.path(key)?;
+
+        let tmp_dir = self.root.join(TMP_DIR);
+        tokio::fs::create_dir_all(&tmp_dir).await?;
+
+        let tmp_path = tmp_dir.join(uuid::Uuid::new_v4().to_string());
+
+        let write = async {
+            let mut file = tokio::fs::File::create(&tmp_path).await?;
+            let mut written = 0;
+
+            while let Some(chunk) = data.next().await {
+                let chunk = chunk?;
+
+                file.write_all(&chunk).await?;
+                written += chunk.len() as u64;
+            }
+
+            file.sync_all().await?;
+
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+
+            // Rename is atomic, so readers get either the old or the new blob
+            tokio::fs::rename(&tmp_path, &path).await?;
+
+            Ok(written)
+        };
+
+        let res = write.await;
+
+        if res.is_err() {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+        }
+
+        res
+    }
+
+    async fn get(&self, key: &str, range: Option<Range<u64>>) -> io::Result<Blob> {
+        let mut file = match tokio::fs::File::open(self.path(key)?).await {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(not_found(key)),
+            Err(e) => return Err(e),
+        };
+
+        let total = file.metadata().await?.len();
+        let range = clamp_range(range, total);
+
+        file.seek(io::SeekFrom::Start(range.start)).await?;
+
+        let size = range.end - range.start;
+        let reader = file.take(size);
+
+        let stream = futures::stream::try_unfold(reader, |mut reader| async move {
+            let mut buf = vec![0; READ_CHUNK];
+            let read = reader.read(&mut buf).await?;
+
+            if read == 0 {
+                return Ok(None);
+            }
+
+            buf.truncate(read);
+
+            Ok(Some((Bytes::from(buf), reader)))
+        });
+
+        Ok(Blob {
+            size,
+            total,
+            stream: Box::pin(stream),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> io::Result<bool> {
+        Ok(tokio::fs::metadata(self.path(key)?)
+            .await
+            .is_ok_and(|v| v.is_file()))
+    }
+
+    async fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut res = vec![];
+        let mut dirs = vec![(self.root.clone(), String::new())];
+
+        while let Some((dir, key_prefix)) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(v) => v,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+
+                if key_prefix.is_empty() && name == TMP_DIR {
+                    continue;
+                }
+
+                let key = format!("{key_prefix}{name}");
+
+                if entry.file_type().await?.is_dir() {
+                    dirs.push((entry.path(), format!("{key}/")));
+                } else if key.starts_with(prefix) {
+                    res.push(key);
+                }
+            }
+        }
+
+        res.sort();
+
+        Ok(res)
+    }
+
+    fn describe(&self) -> String {
+        format!("Filesystem {:?}", self.root)
+    }
+}
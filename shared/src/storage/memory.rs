@@ -0,0 +1,75 @@
+use super::{check_key, clamp_range, not_found, read_all, Blob, BlobStorage, BlobStream};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Range;
+use std::sync::RwLock;
+
+/// Keeps blobs in memory, for tests and benchmarks
+#[derive(Default)]
+pub struct MemoryStorage {
+    blobs: RwLock<BTreeMap<String, Bytes>>,
+}
+
+#[async_trait]
+impl BlobStorage for MemoryStorage {
+    async fn put(&self, key: &str, data: BlobStream) -> io::Result<u64> {
+        check_key(key)?;
+
+        let data = read_all(data, 0).await?;
+        let size = data.len() as u64;
+
+        self.blobs
+            .write()
+            .unwrap()
+            .insert(key.to_string(), Bytes::from(data));
+
+        Ok(size)
+    }
+
+    async fn get(&self, key: &str, range: Option<Range<u64>>) -> io::Result<Blob> {
+        let data = self
+            .blobs
+            .read()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| not_found(key))?;
+
+        let total = data.len() as u64;
+        let range = clamp_range(range, total);
+        let data = data.slice(range.start as usize..range.end as usize);
+
+        Ok(Blob {
+            size: data.len() as u64,
+            total,
+            stream: Box::pin(futures::stream::once(async move { Ok(data) })),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        self.blobs.write().unwrap().remove(key);
+
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> io::Result<bool> {
+        Ok(self.blobs.read().unwrap().contains_key(key))
+    }
+
+    async fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        Ok(self
+            .blobs
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|v| v.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    fn describe(&self) -> String {
+        "Memory".to_string()
+    }
+}
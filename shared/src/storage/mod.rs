@@ -0,0 +1,203 @@
+//! Blob storage of compressed game files
+//!
+//! Keys are `/` separated relative paths, e.g. `data/textures/sky.dds`
+mod fs;
+mod memory;
+mod s3;
+
+pub use fs::FsStorage;
+pub use memory::MemoryStorage;
+pub use s3::{S3Config, S3Storage};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use std::io;
+use std::ops::Range;
+use std::pin::Pin;
+
+pub type BlobStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+pub struct Blob {
+    /// Bytes in `stream`, the requested range length for ranged reads
+    pub size: u64,
+    /// Size of the whole blob
+    pub total: u64,
+    pub stream: BlobStream,
+}
+
+#[async_trait]
+pub trait BlobStorage: Send + Sync {
+    /// Replaces the blob, readers never see a partially written one. Returns written bytes
+    async fn put(&self, key: &str, data: BlobStream) -> io::Result<u64>;
+
+    /// `range` is end exclusive, a missing blob is [`io::ErrorKind::NotFound`]
+    async fn get(&self, key: &str, range: Option<Range<u64>>) -> io::Result<Blob>;
+
+    /// Deleting a missing blob is not an error
+    async fn delete(&self, key: &str) -> io::Result<()>;
+
+    async fn exists(&self, key: &str) -> io::Result<bool>;
+
+    /// Sorted keys starting with `prefix`
+    async fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+
+    /// Storage type and location for startup logs
+    fn describe(&self) -> String;
+
+    async fn put_bytes(&self, key: &str, data: Vec<u8>) -> io::Result<u64> {
+        self.put(key, bytes_stream(data)).await
+    }
+
+    async fn get_bytes(&self, key: &str) -> io::Result<Vec<u8>> {
+        let blob = self.get(key, None).await?;
+
+        read_all(blob.stream, blob.size).await
+    }
+
+    /// Replaces the blob `to` with a copy of `from`. Returns copied bytes
+    async fn copy(&self, from: &str, to: &str) -> io::Result<u64> {
+        let blob = self.get(from, None).await?;
+
+        self.put(to, blob.stream).await
+    }
+}
+
+pub fn bytes_stream(data: Vec<u8>) -> BlobStream {
+    Box::pin(futures::stream::once(async move { Ok(Bytes::from(data)) }))
+}
+
+pub async fn read_all(mut stream: BlobStream, size_hint: u64) -> io::Result<Vec<u8>> {
+    let mut res = Vec::with_capacity(size_hint as usize);
+
+    while let Some(chunk) = stream.next().await {
+        res.extend_from_slice(&chunk?);
+    }
+
+    Ok(res)
+}
+
+/// Rejects keys which can escape the storage root
+pub fn check_key(key: &str) -> io::Result<()> {
+    if key
+        .split('/')
+        .any(|v| v.is_empty() || v == "." || v == ".." || v.contains('\\'))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid blob key {key:?}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// `folder/sub` + `file` -> `folder/sub/file`, root folder is an empty path
+pub fn blob_key(folder_path: &str, file_name: &str) -> String {
+    if folder_path.is_empty() {
+        file_name.to_string()
+    } else {
+        format!("{folder_path}/{file_name}")
+    }
+}
+
+fn not_found(key: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("No blob {key}"))
+}
+
+/// Clamps `range` to the blob size
+fn clamp_range(range: Option<Range<u64>>, size: u64) -> Range<u64> {
+    match range {
+        Some(v) => v.start.min(size)..v.end.min(size).max(v.start.min(size)),
+        None => 0..size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Behaviour every storage has to follow
+    pub(super) async fn check_storage(storage: &dyn BlobStorage) {
+        let data: Vec<u8> = (0..100_000u32).map(|v| (v % 251) as u8).collect();
+
+        assert_eq!(
+            storage
+                .put_bytes("a/b/file.bin", data.clone())
+                .await
+                .unwrap(),
+            data.len() as u64
+        );
+        storage.put_bytes("a/other", vec![1, 2, 3]).await.unwrap();
+        storage.put_bytes("root", vec![]).await.unwrap();
+
+        assert_eq!(storage.get_bytes("a/b/file.bin").await.unwrap(), data);
+        assert_eq!(storage.get_bytes("root").await.unwrap(), Vec::<u8>::new());
+
+        let blob = storage.get("a/b/file.bin", Some(10..20)).await.unwrap();
+        assert_eq!(blob.size, 10);
+        assert_eq!(blob.total, data.len() as u64);
+        assert_eq!(read_all(blob.stream, 0).await.unwrap(), data[10..20]);
+
+        // Past the end, clients resuming a finished download
+        let blob = storage
+            .get("a/b/file.bin", Some(100_000..u64::MAX))
+            .await
+            .unwrap();
+        assert_eq!((blob.size, blob.total), (0, data.len() as u64));
+        let blob = storage.get("a/b/file.bin", Some(5..5)).await.unwrap();
+        assert_eq!((blob.size, blob.total), (0, data.len() as u64));
+        assert_eq!(storage.get("root", None).await.unwrap().total, 0);
+
+        assert!(storage.exists("a/other").await.unwrap());
+        assert!(!storage.exists("a/missing").await.unwrap());
+        assert_eq!(
+            storage.get("a/missing", None).await.err().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        assert_eq!(
+            storage.list("").await.unwrap(),
+            vec!["a/b/file.bin", "a/other", "root"]
+        );
+        assert_eq!(
+            storage.list("a/").await.unwrap(),
+            vec!["a/b/file.bin", "a/other"]
+        );
+
+        storage.put_bytes("a/other", vec![4]).await.unwrap();
+        assert_eq!(storage.get_bytes("a/other").await.unwrap(), vec![4]);
+
+        storage.delete("a/other").await.unwrap();
+        storage.delete("a/other").await.unwrap();
+        assert!(!storage.exists("a/other").await.unwrap());
+
+        storage.copy("a/b/file.bin", "c/copy.bin").await.unwrap();
+        assert_eq!(storage.get_bytes("c/copy.bin").await.unwrap(), data);
+        assert_eq!(
+            storage
+                .copy("a/missing", "c/copy.bin")
+                .await
+                .err()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+
+        assert!(storage.put_bytes("../escape", vec![1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn memory_storage() {
+        check_storage(&MemoryStorage::default()).await;
+    }
+
+    #[tokio::test]
+    async fn fs_storage() {
+        let dir = std::env::temp_dir().join(format!("fs_storage_{}", uuid::Uuid::new_v4()));
+
+        check_storage(&FsStorage::new(&dir)).await;
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// Envelope of a persisted document, `version` selects the migrations to run when it is loaded
+///
+/// Documents saved before envelopes were introduced are version 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}